@@ -1,9 +1,5 @@
-pub(super) mod client_action;
 pub(super) mod composition;
-pub(super) mod full_width;
-pub(super) mod input_mode;
 pub(super) mod ipc_service;
+pub(super) mod kana_input;
 pub(super) mod state;
-pub(super) mod text_util;
 pub(super) mod theme;
-pub(super) mod user_action;
@@ -1,54 +1,46 @@
 use std::cmp::{max, min};
 
 use crate::{
-    engine::user_action::UserAction,
     extension::VKeyExt as _,
     tsf::factory::{TextServiceFactory, TextServiceFactory_Impl},
 };
 
 use super::{
-    client_action::{ClientAction, SetSelectionType, SetTextType},
-    full_width::{to_fullwidth, to_halfwidth},
+    ipc_service::IPCService,
+    kana_input::{kana_for_key, DAKUTEN_TABLE},
+    state::{
+        candidate_page_size, live_conversion, remember_input_mode, IMEState, APP_CONFIG, KEYMAP,
+        ROMAJI_TABLE,
+    },
+};
+use azookey_engine::{
+    candidates::{Candidates, Clause},
+    client_action::{function_text, ClientAction, SetSelectionType},
+    composition::{Composition, CompositionState},
+    composition_engine::CompositionEngine,
+    full_width::to_fullwidth,
     input_mode::InputMode,
-    ipc_service::Candidates,
-    state::IMEState,
+    reconversion::find_target,
     text_util::{to_half_katakana, to_katakana},
-    user_action::{Function, Navigation},
+    undo_commit::LastCommit,
+    user_action::UserAction,
 };
 use windows::Win32::{
     Foundation::WPARAM,
+    System::Threading::GetCurrentThreadId,
     UI::{
-        Input::KeyboardAndMouse::VK_CONTROL,
+        Input::KeyboardAndMouse::{GetKeyboardState, ToUnicode, VK_CONTROL, VK_MENU, VK_SHIFT},
         TextServices::{ITfComposition, ITfCompositionSink_Impl, ITfContext},
+        WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId},
     },
 };
 
 use anyhow::{Context, Result};
-
-#[derive(Default, Clone, PartialEq, Debug)]
-pub enum CompositionState {
-    #[default]
-    None,
-    Composing,
-    Previewing,
-    Selecting,
-}
-
-#[derive(Default, Clone, Debug)]
-pub struct Composition {
-    pub preview: String, // text to be previewed
-    pub suffix: String,  // text to be appended after preview
-    pub raw_input: String,
-    pub raw_hiragana: String,
-
-    pub corresponding_count: i32, // corresponding count of the preview
-
-    pub selection_index: i32,
-    pub candidates: Candidates,
-
-    pub state: CompositionState,
-    pub tip_composition: Option<ITfComposition>,
-}
+use shared::{
+    keymap::{KeyCommand, KeyStroke, KeymapState},
+    romaji::RomajiTable,
+    InputStyle,
+};
 
 impl ITfCompositionSink_Impl for TextServiceFactory_Impl {
     #[macros::anyhow]
@@ -78,11 +70,6 @@ impl TextServiceFactory {
             return Ok(None);
         };
 
-        // check shortcut keys
-        if VK_CONTROL.is_pressed() {
-            return Ok(None);
-        }
-
         #[allow(clippy::let_and_return)]
         let (composition, mode) = {
             let text_service = self.borrow()?;
@@ -91,217 +78,53 @@ impl TextServiceFactory {
             (composition, mode)
         };
 
-        let action = UserAction::try_from(wparam.0)?;
-
-        let (transition, actions) = match composition.state {
-            CompositionState::None => match action {
-                UserAction::Input(char) if mode == InputMode::Kana => (
-                    CompositionState::Composing,
-                    vec![
-                        ClientAction::StartComposition,
-                        ClientAction::AppendText(char.to_string()),
-                    ],
-                ),
-                UserAction::Number(number) if mode == InputMode::Kana => (
-                    CompositionState::Composing,
-                    vec![
-                        ClientAction::StartComposition,
-                        ClientAction::AppendText(number.to_string()),
-                    ],
-                ),
-                UserAction::ToggleInputMode => (
-                    CompositionState::None,
-                    vec![match mode {
-                        InputMode::Kana => ClientAction::SetIMEMode(InputMode::Latin),
-                        InputMode::Latin => ClientAction::SetIMEMode(InputMode::Kana),
-                    }],
-                ),
-                _ => {
-                    return Ok(None);
-                }
-            },
-            CompositionState::Composing => match action {
-                UserAction::Input(char) => (
-                    CompositionState::Composing,
-                    vec![ClientAction::AppendText(char.to_string())],
-                ),
-                UserAction::Number(number) => (
-                    CompositionState::Composing,
-                    vec![ClientAction::AppendText(number.to_string())],
-                ),
-                UserAction::Backspace => {
-                    if composition.preview.chars().count() == 1 {
-                        (
-                            CompositionState::None,
-                            vec![ClientAction::RemoveText, ClientAction::EndComposition],
-                        )
-                    } else {
-                        (CompositionState::Composing, vec![ClientAction::RemoveText])
-                    }
-                }
-                UserAction::Enter => {
-                    if composition.suffix.is_empty() {
-                        (CompositionState::None, vec![ClientAction::EndComposition])
-                    } else {
-                        (
-                            CompositionState::Composing,
-                            vec![ClientAction::ShrinkText("".to_string())],
-                        )
-                    }
-                }
-                UserAction::Escape => (
-                    CompositionState::None,
-                    vec![ClientAction::RemoveText, ClientAction::EndComposition],
-                ),
-                UserAction::Navigation(direction) => match direction {
-                    Navigation::Right => (
-                        CompositionState::Composing,
-                        vec![ClientAction::MoveCursor(1)],
-                    ),
-                    Navigation::Left => (
-                        CompositionState::Composing,
-                        vec![ClientAction::MoveCursor(-1)],
-                    ),
-                    Navigation::Up => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetSelection(SetSelectionType::Up)],
-                    ),
-                    Navigation::Down => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetSelection(SetSelectionType::Down)],
-                    ),
-                },
-                UserAction::ToggleInputMode => (
-                    CompositionState::None,
-                    vec![
-                        ClientAction::EndComposition,
-                        ClientAction::SetIMEMode(InputMode::Latin),
-                    ],
-                ),
-                UserAction::Space | UserAction::Tab => (
-                    CompositionState::Previewing,
-                    vec![ClientAction::SetSelection(SetSelectionType::Down)],
-                ),
-                UserAction::Function(key) => match key {
-                    Function::Six => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::Hiragana)],
-                    ),
-                    Function::Seven => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::Katakana)],
-                    ),
-                    Function::Eight => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::HalfKatakana)],
-                    ),
-                    Function::Nine => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::FullLatin)],
-                    ),
-                    Function::Ten => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::HalfLatin)],
-                    ),
-                },
-                _ => {
-                    return Ok(None);
-                }
-            },
-            CompositionState::Previewing => match action {
-                UserAction::Input(char) => (
-                    CompositionState::Composing,
-                    vec![ClientAction::ShrinkText(char.to_string())],
-                ),
-                UserAction::Number(number) => (
-                    CompositionState::Composing,
-                    vec![ClientAction::ShrinkText(number.to_string())],
-                ),
-                UserAction::Backspace => {
-                    if composition.preview.chars().count() == 1 {
-                        (
-                            CompositionState::None,
-                            vec![ClientAction::RemoveText, ClientAction::EndComposition],
-                        )
-                    } else {
-                        (CompositionState::Composing, vec![ClientAction::RemoveText])
-                    }
-                }
-                UserAction::Enter => {
-                    if composition.suffix.is_empty() {
-                        (CompositionState::None, vec![ClientAction::EndComposition])
-                    } else {
-                        (
-                            CompositionState::Composing,
-                            vec![ClientAction::ShrinkText("".to_string())],
-                        )
-                    }
-                }
-                UserAction::Escape => (
-                    CompositionState::None,
-                    vec![ClientAction::RemoveText, ClientAction::EndComposition],
-                ),
-                UserAction::Navigation(direction) => match direction {
-                    Navigation::Right => (
-                        CompositionState::Composing,
-                        vec![ClientAction::MoveCursor(1)],
-                    ),
-                    Navigation::Left => (
-                        CompositionState::Composing,
-                        vec![ClientAction::MoveCursor(-1)],
-                    ),
-                    Navigation::Up => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetSelection(SetSelectionType::Up)],
-                    ),
-                    Navigation::Down => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetSelection(SetSelectionType::Down)],
-                    ),
-                },
-                UserAction::ToggleInputMode => (
-                    CompositionState::None,
-                    vec![
-                        ClientAction::EndComposition,
-                        ClientAction::SetIMEMode(InputMode::Latin),
-                    ],
-                ),
-                UserAction::Space | UserAction::Tab => (
-                    CompositionState::Previewing,
-                    vec![ClientAction::SetSelection(SetSelectionType::Down)],
-                ),
-                UserAction::Function(key) => match key {
-                    Function::Six => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::Hiragana)],
-                    ),
-                    Function::Seven => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::Katakana)],
-                    ),
-                    Function::Eight => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::HalfKatakana)],
-                    ),
-                    Function::Nine => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::FullLatin)],
-                    ),
-                    Function::Ten => (
-                        CompositionState::Previewing,
-                        vec![ClientAction::SetTextWithType(SetTextType::HalfLatin)],
-                    ),
-                },
-                _ => {
+        let state = match composition.state {
+            CompositionState::None => KeymapState::Precomposition,
+            _ => KeymapState::Composition,
+        };
+        let key = KeyStroke::new(
+            wparam.0 as u16,
+            VK_CONTROL.is_pressed(),
+            VK_SHIFT.is_pressed(),
+            VK_MENU.is_pressed(),
+        );
+        let command = KEYMAP
+            .read()
+            .ok()
+            .and_then(|keymap| keymap.lookup(state, &key));
+
+        let input_style = APP_CONFIG
+            .read()
+            .map(|config| config.input.style)
+            .unwrap_or_default();
+        let kana_input = matches!(
+            mode,
+            InputMode::Kana | InputMode::Katakana | InputMode::HalfKatakana
+        ) && input_style == InputStyle::Kana;
+
+        // the caret has been moved or the text has been edited since the commit
+        if command == Some(KeyCommand::UndoCommit) {
+            if let Some(last_commit) = &composition.last_commit {
+                let (preceding, selected) = self.surrounding_selection()?;
+                if !last_commit.is_before_caret(&preceding, &selected) {
+                    self.borrow()?.borrow_mut_composition()?.last_commit = None;
                     return Ok(None);
                 }
-            },
-            _ => {
-                return Ok(None);
             }
+        }
+
+        let action = match command {
+            Some(command) => UserAction::from(command),
+            // shortcut keys of the application
+            None if key.ctrl || key.alt => return Ok(None),
+            None => match kana_for_key(key.vk, key.shift) {
+                Some(kana) if kana_input => UserAction::Input(kana),
+                _ => key_action(wparam.0)?,
+            },
         };
 
-        Ok(Some((actions, transition)))
+        let engine = CompositionEngine::new(live_conversion(), candidate_page_size());
+        Ok(engine.process(&action, &composition, &mode))
     }
 
     #[tracing::instrument]
@@ -321,6 +144,40 @@ impl TextServiceFactory {
         Ok(true)
     }
 
+    // the mouse operations on the candidate window, see tsf::candidate_events
+    #[tracing::instrument]
+    pub fn handle_candidate_event(&self, action: UserAction) -> Result<()> {
+        // every client receives the events, and only the focused one owns the shown candidates
+        let foreground_thread = unsafe { GetWindowThreadProcessId(GetForegroundWindow(), None) };
+        if foreground_thread != unsafe { GetCurrentThreadId() } {
+            return Ok(());
+        }
+
+        if self.borrow()?.context.is_none() {
+            return Ok(());
+        }
+
+        self.process_action(&action)
+    }
+
+    // the actions which don't come from the keys, e.g. the mouse or the reconversion requested by the application
+    #[tracing::instrument]
+    pub fn process_action(&self, action: &UserAction) -> Result<()> {
+        let (composition, mode) = {
+            let text_service = self.borrow()?;
+            let composition = text_service.borrow_composition()?.clone();
+            let mode = IMEState::get()?.input_mode.clone();
+            (composition, mode)
+        };
+
+        let engine = CompositionEngine::new(live_conversion(), candidate_page_size());
+        if let Some((actions, transition)) = engine.process(action, &composition, &mode) {
+            self.handle_action(&actions, transition)?;
+        }
+
+        Ok(())
+    }
+
     #[tracing::instrument]
     pub fn handle_action(
         &self,
@@ -339,94 +196,230 @@ impl TextServiceFactory {
         let mut suffix = composition.suffix.clone();
         let mut raw_input = composition.raw_input.clone();
         let mut raw_hiragana = composition.raw_hiragana.clone();
+        let mut pending = composition.pending.clone();
         let mut corresponding_count = composition.corresponding_count.clone();
+        let mut cursor = composition.cursor;
+        let mut clauses = composition.clauses.clone();
+        let mut clause_index = composition.clause_index;
         let mut candidates = composition.candidates.clone();
         let mut selection_index = composition.selection_index;
+        // only the batch which commits the composition keeps it, e.g. Enter but not Escape
+        let mut last_commit = None;
+        let mut text_type = None;
         let mut ipc_service = IMEState::get()?
             .ipc_service
             .clone()
             .context("ipc_service is None")?;
         let mut transition = transition;
+        let romaji_table = match ROMAJI_TABLE.read() {
+            Ok(guard) => guard,
+            Err(e) => anyhow::bail!("Failed to lock romaji table: {:?}", e),
+        };
+        let mut input_style = APP_CONFIG
+            .read()
+            .map(|config| config.input.style)
+            .unwrap_or_default();
+        let live_conversion = live_conversion();
 
         self.update_context(&preview)?;
 
         for action in actions {
+            // the pending romaji is converted before the composition is converted or committed, e.g. "n" -> "ん"
+            if !pending.is_empty()
+                && !matches!(
+                    action,
+                    ClientAction::StartComposition
+                        | ClientAction::EndComposition
+                        | ClientAction::AppendText(_)
+                        | ClientAction::RemoveText
+                        | ClientAction::SetIMEMode(_)
+                )
+            {
+                let flushed = conversion_table(input_style, &romaji_table).flush(&pending);
+                if flushed != pending {
+                    candidates = replace_pending(
+                        &mut ipc_service,
+                        &mut raw_input,
+                        cursor,
+                        &pending,
+                        "",
+                        &flushed,
+                        "",
+                    )?;
+                    candidates = direct_candidates(&mode, live_conversion, candidates, &raw_input);
+                    selection_index = 0;
+
+                    let (text, sub_text, count) = candidates.candidate(selection_index);
+                    let hiragana = candidates.hiragana.clone();
+                    corresponding_count = count;
+                    cursor = candidates.cursor;
+
+                    preview = text.clone();
+                    suffix = sub_text.clone();
+                    raw_hiragana = hiragana.clone();
+
+                    self.set_text(
+                        &text,
+                        &sub_text,
+                        display_cursor(&mode, live_conversion, &text, &sub_text, &hiragana, cursor),
+                    )?;
+                    ipc_service.set_candidates(&candidates)?;
+                    ipc_service.set_selection(selection_index as i32)?;
+                }
+                pending.clear();
+            }
+
             match action {
                 ClientAction::StartComposition => {
                     self.start_composition()?;
                     self.update_pos()?;
-                    ipc_service.show_window()?;
+                    // the direct input modes and the classic conversion show the window only while converting
+                    if !mode.is_direct() && live_conversion {
+                        ipc_service.show_window()?;
+                    }
+                }
+                ClientAction::CommitCandidate => {
+                    // the text typed in the direct input modes is not learned
+                    if !(mode.is_direct() && clauses.is_empty()) {
+                        commit_candidate(
+                            &mut ipc_service,
+                            &preview,
+                            &suffix,
+                            &raw_hiragana,
+                            &clauses,
+                            corresponding_count,
+                        )?;
+                    }
+
+                    last_commit = Some(LastCommit {
+                        preview: preview.clone(),
+                        suffix: suffix.clone(),
+                        raw_input: raw_input.clone(),
+                        raw_hiragana: raw_hiragana.clone(),
+                        corresponding_count,
+                    });
                 }
                 ClientAction::EndComposition => {
+                    clauses.clear();
                     self.end_composition()?;
                     selection_index = 0;
                     corresponding_count = 0;
+                    cursor = 0;
                     preview.clear();
                     suffix.clear();
                     raw_input.clear();
                     raw_hiragana.clear();
+                    pending.clear();
+                    candidates.predictions.clear();
                     ipc_service.hide_window()?;
-                    ipc_service.set_candidates(vec![])?;
+                    ipc_service.set_candidates(&Candidates::default())?;
                     ipc_service.clear_text()?;
                 }
                 ClientAction::AppendText(text) => {
-                    raw_input.push_str(&text);
-
-                    let text = match mode {
-                        InputMode::Kana => to_fullwidth(text, false),
-                        InputMode::Latin => text.to_string(),
-                    };
-
-                    candidates = ipc_service.append_text(text.clone())?;
-                    let text = candidates.texts[selection_index as usize].clone();
-                    let sub_text = candidates.sub_texts[selection_index as usize].clone();
+                    clauses.clear();
+                    candidates = append_text(
+                        &mut ipc_service,
+                        conversion_table(input_style, &romaji_table),
+                        &mode,
+                        text,
+                        &mut raw_input,
+                        &mut pending,
+                        cursor,
+                    )?;
+                    candidates = direct_candidates(&mode, live_conversion, candidates, &raw_input);
+                    let (text, sub_text, count) = candidates.candidate(selection_index);
                     let hiragana = candidates.hiragana.clone();
 
-                    corresponding_count = candidates.corresponding_count[selection_index as usize];
+                    corresponding_count = count;
+                    cursor = candidates.cursor;
 
                     preview = text.clone();
                     suffix = sub_text.clone();
                     raw_hiragana = hiragana.clone();
 
-                    self.set_text(&text, &sub_text)?;
-                    ipc_service.set_candidates(candidates.texts.clone())?;
+                    self.set_text(
+                        &text,
+                        &sub_text,
+                        display_cursor(&mode, live_conversion, &text, &sub_text, &hiragana, cursor),
+                    )?;
+                    ipc_service.set_candidates(&candidates)?;
                     ipc_service.set_selection(selection_index as i32)?;
                 }
                 ClientAction::RemoveText => {
+                    clauses.clear();
                     candidates = ipc_service.remove_text()?;
-                    let empty = "".to_string();
-                    let text = candidates
-                        .texts
-                        .get(selection_index as usize)
-                        .cloned()
-                        .unwrap_or(empty.clone());
-                    let sub_text = candidates
-                        .sub_texts
-                        .get(selection_index as usize)
-                        .cloned()
-                        .unwrap_or(empty.clone());
+
+                    // the character before the cursor is removed
+                    if (candidates.cursor as usize) < raw_input.len() {
+                        raw_input.remove(candidates.cursor as usize);
+                    }
+                    pending.pop();
+                    candidates = direct_candidates(&mode, live_conversion, candidates, &raw_input);
+
+                    let (text, sub_text, count) = candidates.candidate(selection_index);
                     let hiragana = candidates.hiragana.clone();
-                    corresponding_count = candidates
-                        .corresponding_count
-                        .get(selection_index as usize)
-                        .cloned()
-                        .unwrap_or(0);
-
-                    raw_input = raw_input
-                        .chars()
-                        .take(corresponding_count as usize)
-                        .collect();
+                    corresponding_count = count;
+                    cursor = candidates.cursor;
+
                     preview = text.clone();
                     suffix = sub_text.clone();
                     raw_hiragana = hiragana.clone();
 
-                    self.set_text(&text, &sub_text)?;
-                    ipc_service.set_candidates(candidates.texts.clone())?;
+                    self.set_text(
+                        &text,
+                        &sub_text,
+                        display_cursor(&mode, live_conversion, &text, &sub_text, &hiragana, cursor),
+                    )?;
+                    ipc_service.set_candidates(&candidates)?;
                     ipc_service.set_selection(selection_index as i32)?;
                 }
-                ClientAction::MoveCursor(_offset) => {
-                    // TODO: I'll use azookey-kkc's composingText
-                    // self.set_cursor(offset)?;
+                ClientAction::MoveCursor(offset) => {
+                    clauses.clear();
+                    // the text before the cursor is converted, and the rest is shown as hiragana
+                    candidates = ipc_service.move_cursor(*offset)?;
+                    candidates = direct_candidates(&mode, live_conversion, candidates, &raw_input);
+                    selection_index = 0;
+
+                    let (text, sub_text, count) = candidates.candidate(selection_index);
+                    let hiragana = candidates.hiragana.clone();
+                    corresponding_count = count;
+                    cursor = candidates.cursor;
+
+                    preview = text.clone();
+                    suffix = sub_text.clone();
+                    raw_hiragana = hiragana.clone();
+
+                    self.set_text(
+                        &text,
+                        &sub_text,
+                        display_cursor(&mode, live_conversion, &text, &sub_text, &hiragana, cursor),
+                    )?;
+                    ipc_service.set_candidates(&candidates)?;
+                    ipc_service.set_selection(selection_index as i32)?;
+                }
+                ClientAction::ShowUnconverted => {
+                    clauses.clear();
+                    // the conversion of the server is reset, and the hiragana is shown like the direct input modes
+                    candidates = ipc_service.move_cursor(0)?;
+                    candidates = direct_candidates(&mode, false, candidates, &raw_input);
+                    selection_index = 0;
+
+                    let (text, sub_text, count) = candidates.candidate(selection_index);
+                    let hiragana = candidates.hiragana.clone();
+                    corresponding_count = count;
+                    cursor = candidates.cursor;
+
+                    preview = text.clone();
+                    suffix = sub_text.clone();
+                    raw_hiragana = hiragana.clone();
+
+                    self.set_text(
+                        &text,
+                        &sub_text,
+                        display_cursor(&mode, false, &text, &sub_text, &hiragana, cursor),
+                    )?;
+                    ipc_service.set_candidates(&candidates)?;
+                    ipc_service.set_selection(selection_index as i32)?;
                 }
                 ClientAction::SetIMEMode(mode) => {
                     self.start_composition()?;
@@ -435,100 +428,318 @@ impl TextServiceFactory {
 
                     let mut ime_state = IMEState::get()?;
                     ime_state.input_mode = mode.clone();
+                    remember_input_mode(mode);
 
                     // update the language bar
                     self.update_lang_bar()?;
 
-                    let mode = match mode {
-                        InputMode::Latin => "A",
-                        InputMode::Kana => "あ",
-                    };
-
-                    ipc_service.set_input_mode(mode)?;
+                    ipc_service.set_input_mode(mode.indicator())?;
 
                     selection_index = 0;
                     corresponding_count = 0;
+                    cursor = 0;
+                    clauses.clear();
                     preview.clear();
                     suffix.clear();
                     raw_input.clear();
                     raw_hiragana.clear();
+                    pending.clear();
+                    candidates.predictions.clear();
                     ipc_service.clear_text()?;
                 }
-                ClientAction::SetSelection(selection) => {
-                    let candidates = {
-                        let text_service = self.borrow()?;
-                        let composition = text_service.borrow_composition()?.clone();
-                        let candidates = composition.candidates.clone();
-                        candidates
+                ClientAction::ToggleInputStyle => {
+                    input_style = match input_style {
+                        InputStyle::Romaji => InputStyle::Kana,
+                        InputStyle::Kana => InputStyle::Romaji,
                     };
 
-                    let texts = candidates.texts.clone();
-                    let sub_texts = candidates.sub_texts.clone();
+                    // the toggle is kept in memory, since writing the settings file on a key press can fail
+                    // the style in the settings is used again when the settings are changed
+                    if let Ok(mut app_config) = APP_CONFIG.write() {
+                        app_config.input.style = input_style;
+                    }
+                }
+                ClientAction::SetSelection(selection) => {
+                    // start the clause conversion from the first clause
+                    if clauses.is_empty() {
+                        if mode.is_direct() || !live_conversion {
+                            ipc_service.show_window()?;
+                        }
+                        clauses = ipc_service.get_clauses()?;
+                        clause_index = 0;
+                        candidates = clause_candidates(&mut ipc_service, &clauses, clause_index)?;
+                        selection_index = surface_index(&candidates, &clauses, clause_index);
+                    }
 
+                    let last_index = candidates.texts.len() as i32 - 1;
                     selection_index = match selection {
                         SetSelectionType::Up => max(0, selection_index - 1),
-                        SetSelectionType::Down => min(texts.len() as i32 - 1, selection_index + 1),
-                        SetSelectionType::Number(number) => *number,
+                        SetSelectionType::Down => min(last_index, selection_index + 1),
+                        SetSelectionType::Number(number) => max(0, min(last_index, *number)),
                     };
 
-                    ipc_service.set_selection(selection_index as i32)?;
-                    let text = texts[selection_index as usize].clone();
-                    let sub_text = sub_texts[selection_index as usize].clone();
-                    let hiragana = candidates.hiragana.clone();
-                    corresponding_count = candidates.corresponding_count[selection_index as usize];
+                    (preview, suffix, corresponding_count) = self.apply_candidate(
+                        &mut ipc_service,
+                        candidates.candidate(selection_index),
+                        &mut clauses,
+                        clause_index,
+                        &candidates,
+                        selection_index,
+                    )?;
+                }
+                ClientAction::FocusClause(offset) => {
+                    if clauses.is_empty() {
+                        clauses = ipc_service.get_clauses()?;
+                        clause_index = 0;
+                    } else {
+                        clause_index = (clause_index + offset).clamp(0, clauses.len() as i32 - 1);
+                    }
 
-                    preview = text.clone();
-                    suffix = sub_text.clone();
-                    raw_hiragana = hiragana.clone();
+                    candidates = clause_candidates(&mut ipc_service, &clauses, clause_index)?;
+                    selection_index = surface_index(&candidates, &clauses, clause_index);
 
-                    self.set_text(&text, &sub_text)?;
+                    (preview, suffix, corresponding_count) = self.apply_candidate(
+                        &mut ipc_service,
+                        candidates.candidate(selection_index),
+                        &mut clauses,
+                        clause_index,
+                        &candidates,
+                        selection_index,
+                    )?;
+                }
+                ClientAction::ResizeClause(offset) => {
+                    if clauses.is_empty() {
+                        clause_index = 0;
+                    }
+
+                    // the clauses before the focused one keep the chosen candidates
+                    let mut resized = ipc_service.resize_clause(clause_index, *offset)?;
+                    for (clause, previous) in
+                        resized.iter_mut().zip(&clauses).take(clause_index as usize)
+                    {
+                        clause.surface = previous.surface.clone();
+                    }
+                    clauses = resized;
+
+                    candidates = clause_candidates(&mut ipc_service, &clauses, clause_index)?;
+                    selection_index = 0;
+
+                    (preview, suffix, corresponding_count) = self.apply_candidate(
+                        &mut ipc_service,
+                        candidates.candidate(selection_index),
+                        &mut clauses,
+                        clause_index,
+                        &candidates,
+                        selection_index,
+                    )?;
                 }
                 ClientAction::ShrinkText(text) => {
+                    // the preview is committed
+                    commit_candidate(
+                        &mut ipc_service,
+                        &preview,
+                        &suffix,
+                        &raw_hiragana,
+                        &clauses,
+                        corresponding_count,
+                    )?;
+                    clauses.clear();
                     // shrink text
-                    raw_input.push_str(&text);
-                    raw_input = raw_input
-                        .chars()
-                        .skip(corresponding_count as usize)
-                        .collect();
-
-                    ipc_service.shrink_text(corresponding_count.clone())?;
-                    let text = match mode {
-                        InputMode::Kana => to_fullwidth(text, false),
-                        InputMode::Latin => text.to_string(),
-                    };
-                    candidates = ipc_service.append_text(text)?;
+                    raw_input.drain(..min(corresponding_count as usize, raw_input.len()));
+
+                    let shrunk = ipc_service.shrink_text(corresponding_count.clone())?;
+                    candidates = append_text(
+                        &mut ipc_service,
+                        conversion_table(input_style, &romaji_table),
+                        &mode,
+                        text,
+                        &mut raw_input,
+                        &mut pending,
+                        shrunk.cursor,
+                    )?;
+                    candidates = direct_candidates(&mode, live_conversion, candidates, &raw_input);
                     selection_index = 0;
 
-                    let text = candidates.texts[selection_index as usize].clone();
-                    let sub_text = candidates.sub_texts[selection_index as usize].clone();
+                    let (text, sub_text, count) = candidates.candidate(selection_index);
                     let hiragana = candidates.hiragana.clone();
                     self.shift_start(&preview, &text)?;
 
-                    corresponding_count = candidates.corresponding_count[selection_index as usize];
+                    corresponding_count = count;
+                    cursor = candidates.cursor;
                     preview = text.clone();
                     suffix = sub_text.clone();
                     raw_hiragana = hiragana.clone();
 
-                    ipc_service.set_candidates(candidates.texts.clone())?;
+                    ipc_service.set_candidates(&candidates)?;
                     ipc_service.set_selection(selection_index as i32)?;
                     self.update_pos()?;
 
                     transition = CompositionState::Composing;
                 }
-                ClientAction::SetTextWithType(set_type) => {
-                    let text = match set_type {
-                        SetTextType::Hiragana => raw_hiragana.clone(),
-                        SetTextType::Katakana => to_katakana(&raw_hiragana),
-                        SetTextType::HalfKatakana => to_half_katakana(&raw_hiragana),
-                        SetTextType::FullLatin => to_fullwidth(&raw_input, true),
-                        SetTextType::HalfLatin => to_halfwidth(&raw_input),
+                ClientAction::AcceptPrediction => {
+                    if let Some(prediction) = candidates.predictions.first().cloned() {
+                        clauses.clear();
+
+                        (preview, suffix, corresponding_count) = self.apply_candidate(
+                            &mut ipc_service,
+                            (
+                                prediction.text,
+                                prediction.sub_text,
+                                prediction.corresponding_count,
+                            ),
+                            &mut clauses,
+                            clause_index,
+                            &candidates,
+                            selection_index,
+                        )?;
+                    }
+                }
+                ClientAction::ChooseCandidate(index) => {
+                    if candidates.texts.get(*index as usize).is_some() {
+                        clauses.clear();
+                        selection_index = *index;
+
+                        (preview, suffix, corresponding_count) = self.apply_candidate(
+                            &mut ipc_service,
+                            candidates.candidate(selection_index),
+                            &mut clauses,
+                            clause_index,
+                            &candidates,
+                            selection_index,
+                        )?;
+                    }
+                }
+                ClientAction::Reconvert => {
+                    let (preceding, selected) = self.surrounding_selection()?;
+                    let target = {
+                        let history = &IMEState::get()?.commit_history;
+                        find_target(&preceding, &selected, history)
+                    };
+
+                    // the server looks up the reading of the text which was not committed by this client
+                    let reconversion = target.and_then(|target| {
+                        let reading = match target.reading.clone() {
+                            Some(reading) => reading,
+                            None => ipc_service.reverse_lookup(target.surface.clone()).ok()?,
+                        };
+                        (!reading.is_empty()).then_some((target, reading))
+                    });
+                    let Some((target, reading)) = reconversion else {
+                        // nothing to reconvert, the following actions are skipped
+                        transition = CompositionState::None;
+                        break;
+                    };
+
+                    self.select_preceding(target.preceding_count)?;
+                    self.start_composition()?;
+                    self.update_pos()?;
+                    ipc_service.show_window()?;
+
+                    // the reading is composed as if it was typed
+                    ipc_service.clear_text()?;
+                    candidates = ipc_service.append_text(reading.clone())?;
+                    raw_input = reading.chars().map(|c| c.to_string()).collect();
+                    raw_hiragana = candidates.hiragana.clone();
+                    cursor = candidates.cursor;
+                    clauses.clear();
+                    selection_index = 0;
+
+                    (preview, suffix, corresponding_count) = self.apply_candidate(
+                        &mut ipc_service,
+                        (
+                            target.surface.clone(),
+                            String::new(),
+                            reading.chars().count() as i32,
+                        ),
+                        &mut clauses,
+                        clause_index,
+                        &candidates,
+                        selection_index,
+                    )?;
+                }
+                ClientAction::UndoCommit => {
+                    let Some(committed) = composition.last_commit.clone() else {
+                        transition = CompositionState::None;
+                        break;
                     };
 
-                    self.set_text(&text, "")?;
+                    // the committed text is selected, so that the composition covers it
+                    self.select_preceding(committed.text().chars().count() as i32)?;
+                    self.start_composition()?;
+                    self.update_pos()?;
+                    if !mode.is_direct() && live_conversion {
+                        ipc_service.show_window()?;
+                    }
+
+                    // the reading is composed again, and the committed candidate is previewed
+                    ipc_service.clear_text()?;
+                    candidates = ipc_service.append_text(committed.raw_hiragana.clone())?;
+                    candidates =
+                        direct_candidates(&mode, live_conversion, candidates, &committed.raw_input);
+                    selection_index = candidates
+                        .texts
+                        .iter()
+                        .position(|text| *text == committed.preview)
+                        .unwrap_or(0) as i32;
+
+                    // the shown candidates are of the whole text, and the engine chooses from them until the clauses are fetched
+                    clauses.clear();
+                    raw_input = committed.raw_input;
+                    raw_hiragana = candidates.hiragana.clone();
+                    cursor = candidates.cursor;
+
+                    (preview, suffix, corresponding_count) = self.apply_candidate(
+                        &mut ipc_service,
+                        (
+                            committed.preview,
+                            committed.suffix,
+                            committed.corresponding_count,
+                        ),
+                        &mut clauses,
+                        clause_index,
+                        &candidates,
+                        selection_index,
+                    )?;
+                }
+                ClientAction::SetTextWithType(set_type) => {
+                    clauses.clear();
+                    // pressing the same key again converts the text in another way
+                    let count = composition.text_type_count(set_type);
+                    text_type = Some((set_type.clone(), count));
+
+                    let text = function_text(set_type, count, &raw_hiragana, &raw_input.concat());
+
+                    // the whole text is converted, so that Enter commits all the typed keys
+                    preview = text.clone();
+                    suffix.clear();
+                    corresponding_count = raw_input.len() as i32;
+
+                    self.set_text(&text, "", text.chars().count() as i32)?;
                 }
             }
         }
 
+        // back to the typed text from the conversion, e.g. Escape in Selecting
+        if (mode.is_direct() || !live_conversion)
+            && transition == CompositionState::Composing
+            && matches!(
+                composition.state,
+                CompositionState::Previewing | CompositionState::Selecting
+            )
+        {
+            ipc_service.hide_window()?;
+        }
+
+        if candidates.predictions != composition.candidates.predictions {
+            ipc_service.set_predictions(
+                candidates
+                    .predictions
+                    .iter()
+                    .map(|p| p.text.clone())
+                    .collect(),
+            )?;
+        }
+
         let text_service = self.borrow()?;
         let mut composition = text_service.borrow_mut_composition()?;
 
@@ -537,10 +748,317 @@ impl TextServiceFactory {
         composition.selection_index = selection_index;
         composition.raw_input = raw_input.clone();
         composition.raw_hiragana = raw_hiragana.clone();
+        composition.pending = pending;
         composition.candidates = candidates;
         composition.suffix = suffix.clone();
         composition.corresponding_count = corresponding_count;
+        composition.cursor = cursor;
+        composition.clauses = clauses;
+        composition.clause_index = clause_index;
+        composition.last_commit = last_commit;
+        composition.text_type = text_type;
 
         Ok(())
     }
+
+    // shows the chosen candidate, the focused clause takes it while converting by clauses
+    // returns the preview, the suffix and the corresponding count of the whole composition
+    fn apply_candidate(
+        &self,
+        ipc_service: &mut IPCService,
+        (text, sub_text, count): (String, String, i32),
+        clauses: &mut [Clause],
+        clause_index: i32,
+        candidates: &Candidates,
+        selection_index: i32,
+    ) -> Result<(String, String, i32)> {
+        let applied = match clauses.get_mut(clause_index as usize) {
+            Some(clause) => {
+                clause.surface = text;
+                self.set_clauses(clauses, clause_index)?;
+
+                let (preview, corresponding_count) = join_clauses(clauses);
+                (preview, String::new(), corresponding_count)
+            }
+            None => {
+                self.set_text(&text, &sub_text, text.chars().count() as i32)?;
+                (text, sub_text, count)
+            }
+        };
+
+        ipc_service.set_candidates(candidates)?;
+        ipc_service.set_selection(selection_index)?;
+
+        Ok(applied)
+    }
+}
+
+// the kana input uses the table to combine the dakuten with the previous kana
+fn conversion_table(style: InputStyle, romaji_table: &RomajiTable) -> &RomajiTable {
+    match style {
+        InputStyle::Romaji => romaji_table,
+        InputStyle::Kana => &DAKUTEN_TABLE,
+    }
+}
+
+// sends the typed text to the server
+// in kana mode, the romaji is converted with the table and the pending keys are sent as fullwidth alphabets,
+// since the server doesn't convert them
+fn append_text(
+    ipc_service: &mut IPCService,
+    romaji_table: &RomajiTable,
+    mode: &InputMode,
+    text: &str,
+    raw_input: &mut Vec<String>,
+    pending: &mut String,
+    cursor: i32,
+) -> Result<Candidates> {
+    if matches!(mode, InputMode::Latin | InputMode::FullLatin) {
+        let index = min(cursor as usize, raw_input.len());
+        raw_input.splice(index..index, text.chars().map(|c| c.to_string()));
+        return ipc_service.append_text(text.to_string());
+    }
+
+    let mut candidates = None;
+    let mut cursor = cursor;
+    for c in text.chars() {
+        let (output, next) = romaji_table.feed(pending, c);
+        let result = replace_pending(
+            ipc_service,
+            raw_input,
+            cursor,
+            pending,
+            &c.to_string(),
+            &output,
+            &next,
+        )?;
+
+        cursor = result.cursor;
+        *pending = next;
+        candidates = Some(result);
+    }
+
+    match candidates {
+        Some(candidates) => Ok(candidates),
+        None => ipc_service.append_text(String::new()),
+    }
+}
+
+// replaces the pending romaji before the cursor with the converted text and the new pending romaji
+// the keys of the new pending romaji are kept for its characters, and the rest are given to the converted text
+fn replace_pending(
+    ipc_service: &mut IPCService,
+    raw_input: &mut Vec<String>,
+    cursor: i32,
+    pending: &str,
+    key: &str,
+    output: &str,
+    next: &str,
+) -> Result<Candidates> {
+    let pending_count = pending.chars().count();
+    let end = min(cursor as usize, raw_input.len());
+    let start = end.saturating_sub(pending_count);
+
+    let keys: String = raw_input.drain(start..end).collect::<String>() + key;
+
+    let output_key_count = keys.chars().count().saturating_sub(next.chars().count());
+    let output_keys: String = keys.chars().take(output_key_count).collect();
+    let keys = output
+        .chars()
+        .enumerate()
+        .map(|(index, _)| {
+            if index == 0 {
+                output_keys.clone()
+            } else {
+                String::new()
+            }
+        })
+        .chain(next.chars().map(|c| c.to_string()));
+    raw_input.splice(start..start, keys);
+
+    let text: String = output
+        .chars()
+        .chain(next.chars())
+        .map(|c| to_fullwidth(&c.to_string(), c.is_ascii_alphabetic()))
+        .collect();
+    ipc_service.replace_text(pending_count as u32, text)
+}
+
+// the typed text in the direct input modes, the text before the cursor is the preview and the rest is the suffix
+// without the live conversion, the hiragana is shown in the same way
+// the predictions are dropped, since the window is not shown while composing
+fn direct_candidates(
+    mode: &InputMode,
+    live_conversion: bool,
+    candidates: Candidates,
+    raw_input: &[String],
+) -> Candidates {
+    let convert = |hiragana: &str, raw_input: &[String]| match mode {
+        InputMode::Katakana => to_katakana(hiragana),
+        InputMode::HalfKatakana => to_half_katakana(hiragana),
+        InputMode::FullLatin => to_fullwidth(&raw_input.concat(), true),
+        InputMode::Latin | InputMode::Kana => hiragana.to_string(),
+    };
+
+    if !mode.is_direct() && live_conversion {
+        return candidates;
+    }
+
+    let cursor = min(candidates.cursor.max(0) as usize, raw_input.len());
+    let before: String = candidates.hiragana.chars().take(cursor).collect();
+    let after: String = candidates.hiragana.chars().skip(cursor).collect();
+
+    Candidates {
+        texts: vec![convert(&before, &raw_input[..cursor])],
+        sub_texts: vec![convert(&after, &raw_input[cursor..])],
+        corresponding_count: vec![cursor as i32],
+        predictions: vec![],
+        readings: vec![before],
+        sources: vec![],
+        annotations: vec![],
+        scores: vec![],
+        ..candidates
+    }
+}
+
+// caret position in the displayed text (preview + suffix)
+// the suffix is the unconverted rest of the hiragana, so the caret after the preview is placed in the suffix
+// in the direct input modes and without the live conversion, the caret is always after the preview
+fn display_cursor(
+    mode: &InputMode,
+    live_conversion: bool,
+    preview: &str,
+    suffix: &str,
+    hiragana: &str,
+    cursor: i32,
+) -> i32 {
+    if mode.is_direct() || !live_conversion {
+        return preview.chars().count() as i32;
+    }
+
+    let converted_count = hiragana
+        .chars()
+        .count()
+        .saturating_sub(suffix.chars().count()) as i32;
+
+    preview.chars().count() as i32 + max(0, cursor - converted_count)
+}
+
+// candidates of the clause, the current surface is used if the server returns nothing
+fn clause_candidates(
+    ipc_service: &mut IPCService,
+    clauses: &[Clause],
+    index: i32,
+) -> Result<Candidates> {
+    let mut candidates = ipc_service.get_clause_candidates(index)?;
+
+    if candidates.texts.is_empty() {
+        let clause = clauses.get(index as usize).cloned().unwrap_or_default();
+        candidates.texts = vec![clause.surface];
+        candidates.sub_texts = vec!["".to_string()];
+        candidates.corresponding_count = vec![clause.corresponding_count];
+        candidates.readings = vec![clause.reading];
+    }
+
+    Ok(candidates)
+}
+
+// index of the candidate which is already chosen for the clause
+fn surface_index(candidates: &Candidates, clauses: &[Clause], index: i32) -> i32 {
+    clauses
+        .get(index as usize)
+        .and_then(|clause| candidates.texts.iter().position(|t| *t == clause.surface))
+        .unwrap_or(0) as i32
+}
+
+// text to be committed and its corresponding count
+fn join_clauses(clauses: &[Clause]) -> (String, i32) {
+    let text = clauses.iter().map(|c| c.surface.as_str()).collect();
+    let corresponding_count = clauses.iter().map(|c| c.corresponding_count).sum();
+
+    (text, corresponding_count)
+}
+
+// report the committed text to the server so that it can be learned
+// each clause is reported separately, since the candidate is chosen per clause
+fn commit_candidate(
+    ipc_service: &mut IPCService,
+    preview: &str,
+    suffix: &str,
+    hiragana: &str,
+    clauses: &[Clause],
+    corresponding_count: i32,
+) -> Result<()> {
+    if !clauses.is_empty() {
+        for clause in clauses {
+            IMEState::get()?
+                .commit_history
+                .push(&clause.surface, &clause.reading);
+            ipc_service.commit_candidate(
+                clause.reading.clone(),
+                clause.surface.clone(),
+                clause.corresponding_count,
+            )?;
+        }
+        return Ok(());
+    }
+
+    if preview.is_empty() {
+        return Ok(());
+    }
+
+    // the suffix is the unconverted rest of the hiragana
+    let reading_count = hiragana
+        .chars()
+        .count()
+        .saturating_sub(suffix.chars().count());
+    let reading: String = hiragana.chars().take(reading_count).collect();
+
+    IMEState::get()?.commit_history.push(preview, &reading);
+    ipc_service.commit_candidate(reading, preview.to_string(), corresponding_count)
+}
+
+// keys which are not bound in the keymap
+fn key_action(key_code: usize) -> Result<UserAction> {
+    let action = match key_code {
+        0x30..=0x39 | 0x60..=0x69 if !VK_SHIFT.is_pressed() => {
+            match key_code {
+                0x30 | 0x60 => UserAction::Number(0), // VK_0, VK_NUMPAD0
+                0x31 | 0x61 => UserAction::Number(1), // VK_1, VK_NUMPAD1
+                0x32 | 0x62 => UserAction::Number(2), // VK_2, VK_NUMPAD2
+                0x33 | 0x63 => UserAction::Number(3), // VK_3, VK_NUMPAD3
+                0x34 | 0x64 => UserAction::Number(4), // VK_4, VK_NUMPAD4
+                0x35 | 0x65 => UserAction::Number(5), // VK_5, VK_NUMPAD5
+                0x36 | 0x66 => UserAction::Number(6), // VK_6, VK_NUMPAD6
+                0x37 | 0x67 => UserAction::Number(7), // VK_7, VK_NUMPAD7
+                0x38 | 0x68 => UserAction::Number(8), // VK_8, VK_NUMPAD8
+                0x39 | 0x69 => UserAction::Number(9), // VK_9, VK_NUMPAD9
+                _ => UserAction::Unknown,
+            }
+        }
+
+        _ => {
+            let key_state = {
+                let mut key_state = [0u8; 256];
+                unsafe {
+                    GetKeyboardState(&mut key_state)?;
+                }
+                key_state
+            };
+            let unicode = {
+                let mut unicode = [0u16; 1];
+                unsafe { ToUnicode(key_code as u32, 0, Some(&key_state), &mut unicode, 0) };
+                unicode[0]
+            };
+
+            // control characters and space are handled by the keymap
+            if unicode > 0x20 {
+                UserAction::Input(char::from_u32(unicode as u32).context("Invalid char")?)
+            } else {
+                UserAction::Unknown
+            }
+        }
+    };
+
+    Ok(action)
 }
@@ -1,35 +0,0 @@
-use crate::tsf::factory::TextServiceFactory;
-
-use windows::{
-    core::Interface,
-    Win32::UI::TextServices::{ITfLangBarItemButton, ITfLangBarItemMgr},
-};
-
-use anyhow::Result;
-
-#[derive(Default, Clone, PartialEq, Debug)]
-pub enum InputMode {
-    #[default]
-    Latin,
-    Kana,
-}
-
-impl TextServiceFactory {
-    pub fn update_lang_bar(&self) -> Result<()> {
-        // change the icon of the language bar item
-        let text_service = self.borrow()?;
-        let thread_mgr = text_service.thread_mgr()?;
-
-        unsafe {
-            thread_mgr
-                .cast::<ITfLangBarItemMgr>()?
-                .RemoveItem(&text_service.this::<ITfLangBarItemButton>()?)?;
-
-            thread_mgr
-                .cast::<ITfLangBarItemMgr>()?
-                .AddItem(&text_service.this::<ITfLangBarItemButton>()?)?;
-        };
-
-        Ok(())
-    }
-}
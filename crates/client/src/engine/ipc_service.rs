@@ -1,10 +1,12 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use azookey_engine::candidates::{Candidates, Clause};
 use hyper_util::rt::TokioIo;
 use shared::proto::{
-    azookey_service_client::AzookeyServiceClient, window_service_client::WindowServiceClient,
+    azookey_service_client::AzookeyServiceClient, window_event::Event,
+    window_service_client::WindowServiceClient,
 };
 use std::{sync::Arc, time::Duration};
-use tokio::{net::windows::named_pipe::ClientOptions, time};
+use tokio::{net::windows::named_pipe::ClientOptions, task::JoinHandle, time};
 use tonic::transport::Endpoint;
 use tower::service_fn;
 use windows::Win32::Foundation::ERROR_PIPE_BUSY;
@@ -17,14 +19,8 @@ pub struct IPCService {
     // candidate window server client
     window_client: WindowServiceClient<tonic::transport::channel::Channel>,
     runtime: Arc<tokio::runtime::Runtime>,
-}
-
-#[derive(Debug, Clone, Default)]
-pub struct Candidates {
-    pub texts: Vec<String>,
-    pub sub_texts: Vec<String>,
-    pub hiragana: String,
-    pub corresponding_count: Vec<i32>,
+    // the composing text is kept per session on the kkc server
+    session_id: u32,
 }
 
 impl IPCService {
@@ -67,24 +63,47 @@ impl IPCService {
             )),
         )?;
 
-        let azookey_client = AzookeyServiceClient::new(server_channel);
+        let mut azookey_client = AzookeyServiceClient::new(server_channel);
         let window_client = WindowServiceClient::new(ui_channel);
         tracing::debug!("Connected to server: {:?}", azookey_client);
 
+        let session_id = runtime
+            .block_on(
+                azookey_client
+                    .create_session(tonic::Request::new(shared::proto::CreateSessionRequest {})),
+            )?
+            .into_inner()
+            .session_id;
+        tracing::debug!("Session created: {session_id}");
+
         Ok(Self {
             azookey_client,
             window_client,
             runtime: Arc::new(runtime),
+            session_id,
         })
     }
 }
 
 // implement methods to interact with kkc server
 impl IPCService {
+    #[tracing::instrument]
+    pub fn close_session(&mut self) -> anyhow::Result<()> {
+        let request = tonic::Request::new(shared::proto::CloseSessionRequest {
+            session_id: self.session_id,
+        });
+        self.runtime
+            .clone()
+            .block_on(self.azookey_client.close_session(request))?;
+
+        Ok(())
+    }
+
     #[tracing::instrument]
     pub fn append_text(&mut self, text: String) -> anyhow::Result<Candidates> {
         let request = tonic::Request::new(shared::proto::AppendTextRequest {
             text_to_append: text,
+            session_id: self.session_id,
         });
 
         let response = self
@@ -94,24 +113,7 @@ impl IPCService {
         let composing_text = response.into_inner().composing_text;
 
         let candidates = if let Some(composing_text) = composing_text {
-            Candidates {
-                texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.text.clone())
-                    .collect(),
-                sub_texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.subtext.clone())
-                    .collect(),
-                hiragana: composing_text.hiragana,
-                corresponding_count: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.corresponding_count)
-                    .collect(),
-            }
+            Candidates::from(composing_text)
         } else {
             anyhow::bail!("composing_text is None");
         };
@@ -121,7 +123,9 @@ impl IPCService {
 
     #[tracing::instrument]
     pub fn remove_text(&mut self) -> anyhow::Result<Candidates> {
-        let request = tonic::Request::new(shared::proto::RemoveTextRequest {});
+        let request = tonic::Request::new(shared::proto::RemoveTextRequest {
+            session_id: self.session_id,
+        });
         let response = self
             .runtime
             .clone()
@@ -129,24 +133,51 @@ impl IPCService {
         let composing_text = response.into_inner().composing_text;
 
         let candidates = if let Some(composing_text) = composing_text {
-            Candidates {
-                texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.text.clone())
-                    .collect(),
-                sub_texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.subtext.clone())
-                    .collect(),
-                hiragana: composing_text.hiragana,
-                corresponding_count: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.corresponding_count)
-                    .collect(),
-            }
+            Candidates::from(composing_text)
+        } else {
+            anyhow::bail!("composing_text is None");
+        };
+
+        Ok(candidates)
+    }
+
+    // removes the characters before the cursor and appends the text in one request
+    #[tracing::instrument]
+    pub fn replace_text(&mut self, remove_count: u32, text: String) -> anyhow::Result<Candidates> {
+        let request = tonic::Request::new(shared::proto::ReplaceTextRequest {
+            remove_count,
+            text_to_append: text,
+            session_id: self.session_id,
+        });
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.replace_text(request))?;
+        let composing_text = response.into_inner().composing_text;
+
+        let candidates = if let Some(composing_text) = composing_text {
+            Candidates::from(composing_text)
+        } else {
+            anyhow::bail!("composing_text is None");
+        };
+
+        Ok(candidates)
+    }
+
+    #[tracing::instrument]
+    pub fn move_cursor(&mut self, offset: i32) -> anyhow::Result<Candidates> {
+        let request = tonic::Request::new(shared::proto::MoveCursorRequest {
+            offset,
+            session_id: self.session_id,
+        });
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.move_cursor(request))?;
+        let composing_text = response.into_inner().composing_text;
+
+        let candidates = if let Some(composing_text) = composing_text {
+            Candidates::from(composing_text)
         } else {
             anyhow::bail!("composing_text is None");
         };
@@ -156,7 +187,9 @@ impl IPCService {
 
     #[tracing::instrument]
     pub fn clear_text(&mut self) -> anyhow::Result<()> {
-        let request = tonic::Request::new(shared::proto::ClearTextRequest {});
+        let request = tonic::Request::new(shared::proto::ClearTextRequest {
+            session_id: self.session_id,
+        });
         let _response = self
             .runtime
             .clone()
@@ -167,7 +200,10 @@ impl IPCService {
 
     #[tracing::instrument]
     pub fn shrink_text(&mut self, offset: i32) -> anyhow::Result<Candidates> {
-        let request = tonic::Request::new(shared::proto::ShrinkTextRequest { offset });
+        let request = tonic::Request::new(shared::proto::ShrinkTextRequest {
+            offset,
+            session_id: self.session_id,
+        });
         let response = self
             .runtime
             .clone()
@@ -175,24 +211,7 @@ impl IPCService {
         let composing_text = response.into_inner().composing_text;
 
         let candidates = if let Some(composing_text) = composing_text {
-            Candidates {
-                texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.text.clone())
-                    .collect(),
-                sub_texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.subtext.clone())
-                    .collect(),
-                hiragana: composing_text.hiragana,
-                corresponding_count: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.corresponding_count)
-                    .collect(),
-            }
+            Candidates::from(composing_text)
         } else {
             anyhow::bail!("composing_text is None");
         };
@@ -200,8 +219,78 @@ impl IPCService {
         Ok(candidates)
     }
 
+    #[tracing::instrument]
+    pub fn get_clauses(&mut self) -> anyhow::Result<Vec<Clause>> {
+        let request = tonic::Request::new(shared::proto::GetClausesRequest {
+            session_id: self.session_id,
+        });
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.get_clauses(request))?;
+        let composing_text = response
+            .into_inner()
+            .composing_text
+            .context("composing_text is None")?;
+
+        Ok(composing_text
+            .clauses
+            .into_iter()
+            .map(|c| Clause {
+                reading: c.reading,
+                surface: c.surface,
+                corresponding_count: c.corresponding_count,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument]
+    pub fn resize_clause(&mut self, index: i32, offset: i32) -> anyhow::Result<Vec<Clause>> {
+        let request = tonic::Request::new(shared::proto::ResizeClauseRequest {
+            session_id: self.session_id,
+            index,
+            offset,
+        });
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.resize_clause(request))?;
+        let composing_text = response
+            .into_inner()
+            .composing_text
+            .context("composing_text is None")?;
+
+        Ok(composing_text
+            .clauses
+            .into_iter()
+            .map(|c| Clause {
+                reading: c.reading,
+                surface: c.surface,
+                corresponding_count: c.corresponding_count,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument]
+    pub fn get_clause_candidates(&mut self, index: i32) -> anyhow::Result<Candidates> {
+        let request = tonic::Request::new(shared::proto::GetClauseCandidatesRequest {
+            session_id: self.session_id,
+            index,
+        });
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.get_clause_candidates(request))?;
+        let suggestions = response.into_inner().suggestions;
+
+        Ok(Candidates::from(suggestions.as_slice()))
+    }
+
     pub fn set_context(&mut self, context: String) -> anyhow::Result<()> {
-        let request = tonic::Request::new(shared::proto::SetContextRequest { context });
+        let request = tonic::Request::new(shared::proto::SetContextRequest {
+            context,
+            session_id: self.session_id,
+        });
         let _response = self
             .runtime
             .clone()
@@ -209,6 +298,51 @@ impl IPCService {
 
         Ok(())
     }
+
+    pub fn commit_candidate(
+        &mut self,
+        reading: String,
+        surface: String,
+        corresponding_count: i32,
+    ) -> anyhow::Result<()> {
+        let request = tonic::Request::new(shared::proto::CommitCandidateRequest {
+            session_id: self.session_id,
+            reading,
+            surface,
+            corresponding_count,
+        });
+        let _response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.commit_candidate(request))?;
+
+        Ok(())
+    }
+
+    // empty if the reading is not found
+    pub fn reverse_lookup(&mut self, surface: String) -> anyhow::Result<String> {
+        let request = tonic::Request::new(shared::proto::ReverseLookupRequest {
+            session_id: self.session_id,
+            surface,
+        });
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.reverse_lookup(request))?;
+
+        Ok(response.into_inner().reading)
+    }
+
+    // the server exits and is started again by the launcher
+    pub fn shutdown(&mut self) -> anyhow::Result<()> {
+        let request = tonic::Request::new(shared::proto::ShutdownRequest {});
+        let _response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.shutdown(request))?;
+
+        Ok(())
+    }
 }
 
 // implement methods to interact with candidate window server
@@ -257,8 +391,10 @@ impl IPCService {
     }
 
     #[tracing::instrument]
-    pub fn set_candidates(&mut self, candidates: Vec<String>) -> anyhow::Result<()> {
-        let request = tonic::Request::new(shared::proto::SetCandidateRequest { candidates });
+    pub fn set_candidates(&mut self, candidates: &Candidates) -> anyhow::Result<()> {
+        let request = tonic::Request::new(shared::proto::SetCandidateRequest {
+            candidates: candidates.entries(),
+        });
         self.runtime
             .clone()
             .block_on(self.window_client.set_candidate(request))?;
@@ -266,6 +402,16 @@ impl IPCService {
         Ok(())
     }
 
+    #[tracing::instrument]
+    pub fn set_predictions(&mut self, predictions: Vec<String>) -> anyhow::Result<()> {
+        let request = tonic::Request::new(shared::proto::SetPredictionsRequest { predictions });
+        self.runtime
+            .clone()
+            .block_on(self.window_client.set_predictions(request))?;
+
+        Ok(())
+    }
+
     #[tracing::instrument]
     pub fn set_selection(&mut self, index: i32) -> anyhow::Result<()> {
         let request = tonic::Request::new(shared::proto::SetSelectionRequest { index });
@@ -287,4 +433,25 @@ impl IPCService {
 
         Ok(())
     }
+
+    // the events are received on the runtime, so the handler has to pass them to the thread of the text service
+    pub fn watch_events(&mut self, handler: impl Fn(Event) + Send + 'static) -> JoinHandle<()> {
+        let mut window_client = self.window_client.clone();
+        self.runtime.spawn(async move {
+            let request = tonic::Request::new(shared::proto::EmptyResponse {});
+            let mut events = match window_client.watch_events(request).await {
+                Ok(response) => response.into_inner(),
+                Err(e) => {
+                    tracing::error!("Failed to watch the candidate window: {:?}", e);
+                    return;
+                }
+            };
+
+            while let Ok(Some(event)) = events.message().await {
+                if let Some(event) = event.event {
+                    handler(event);
+                }
+            }
+        })
+    }
 }
@@ -0,0 +1,107 @@
+// kana input on the JIS kana layout
+use std::sync::LazyLock;
+
+use shared::romaji::RomajiTable;
+
+// virtual key code, kana and kana with shift
+const KANA_LAYOUT: &[(u16, char, char)] = &[
+    (0x31, 'ぬ', 'ぬ'), // 1
+    (0x32, 'ふ', 'ふ'), // 2
+    (0x33, 'あ', 'ぁ'), // 3
+    (0x34, 'う', 'ぅ'), // 4
+    (0x35, 'え', 'ぇ'), // 5
+    (0x36, 'お', 'ぉ'), // 6
+    (0x37, 'や', 'ゃ'), // 7
+    (0x38, 'ゆ', 'ゅ'), // 8
+    (0x39, 'よ', 'ょ'), // 9
+    (0x30, 'わ', 'を'), // 0
+    (0xBD, 'ほ', 'ほ'), // -
+    (0xDE, 'へ', 'へ'), // ^
+    (0xDC, 'ー', 'ー'), // Yen
+    (0x51, 'た', 'た'), // Q
+    (0x57, 'て', 'て'), // W
+    (0x45, 'い', 'ぃ'), // E
+    (0x52, 'す', 'す'), // R
+    (0x54, 'か', 'か'), // T
+    (0x59, 'ん', 'ん'), // Y
+    (0x55, 'な', 'な'), // U
+    (0x49, 'に', 'に'), // I
+    (0x4F, 'ら', 'ら'), // O
+    (0x50, 'せ', 'せ'), // P
+    (0xC0, '゛', '゛'), // @
+    (0xDB, '゜', '「'), // [
+    (0x41, 'ち', 'ち'), // A
+    (0x53, 'と', 'と'), // S
+    (0x44, 'し', 'し'), // D
+    (0x46, 'は', 'は'), // F
+    (0x47, 'き', 'き'), // G
+    (0x48, 'く', 'く'), // H
+    (0x4A, 'ま', 'ま'), // J
+    (0x4B, 'の', 'の'), // K
+    (0x4C, 'り', 'り'), // L
+    (0xBB, 'れ', 'れ'), // ;
+    (0xBA, 'け', 'け'), // :
+    (0xDD, 'む', '」'), // ]
+    (0x5A, 'つ', 'っ'), // Z
+    (0x58, 'さ', 'さ'), // X
+    (0x43, 'そ', 'そ'), // C
+    (0x56, 'ひ', 'ひ'), // V
+    (0x42, 'こ', 'こ'), // B
+    (0x4E, 'み', 'み'), // N
+    (0x4D, 'も', 'も'), // M
+    (0xBC, 'ね', '、'), // ,
+    (0xBE, 'る', '。'), // .
+    (0xBF, 'め', '・'), // /
+    (0xE2, 'ろ', 'ろ'), // Ro
+];
+
+const DAKUTEN: &[(&str, &str)] = &[
+    ("う", "ゔ"),
+    ("か", "が"),
+    ("き", "ぎ"),
+    ("く", "ぐ"),
+    ("け", "げ"),
+    ("こ", "ご"),
+    ("さ", "ざ"),
+    ("し", "じ"),
+    ("す", "ず"),
+    ("せ", "ぜ"),
+    ("そ", "ぞ"),
+    ("た", "だ"),
+    ("ち", "ぢ"),
+    ("つ", "づ"),
+    ("て", "で"),
+    ("と", "ど"),
+    ("は", "ば"),
+    ("ひ", "び"),
+    ("ふ", "ぶ"),
+    ("へ", "べ"),
+    ("ほ", "ぼ"),
+];
+
+const HANDAKUTEN: &[(&str, &str)] = &[
+    ("は", "ぱ"),
+    ("ひ", "ぴ"),
+    ("ふ", "ぷ"),
+    ("へ", "ぺ"),
+    ("ほ", "ぽ"),
+];
+
+// the kana which can take the dakuten waits for the next key, and is combined with it
+pub static DAKUTEN_TABLE: LazyLock<RomajiTable> = LazyLock::new(|| {
+    let mut table = RomajiTable::empty();
+    for (kana, voiced) in DAKUTEN {
+        table.insert(&format!("{}゛", kana), voiced, "");
+    }
+    for (kana, semi_voiced) in HANDAKUTEN {
+        table.insert(&format!("{}゜", kana), semi_voiced, "");
+    }
+    table
+});
+
+pub fn kana_for_key(key_code: u16, shift: bool) -> Option<char> {
+    KANA_LAYOUT
+        .iter()
+        .find(|(vk, _, _)| *vk == key_code)
+        .map(|(_, kana, shifted)| if shift { *shifted } else { *kana })
+}
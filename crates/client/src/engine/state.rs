@@ -1,11 +1,22 @@
 use std::{
     collections::HashMap,
-    sync::{LazyLock, Mutex, MutexGuard},
+    path::Path,
+    sync::{LazyLock, Mutex, MutexGuard, RwLock},
+};
+
+use shared::{
+    config_watcher::{self, ConfigWatcher},
+    input_mode_memory::InputModeMemory,
+    keymap::Keymap,
+    romaji::RomajiTable,
+    AppConfig,
 };
 
 use windows::{core::GUID, Win32::UI::TextServices::ITfContext};
 
-use super::{input_mode::InputMode, ipc_service::IPCService};
+use azookey_engine::{input_mode::InputMode, reconversion::CommitHistory};
+
+use super::ipc_service::IPCService;
 
 #[derive(Debug)]
 pub struct IMEState {
@@ -13,23 +24,177 @@ pub struct IMEState {
     pub input_mode: InputMode,
     pub cookies: HashMap<GUID, u32>,
     pub context: Option<ITfContext>,
+    // readings of the committed texts, used by the reconversion
+    pub commit_history: CommitHistory,
+}
+
+// TSF activates the text service once per thread, so each thread has its own session of the server
+// the state is leaked to hand out the 'static guard, it is one per thread which has activated the text service
+thread_local! {
+    static IME_STATE: &'static Mutex<IMEState> = {
+        tracing::debug!("Creating IMEState");
+        Box::leak(Box::new(Mutex::new(IMEState {
+            ipc_service: None,
+            input_mode: InputMode::default(),
+            cookies: HashMap::new(),
+            context: None,
+            commit_history: CommitHistory::default(),
+        })))
+    };
+}
+
+// latest settings, updated by the ConfigWatcher shared by the text services
+// this is separated from IMEState, since the watcher thread would make IMEState::get() fail while it holds the lock
+pub static APP_CONFIG: LazyLock<RwLock<AppConfig>> =
+    LazyLock::new(|| RwLock::new(AppConfig::read().unwrap_or_default()));
+// lookup table of the key bindings, rebuilt when the settings are changed
+pub static KEYMAP: LazyLock<RwLock<Keymap>> =
+    LazyLock::new(|| RwLock::new(Keymap::new(&APP_CONFIG.read().unwrap().keymap)));
+
+// romaji table with the user rules
+pub static ROMAJI_TABLE: LazyLock<RwLock<RomajiTable>> =
+    LazyLock::new(|| RwLock::new(load_romaji_table(&APP_CONFIG.read().unwrap())));
+
+// the text services of all the threads share one watcher
+// it is stopped when the last one is deactivated, so that the thread doesn't outlive the dll
+struct SharedConfigWatcher {
+    users: usize,
+    watcher: Option<ConfigWatcher>,
 }
 
-pub static IME_STATE: LazyLock<Mutex<IMEState>> = LazyLock::new(|| {
-    tracing::debug!("Creating IMEState");
-    Mutex::new(IMEState {
-        ipc_service: None,
-        input_mode: InputMode::default(),
-        cookies: HashMap::new(),
-        context: None,
-    })
+static CONFIG_WATCHER: Mutex<SharedConfigWatcher> = Mutex::new(SharedConfigWatcher {
+    users: 0,
+    watcher: None,
 });
+
+// executable name of the app which loaded the text service, used to look up the app rules
+pub static APP_NAME: LazyLock<String> = LazyLock::new(|| {
+    std::env::current_exe()
+        .ok()
+        .as_deref()
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+});
+
+// input mode when the app is focused, the rule of the app takes precedence over the remembered one
+pub fn app_input_mode() -> Option<InputMode> {
+    let config = APP_CONFIG.read().ok()?;
+    if let Some(mode) = config.app_rule(&APP_NAME).and_then(|rule| rule.input_mode) {
+        return Some(mode.into());
+    }
+    if !config.input.remember_mode {
+        return None;
+    }
+
+    InputModeMemory::read().ok()?.get(&APP_NAME).map(Into::into)
+}
+
+// the rule of the app takes precedence over the setting
+pub fn live_conversion() -> bool {
+    APP_CONFIG
+        .read()
+        .map(|config| config.live_conversion(&APP_NAME))
+        .unwrap_or(true)
+}
+
+pub fn candidate_page_size() -> i32 {
+    APP_CONFIG
+        .read()
+        .map(|config| config.candidate_window.page_size())
+        .unwrap_or(9) as i32
+}
+
+// the mode is still switched even if it can't be saved
+pub fn remember_input_mode(mode: &InputMode) {
+    let remember = APP_CONFIG
+        .read()
+        .map(|config| config.input.remember_mode)
+        .unwrap_or(false);
+    if !remember || APP_NAME.is_empty() {
+        return;
+    }
+
+    // a broken file is overwritten, since it only holds the last modes
+    let mut memory = InputModeMemory::read().unwrap_or_default();
+    if memory.set(&APP_NAME, mode.into()) {
+        if let Err(e) = memory.write() {
+            tracing::error!("Failed to save input mode: {:?}", e);
+        }
+    }
+}
+
+fn load_romaji_table(config: &AppConfig) -> RomajiTable {
+    if config.input.romaji_table.is_empty() {
+        return RomajiTable::default();
+    }
+
+    match RomajiTable::load(Path::new(&config.input.romaji_table)) {
+        Ok(table) => table,
+        Err(e) => {
+            tracing::error!("Failed to load romaji table: {:?}", e);
+            RomajiTable::default()
+        }
+    }
+}
+
+pub fn update_app_config(config: AppConfig) {
+    if let Ok(mut keymap) = KEYMAP.write() {
+        *keymap = Keymap::new(&config.keymap);
+    }
+    if let Ok(mut romaji_table) = ROMAJI_TABLE.write() {
+        *romaji_table = load_romaji_table(&config);
+    }
+    if let Ok(mut app_config) = APP_CONFIG.write() {
+        *app_config = config;
+    }
+}
+
+// called on Activate, the first text service starts the watcher with the latest settings
+pub fn watch_config() {
+    let Ok(mut shared) = CONFIG_WATCHER.lock() else {
+        return;
+    };
+
+    shared.users += 1;
+    if shared.watcher.is_none() {
+        if let Ok(config) = AppConfig::read() {
+            update_app_config(config);
+        }
+        shared.watcher = Some(ConfigWatcher::spawn(
+            config_watcher::DEFAULT_INTERVAL,
+            |config| {
+                tracing::debug!("Settings changed");
+                update_app_config(config);
+            },
+        ));
+    }
+}
+
+// called on Deactivate, the thread of the watcher is joined when the last text service leaves
+pub fn unwatch_config() {
+    let watcher = match CONFIG_WATCHER.lock() {
+        Ok(mut shared) => {
+            shared.users = shared.users.saturating_sub(1);
+            if shared.users == 0 {
+                shared.watcher.take()
+            } else {
+                None
+            }
+        }
+        Err(_) => return,
+    };
+
+    // joined outside the lock
+    drop(watcher);
+}
+
 unsafe impl Sync for IMEState {}
 unsafe impl Send for IMEState {}
 
 impl IMEState {
     pub fn get() -> anyhow::Result<MutexGuard<'static, IMEState>> {
-        match IME_STATE.try_lock() {
+        match IME_STATE.with(|state| *state).try_lock() {
             Ok(guard) => Ok(guard),
             Err(e) => anyhow::bail!("Failed to lock state: {:?}", e),
         }
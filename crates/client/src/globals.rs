@@ -9,7 +9,7 @@ use anyhow::{Context, Result};
 use windows::{
     core::GUID,
     Win32::{
-        Foundation::{FALSE, HMODULE, MAX_PATH},
+        Foundation::{FALSE, HMODULE, MAX_PATH, TRUE},
         System::LibraryLoader::GetModuleFileNameW,
         UI::TextServices::{
             TF_ATTR_TARGET_CONVERTED, TF_CT_NONE, TF_DA_COLOR, TF_DA_COLOR_0, TF_DISPLAYATTRIBUTE,
@@ -49,6 +49,28 @@ pub const DISPLAY_ATTRIBUTE: TF_DISPLAYATTRIBUTE = TF_DISPLAYATTRIBUTE {
     bAttr: TF_ATTR_TARGET_CONVERTED,
 };
 
+// DisplayAttribute for the focused clause
+pub const GUID_DISPLAY_ATTRIBUTE_FOCUSED: GUID =
+    GUID::from_u128(0xffdefe7c_2fc2_11ef_b16b_94e70b2c378c);
+
+pub const DISPLAY_ATTRIBUTE_FOCUSED: TF_DISPLAYATTRIBUTE = TF_DISPLAYATTRIBUTE {
+    crText: TF_DA_COLOR {
+        r#type: TF_CT_NONE,
+        Anonymous: TF_DA_COLOR_0 { nIndex: 0 },
+    },
+    crBk: TF_DA_COLOR {
+        r#type: TF_CT_NONE,
+        Anonymous: TF_DA_COLOR_0 { nIndex: 0 },
+    },
+    lsStyle: TF_LS_SOLID,
+    fBoldLine: TRUE,
+    crLine: TF_DA_COLOR {
+        r#type: TF_CT_NONE,
+        Anonymous: TF_DA_COLOR_0 { nIndex: 0 },
+    },
+    bAttr: TF_ATTR_TARGET_CONVERTED,
+};
+
 // You can use any value for this cookie.
 pub const TEXTSERVICE_LANGBARITEMSINK_COOKIE: u32 = 0;
 
@@ -0,0 +1,133 @@
+use std::{cell::RefCell, ffi::c_void};
+
+use windows::{
+    core::{w, AsImpl, PCWSTR},
+    Win32::{
+        Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM},
+        UI::{
+            TextServices::ITfTextInputProcessor,
+            WindowsAndMessaging::{
+                CreateWindowExW, DefWindowProcW, DestroyWindow, PostMessageW, RegisterClassW,
+                HWND_MESSAGE, WINDOW_EX_STYLE, WINDOW_STYLE, WM_APP, WNDCLASSW,
+            },
+        },
+    },
+};
+
+use anyhow::{Context, Result};
+use shared::proto::window_event::Event;
+use tokio::task::JoinHandle;
+
+use azookey_engine::user_action::UserAction;
+
+use crate::{engine::ipc_service::IPCService, globals::DllModule};
+
+use super::factory::TextServiceFactory;
+
+// the candidate window is in the ui process, and it reports the mouse operations with WatchEvents
+// the events arrive on the runtime of the ipc service, so they are posted to a message-only window
+// to be handled on the thread of the text service
+const WM_CANDIDATE_EVENT: u32 = WM_APP + 1;
+const CLASS_NAME: PCWSTR = w!("AzookeyCandidateEvents");
+
+const SELECT_CANDIDATE: usize = 0;
+const COMMIT_CANDIDATE: usize = 1;
+
+// the text service is activated once per thread, and the window is owned by the same thread
+thread_local! {
+    static TEXT_SERVICE: RefCell<Option<ITfTextInputProcessor>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug)]
+pub struct CandidateEvents {
+    hwnd: HWND,
+    task: JoinHandle<()>,
+}
+
+impl CandidateEvents {
+    pub fn spawn(this: &ITfTextInputProcessor, ipc_service: &mut IPCService) -> Result<Self> {
+        let hinst: HINSTANCE = DllModule::get()?
+            .hinst
+            .context("Dll instance not found")?
+            .into();
+
+        let hwnd = unsafe {
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(wndproc),
+                hInstance: hinst,
+                lpszClassName: CLASS_NAME,
+                ..Default::default()
+            };
+            // the class is registered once per process, so it fails from the second time
+            RegisterClassW(&class);
+
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                CLASS_NAME,
+                PCWSTR::null(),
+                WINDOW_STYLE::default(),
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE,
+                None,
+                hinst,
+                None,
+            )?;
+            hwnd
+        };
+        TEXT_SERVICE.with(|text_service| *text_service.borrow_mut() = Some(this.clone()));
+
+        // HWND is not Send, so the handle is passed as an integer
+        let target = hwnd.0 as isize;
+        let task = ipc_service.watch_events(move |event| {
+            let (kind, index) = match event {
+                Event::SelectCandidate(index) => (SELECT_CANDIDATE, index),
+                Event::CommitCandidate(index) => (COMMIT_CANDIDATE, index),
+            };
+            unsafe {
+                let _ = PostMessageW(
+                    HWND(target as *mut c_void),
+                    WM_CANDIDATE_EVENT,
+                    WPARAM(kind),
+                    LPARAM(index as isize),
+                );
+            }
+        });
+
+        Ok(Self { hwnd, task })
+    }
+}
+
+impl Drop for CandidateEvents {
+    fn drop(&mut self) {
+        self.task.abort();
+        unsafe {
+            let _ = DestroyWindow(self.hwnd);
+        }
+        TEXT_SERVICE.with(|text_service| text_service.borrow_mut().take());
+    }
+}
+
+unsafe extern "system" fn wndproc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if msg != WM_CANDIDATE_EVENT {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+
+    // the text service is cloned, since the handler may end up in Deactivate
+    let Some(this) = TEXT_SERVICE.with(|text_service| text_service.borrow().clone()) else {
+        return LRESULT(0);
+    };
+    let factory: &TextServiceFactory = this.as_impl();
+    let action = match wparam.0 {
+        SELECT_CANDIDATE => UserAction::HoverCandidate(lparam.0 as i32),
+        _ => UserAction::ClickCandidate(lparam.0 as i32),
+    };
+
+    if let Err(e) = factory.handle_candidate_event(action) {
+        tracing::error!("Failed to handle the candidate window event: {:?}", e);
+    }
+
+    LRESULT(0)
+}
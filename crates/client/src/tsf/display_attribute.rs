@@ -12,7 +12,10 @@ use windows::{
 
 use anyhow::Result;
 
-use crate::globals::{DISPLAY_ATTRIBUTE, GUID_DISPLAY_ATTRIBUTE};
+use crate::globals::{
+    DISPLAY_ATTRIBUTE, DISPLAY_ATTRIBUTE_FOCUSED, GUID_DISPLAY_ATTRIBUTE,
+    GUID_DISPLAY_ATTRIBUTE_FOCUSED,
+};
 
 use super::factory::TextServiceFactory_Impl;
 
@@ -101,10 +104,10 @@ pub struct EnumDisplayAttributeInfo {
 #[allow(clippy::new_without_default)]
 impl EnumDisplayAttributeInfo {
     pub fn new() -> Self {
-        let attributes = vec![DisplayAttributeInfo::new(
-            GUID_DISPLAY_ATTRIBUTE,
-            DISPLAY_ATTRIBUTE,
-        )];
+        let attributes = vec![
+            DisplayAttributeInfo::new(GUID_DISPLAY_ATTRIBUTE, DISPLAY_ATTRIBUTE),
+            DisplayAttributeInfo::new(GUID_DISPLAY_ATTRIBUTE_FOCUSED, DISPLAY_ATTRIBUTE_FOCUSED),
+        ];
 
         EnumDisplayAttributeInfo {
             attributes,
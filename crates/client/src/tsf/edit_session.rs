@@ -16,7 +16,13 @@ use std::{cell::Cell, mem::ManuallyDrop, rc::Rc};
 
 use anyhow::{Context, Result};
 
-use crate::{engine::state::IMEState, extension::StringExt as _, globals::GUID_DISPLAY_ATTRIBUTE};
+use azookey_engine::candidates::Clause;
+
+use crate::{
+    engine::state::IMEState,
+    extension::StringExt as _,
+    globals::{GUID_DISPLAY_ATTRIBUTE, GUID_DISPLAY_ATTRIBUTE_FOCUSED},
+};
 
 use super::factory::TextServiceFactory;
 
@@ -71,10 +77,7 @@ impl TextServiceFactory {
         let sink = text_service.this::<ITfCompositionSink>()?;
         let insert = text_service.context::<ITfInsertAtSelection>()?;
 
-        let tip_exists = {
-            let composition = text_service.borrow_composition()?;
-            composition.tip_composition.is_some()
-        };
+        let tip_exists = text_service.tip_composition.try_borrow()?.is_some();
 
         if tip_exists {
             self.end_composition()?;
@@ -96,7 +99,7 @@ impl TextServiceFactory {
         )?;
 
         tracing::debug!("Composition started {composition:?}");
-        text_service.borrow_mut_composition()?.tip_composition = composition;
+        *text_service.tip_composition.try_borrow_mut()? = composition;
 
         Ok(())
     }
@@ -106,7 +109,7 @@ impl TextServiceFactory {
         tracing::debug!("end_composition");
         let text_service = self.borrow()?;
 
-        if let Some(composition) = text_service.borrow_composition()?.tip_composition.clone() {
+        if let Some(composition) = text_service.tip_composition.try_borrow()?.clone() {
             edit_session(
                 text_service.tid,
                 text_service.context()?,
@@ -151,16 +154,17 @@ impl TextServiceFactory {
             tracing::warn!("Composition is not started");
         }
 
-        text_service.borrow_mut_composition()?.tip_composition = None;
+        *text_service.tip_composition.try_borrow_mut()? = None;
 
         Ok(())
     }
 
+    // cursor is the caret position from the start of the composition, in characters
     #[tracing::instrument]
-    pub fn set_text(&self, text: &str, subtext: &str) -> Result<()> {
+    pub fn set_text(&self, text: &str, subtext: &str, cursor: i32) -> Result<()> {
         let text_service = self.borrow()?;
 
-        if let Some(composition) = text_service.borrow_composition()?.tip_composition.clone() {
+        if let Some(composition) = text_service.tip_composition.try_borrow()?.clone() {
             edit_session(
                 text_service.tid,
                 text_service.context()?,
@@ -188,6 +192,91 @@ impl TextServiceFactory {
                             prop.SetValue(cookie, &text_range, &pvar)?;
                         }
 
+                        // then, place the caret inside the composition
+                        range.Collapse(cookie, TF_ANCHOR_START)?;
+                        range.ShiftEnd(cookie, cursor, &mut shifted, std::ptr::null())?;
+                        range.Collapse(cookie, TF_ANCHOR_END)?;
+                        let selection = TF_SELECTION {
+                            range: ManuallyDrop::new(Some(range.clone())),
+                            style: TF_SELECTIONSTYLE {
+                                ase: TF_AE_NONE,
+                                fInterimChar: false.into(),
+                            },
+                        };
+
+                        context.SetSelection(cookie, &[selection])?;
+
+                        Ok(())
+                    }
+                }),
+            )?;
+        } else {
+            tracing::warn!("Composition is not started");
+        }
+
+        Ok(())
+    }
+
+    // set the clauses to the composition, and highlight the focused one
+    #[tracing::instrument]
+    pub fn set_clauses(&self, clauses: &[Clause], focused: i32) -> Result<()> {
+        let text_service = self.borrow()?;
+
+        if let Some(composition) = text_service.tip_composition.try_borrow()?.clone() {
+            edit_session(
+                text_service.tid,
+                text_service.context()?,
+                Rc::new({
+                    let text = clauses
+                        .iter()
+                        .map(|c| c.surface.as_str())
+                        .collect::<String>()
+                        .as_str()
+                        .to_wide_16_unpadded();
+                    let lengths: Vec<i32> = clauses
+                        .iter()
+                        .map(|c| c.surface.chars().count() as i32)
+                        .collect();
+                    let context = text_service.context::<ITfContext>()?;
+                    let display_attribute_atom = text_service.display_attribute_atom.clone();
+
+                    move |cookie| unsafe {
+                        let range = composition.GetRange()?;
+                        range.SetText(cookie, TF_ST_CORRECTION, &text)?;
+
+                        let prop = context.GetProperty(&GUID_PROP_ATTRIBUTE)?;
+                        let mut start = 0;
+                        for (index, length) in lengths.iter().enumerate() {
+                            let guid = if index as i32 == focused {
+                                GUID_DISPLAY_ATTRIBUTE_FOCUSED
+                            } else {
+                                GUID_DISPLAY_ATTRIBUTE
+                            };
+
+                            let clause_range = range.Clone()?;
+                            let mut shifted: i32 = 0;
+                            clause_range.Collapse(cookie, TF_ANCHOR_START)?;
+                            clause_range.ShiftEnd(
+                                cookie,
+                                start + length,
+                                &mut shifted,
+                                std::ptr::null(),
+                            )?;
+                            clause_range.ShiftStart(
+                                cookie,
+                                start,
+                                &mut shifted,
+                                std::ptr::null(),
+                            )?;
+
+                            if let Some(display_attribute) = display_attribute_atom.get(&guid) {
+                                let pvar = VARIANT::from(*display_attribute as i32);
+                                prop.SetValue(cookie, &clause_range, &pvar)?;
+                            }
+
+                            start += length;
+                        }
+
                         range.Collapse(cookie, TF_ANCHOR_END)?;
                         let selection = TF_SELECTION {
                             range: ManuallyDrop::new(Some(range.clone())),
@@ -214,7 +303,7 @@ impl TextServiceFactory {
     pub fn shift_start(&self, text: &str, subtext: &str) -> Result<()> {
         let text_service = self.borrow()?;
 
-        if let Some(composition) = text_service.borrow_composition()?.tip_composition.clone() {
+        if let Some(composition) = text_service.tip_composition.try_borrow()?.clone() {
             edit_session(
                 text_service.tid,
                 text_service.context()?,
@@ -275,9 +364,9 @@ impl TextServiceFactory {
     #[tracing::instrument]
     pub fn update_pos(&self) -> Result<()> {
         let text_service = self.borrow()?;
-        let composition = text_service.borrow_composition()?;
+        let tip_composition = text_service.tip_composition.try_borrow()?.clone();
 
-        if let Some(tip_composition) = composition.tip_composition.clone() {
+        if let Some(tip_composition) = tip_composition {
             edit_session(
                 text_service.tid,
                 text_service.context()?,
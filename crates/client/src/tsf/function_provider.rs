@@ -0,0 +1,92 @@
+use windows::{
+    core::{IUnknown, Interface as _, BSTR, GUID},
+    Win32::{
+        Foundation::{BOOL, E_NOINTERFACE},
+        UI::TextServices::{
+            ITfCandidateList, ITfFnReconversion, ITfFnReconversion_Impl, ITfFunctionProvider_Impl,
+            ITfFunction_Impl, ITfRange,
+        },
+    },
+};
+
+use anyhow::{Context as _, Result};
+
+use azookey_engine::user_action::UserAction;
+
+use crate::globals::GUID_TEXT_SERVICE;
+
+use super::factory::TextServiceFactory_Impl;
+
+// the functions called by the system or the application, e.g. Win+/ calls the reconversion
+impl ITfFunctionProvider_Impl for TextServiceFactory_Impl {
+    #[macros::anyhow]
+    fn GetType(&self) -> Result<GUID> {
+        Ok(GUID_TEXT_SERVICE)
+    }
+
+    #[macros::anyhow]
+    fn GetDescription(&self) -> Result<BSTR> {
+        Ok(BSTR::from("azooKey"))
+    }
+
+    #[macros::anyhow]
+    fn GetFunction(&self, rguid: *const GUID, riid: *const GUID) -> Result<IUnknown> {
+        let (rguid, riid) = unsafe { (*rguid, *riid) };
+
+        if rguid == GUID::zeroed() && riid == ITfFnReconversion::IID {
+            // the caller uses the pointer as the requested interface without QueryInterface
+            let function = self.borrow()?.this::<ITfFnReconversion>()?;
+            return Ok(unsafe { std::mem::transmute::<ITfFnReconversion, IUnknown>(function) });
+        }
+
+        Err(anyhow::Error::new(windows::core::Error::from_hresult(
+            E_NOINTERFACE,
+        )))
+    }
+}
+
+impl ITfFunction_Impl for TextServiceFactory_Impl {
+    #[macros::anyhow]
+    fn GetDisplayName(&self) -> Result<BSTR> {
+        Ok(BSTR::from("再変換"))
+    }
+}
+
+impl ITfFnReconversion_Impl for TextServiceFactory_Impl {
+    // the text can't be read outside of the edit session, so the range is decided on Reconvert
+    #[macros::anyhow]
+    fn QueryRange(
+        &self,
+        prange: Option<&ITfRange>,
+        ppnewrange: *mut Option<ITfRange>,
+        pfconvertable: *mut BOOL,
+    ) -> Result<()> {
+        let range = prange.context("Range is null")?;
+
+        unsafe {
+            *ppnewrange = Some(range.Clone()?);
+            *pfconvertable = BOOL::from(true);
+        }
+
+        Ok(())
+    }
+
+    // the candidates are shown in the candidate window on Reconvert instead
+    #[macros::anyhow]
+    fn GetReconversion(&self, _prange: Option<&ITfRange>) -> Result<ITfCandidateList> {
+        anyhow::bail!("GetReconversion is not supported");
+    }
+
+    // the range becomes the selection, and it is reconverted in the same way as the Henkan key
+    #[macros::anyhow]
+    fn Reconvert(&self, prange: Option<&ITfRange>) -> Result<()> {
+        let range = prange.context("Range is null")?;
+        let context = unsafe { range.GetContext()? };
+        self.borrow_mut()?.context = Some(context);
+
+        self.select_range(range)?;
+        self.process_action(&UserAction::Reconvert)?;
+
+        Ok(())
+    }
+}
@@ -1,35 +1,48 @@
+use std::{path::PathBuf, process::Command};
+
 use windows::{
     core::{IUnknown, Interface as _, BSTR, GUID, PCWSTR},
     Win32::{
         Foundation::{BOOL, E_INVALIDARG, POINT, RECT},
+        Graphics::Gdi::HBITMAP,
         System::Ole::CONNECT_E_CANNOTCONNECT,
         UI::{
             TextServices::{
-                ITfLangBarItemButton_Impl, ITfLangBarItemSink, ITfLangBarItem_Impl, ITfMenu,
-                ITfSource_Impl, TfLBIClick, GUID_LBI_INPUTMODE, TF_LANGBARITEMINFO,
-                TF_LBI_STYLE_BTN_BUTTON,
+                ITfLangBarItemButton, ITfLangBarItemButton_Impl, ITfLangBarItemMgr,
+                ITfLangBarItemSink, ITfLangBarItem_Impl, ITfMenu, ITfSource_Impl, TfLBIClick,
+                GUID_LBI_INPUTMODE, TF_LANGBARITEMINFO, TF_LBI_STYLE_BTN_BUTTON,
+                TF_LBI_STYLE_BTN_MENU, TF_LBMENUF_CHECKED, TF_LBMENUF_RADIOCHECKED,
+                TF_LBMENUF_SEPARATOR,
             },
             WindowsAndMessaging::{LoadImageW, HICON, IMAGE_ICON, LR_DEFAULTCOLOR},
         },
     },
 };
 
+use azookey_engine::{
+    client_action::ClientAction,
+    composition::CompositionState,
+    input_mode::InputMode,
+    lang_bar_menu::{menu_command, menu_items, MenuCommand, MenuItemKind},
+};
+
 use crate::{
     engine::{
-        client_action::ClientAction, composition::CompositionState, input_mode::InputMode,
-        state::IMEState, theme::get_theme,
+        state::{IMEState, APP_CONFIG},
+        theme::get_theme,
     },
     globals::{DllModule, GUID_TEXT_SERVICE, TEXTSERVICE_LANGBARITEMSINK_COOKIE},
 };
 
 use anyhow::{Context as _, Result};
 
-use super::factory::TextServiceFactory_Impl;
+use super::factory::{TextServiceFactory, TextServiceFactory_Impl};
 
 const INFO: TF_LANGBARITEMINFO = TF_LANGBARITEMINFO {
     clsidService: GUID_TEXT_SERVICE,
     guidItem: GUID_LBI_INPUTMODE,
-    dwStyle: TF_LBI_STYLE_BTN_BUTTON,
+    // clicking the icon toggles the mode, and the menu is shown from the drop-down arrow
+    dwStyle: TF_LBI_STYLE_BTN_BUTTON | TF_LBI_STYLE_BTN_MENU,
     ulSort: 0,
     szDescription: [0; 32],
 };
@@ -70,7 +83,7 @@ impl ITfLangBarItemButton_Impl for TextServiceFactory_Impl {
             let ime_mode = &IMEState::get()?.input_mode;
             match ime_mode {
                 InputMode::Latin => InputMode::Kana,
-                InputMode::Kana => InputMode::Latin,
+                _ => InputMode::Latin,
             }
         };
 
@@ -80,15 +93,76 @@ impl ITfLangBarItemButton_Impl for TextServiceFactory_Impl {
         Ok(())
     }
 
-    // this method should not be called
     #[macros::anyhow]
-    fn InitMenu(&self, _pmenu: Option<&ITfMenu>) -> Result<()> {
+    fn InitMenu(&self, pmenu: Option<&ITfMenu>) -> Result<()> {
+        let menu = pmenu.context("Menu is None")?;
+        let mode = IMEState::get()?.input_mode.clone();
+        let zenzai_enabled = APP_CONFIG
+            .read()
+            .map(|config| config.zenzai.enable)
+            .unwrap_or(false);
+
+        for item in menu_items(&mode, zenzai_enabled) {
+            let flags = match item.kind {
+                MenuItemKind::Radio(true) => TF_LBMENUF_RADIOCHECKED,
+                MenuItemKind::Check(true) => TF_LBMENUF_CHECKED,
+                MenuItemKind::Separator => TF_LBMENUF_SEPARATOR,
+                _ => 0,
+            };
+            let label: Vec<u16> = item.label.encode_utf16().collect();
+
+            unsafe {
+                menu.AddMenuItem(
+                    item.id,
+                    flags,
+                    HBITMAP::default(),
+                    HBITMAP::default(),
+                    &label,
+                    std::ptr::null_mut(),
+                )?;
+            }
+        }
+
         Ok(())
     }
 
-    // this method should not be called
     #[macros::anyhow]
-    fn OnMenuSelect(&self, _w_id: u32) -> Result<()> {
+    fn OnMenuSelect(&self, w_id: u32) -> Result<()> {
+        let Some(command) = menu_command(w_id) else {
+            return Ok(());
+        };
+
+        match command {
+            MenuCommand::SetInputMode(mode) => {
+                let actions = vec![ClientAction::SetIMEMode(mode)];
+                self.handle_action(&actions, CompositionState::None)?;
+            }
+            MenuCommand::ToggleZenzai => {
+                // the server and the settings app follow the settings file
+                // the lock is not held while writing, and the toggle is kept only if it is saved
+                let mut config = match APP_CONFIG.read() {
+                    Ok(app_config) => app_config.clone(),
+                    Err(e) => anyhow::bail!("Failed to lock app config: {:?}", e),
+                };
+                config.zenzai.enable = !config.zenzai.enable;
+                config.write()?;
+
+                if let Ok(mut app_config) = APP_CONFIG.write() {
+                    *app_config = config;
+                }
+            }
+            MenuCommand::OpenSettings => open_settings(None)?,
+            MenuCommand::AddWord => open_settings(Some("dictionary"))?,
+            MenuCommand::RestartServer => {
+                let mut ipc_service = IMEState::get()?
+                    .ipc_service
+                    .clone()
+                    .context("ipc_service is None")?;
+                // the launcher starts the server again, and the sessions are recreated on demand
+                ipc_service.shutdown()?;
+            }
+        }
+
         Ok(())
     }
 
@@ -100,14 +174,14 @@ impl ITfLangBarItemButton_Impl for TextServiceFactory_Impl {
         let theme = get_theme()?;
 
         let icon_id = match input_mode {
-            InputMode::Kana => {
+            InputMode::Kana | InputMode::Katakana | InputMode::HalfKatakana => {
                 if theme {
                     102
                 } else {
                     104
                 }
             }
-            InputMode::Latin => {
+            InputMode::Latin | InputMode::FullLatin => {
                 if theme {
                     103
                 } else {
@@ -136,6 +210,24 @@ impl ITfLangBarItemButton_Impl for TextServiceFactory_Impl {
     }
 }
 
+// the settings app is installed by the tauri installer, per user or for all users
+fn open_settings(page: Option<&str>) -> Result<()> {
+    let path = ["LOCALAPPDATA", "ProgramFiles"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .map(|dir| PathBuf::from(dir).join("Azookey").join("Azookey.exe"))
+        .find(|path| path.exists())
+        .context("Settings app not found")?;
+
+    let mut command = Command::new(path);
+    if let Some(page) = page {
+        command.args(["--page", page]);
+    }
+    command.spawn()?;
+
+    Ok(())
+}
+
 impl ITfSource_Impl for TextServiceFactory_Impl {
     #[macros::anyhow]
     fn AdviseSink(&self, riid: *const GUID, punk: Option<&IUnknown>) -> Result<u32> {
@@ -167,3 +259,23 @@ impl ITfSource_Impl for TextServiceFactory_Impl {
         Ok(())
     }
 }
+
+impl TextServiceFactory {
+    pub fn update_lang_bar(&self) -> Result<()> {
+        // change the icon of the language bar item
+        let text_service = self.borrow()?;
+        let thread_mgr = text_service.thread_mgr()?;
+
+        unsafe {
+            thread_mgr
+                .cast::<ITfLangBarItemMgr>()?
+                .RemoveItem(&text_service.this::<ITfLangBarItemButton>()?)?;
+
+            thread_mgr
+                .cast::<ITfLangBarItemMgr>()?
+                .AddItem(&text_service.this::<ITfLangBarItemButton>()?)?;
+        };
+
+        Ok(())
+    }
+}
@@ -7,8 +7,9 @@ use anyhow::{Context as _, Result};
 use windows::{
     core::{IUnknown, Interface},
     Win32::UI::TextServices::{
-        ITfCompartmentMgr, ITfContext, ITfDocumentMgr, GUID_COMPARTMENT_TRANSITORYEXTENSION_PARENT,
-        TF_ANCHOR_START, TF_DEFAULT_SELECTION, TF_HALTCOND, TF_HF_OBJECT, TF_SELECTION,
+        ITfCompartmentMgr, ITfContext, ITfDocumentMgr, ITfRange,
+        GUID_COMPARTMENT_TRANSITORYEXTENSION_PARENT, TF_AE_NONE, TF_ANCHOR_START,
+        TF_DEFAULT_SELECTION, TF_HALTCOND, TF_HF_OBJECT, TF_SELECTION, TF_SELECTIONSTYLE,
         TF_TF_MOVESTART, TS_SS_TRANSITORY,
     },
 };
@@ -184,4 +185,123 @@ impl TextServiceFactory {
             Ok(())
         }
     }
+
+    // the text before the selection and the selected text, used by the reconversion
+    pub fn surrounding_selection(&self) -> Result<(String, String)> {
+        unsafe {
+            let text_service = self.borrow()?;
+            let context = text_service.context::<ITfContext>()?;
+
+            let texts = edit_session::<(String, String)>(
+                text_service.tid,
+                context.clone(),
+                Rc::new(move |cookie| {
+                    let mut pselection: [TF_SELECTION; 1] = [TF_SELECTION::default()];
+                    let mut pfetched = 0;
+                    context.GetSelection(
+                        cookie,
+                        TF_DEFAULT_SELECTION,
+                        &mut pselection,
+                        &mut pfetched,
+                    )?;
+
+                    let prange = &pselection[0].range;
+                    let range = prange.as_ref().context("Range not found")?.Clone()?;
+
+                    let halt_cond = TF_HALTCOND {
+                        pHaltRange: ManuallyDrop::new(None),
+                        aHaltPos: TF_ANCHOR_START,
+                        dwFlags: TF_HF_OBJECT,
+                    };
+
+                    let mut shifted = 0;
+                    let preceding_range = range.Clone()?;
+                    preceding_range.Collapse(cookie, TF_ANCHOR_START)?;
+                    preceding_range.ShiftStart(cookie, -30, &mut shifted, &halt_cond)?;
+
+                    let mut pchtext = [0u16; 64];
+                    let mut pcch = 0;
+                    preceding_range.GetText(cookie, TF_TF_MOVESTART, &mut pchtext, &mut pcch)?;
+                    let preceding = String::from_utf16_lossy(&pchtext[..pcch as usize]);
+
+                    // the selection longer than the buffer is not reconverted, see reconversion::find_target
+                    let mut pchtext = [0u16; 128];
+                    let mut pcch = 0;
+                    range.GetText(cookie, TF_TF_MOVESTART, &mut pchtext, &mut pcch)?;
+                    let selected = String::from_utf16_lossy(&pchtext[..pcch as usize]);
+
+                    Ok((preceding, selected))
+                }),
+            )?;
+
+            texts.context("Failed to read the surrounding text")
+        }
+    }
+
+    // extend the selection to the characters before it, so that the composition covers them
+    pub fn select_preceding(&self, count: i32) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            let text_service = self.borrow()?;
+            let context = text_service.context::<ITfContext>()?;
+
+            edit_session::<()>(
+                text_service.tid,
+                context.clone(),
+                Rc::new(move |cookie| {
+                    let mut pselection: [TF_SELECTION; 1] = [TF_SELECTION::default()];
+                    let mut pfetched = 0;
+                    context.GetSelection(
+                        cookie,
+                        TF_DEFAULT_SELECTION,
+                        &mut pselection,
+                        &mut pfetched,
+                    )?;
+
+                    let range = pselection[0].range.as_ref().context("Range not found")?;
+                    let mut shifted = 0;
+                    range.ShiftStart(cookie, -count, &mut shifted, std::ptr::null())?;
+                    context.SetSelection(cookie, &pselection)?;
+
+                    Ok(())
+                }),
+            )?;
+
+            Ok(())
+        }
+    }
+
+    // used by ITfFnReconversion, the range is given by the application
+    pub fn select_range(&self, range: &ITfRange) -> Result<()> {
+        unsafe {
+            let text_service = self.borrow()?;
+            let context = text_service.context::<ITfContext>()?;
+
+            edit_session::<()>(
+                text_service.tid,
+                context.clone(),
+                Rc::new({
+                    let range = range.Clone()?;
+
+                    move |cookie| {
+                        let selection = TF_SELECTION {
+                            range: ManuallyDrop::new(Some(range.clone())),
+                            style: TF_SELECTIONSTYLE {
+                                ase: TF_AE_NONE,
+                                fInterimChar: false.into(),
+                            },
+                        };
+                        context.SetSelection(cookie, &[selection])?;
+
+                        Ok(())
+                    }
+                }),
+            )?;
+
+            Ok(())
+        }
+    }
 }
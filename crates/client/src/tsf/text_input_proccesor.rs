@@ -1,20 +1,24 @@
 use std::collections::HashMap;
 
 use crate::{
-    engine::{ipc_service, state::IMEState},
-    globals::{DllModule, GUID_DISPLAY_ATTRIBUTE},
+    engine::{
+        ipc_service,
+        state::{unwatch_config, watch_config, IMEState},
+    },
+    globals::{DllModule, GUID_DISPLAY_ATTRIBUTE, GUID_DISPLAY_ATTRIBUTE_FOCUSED},
 };
 
-use super::factory::TextServiceFactory_Impl;
+use super::{candidate_events::CandidateEvents, factory::TextServiceFactory_Impl};
 use windows::{
-    core::Interface as _,
+    core::{IUnknown, Interface as _},
     Win32::{
         Foundation::BOOL,
         System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER},
         UI::TextServices::{
-            CLSID_TF_CategoryMgr, ITfCategoryMgr, ITfKeyEventSink, ITfKeystrokeMgr,
-            ITfLangBarItemButton, ITfLangBarItemMgr, ITfSource, ITfTextInputProcessorEx_Impl,
-            ITfTextInputProcessor_Impl, ITfThreadMgr, ITfThreadMgrEventSink,
+            CLSID_TF_CategoryMgr, ITfCategoryMgr, ITfFunctionProvider, ITfKeyEventSink,
+            ITfKeystrokeMgr, ITfLangBarItemButton, ITfLangBarItemMgr, ITfSource, ITfSourceSingle,
+            ITfTextInputProcessor, ITfTextInputProcessorEx_Impl, ITfTextInputProcessor_Impl,
+            ITfThreadMgr, ITfThreadMgrEventSink,
         },
     },
 };
@@ -71,6 +75,16 @@ impl ITfTextInputProcessor_Impl for TextServiceFactory_Impl {
                 .insert(ITfThreadMgrEventSink::IID, cookie);
         };
 
+        // provide the reconversion to the system, e.g. Win+/
+        tracing::debug!("AdviseFunctionProvider");
+        unsafe {
+            thread_mgr.cast::<ITfSourceSingle>()?.AdviseSingleSink(
+                tid,
+                &ITfFunctionProvider::IID,
+                &text_service.this::<IUnknown>()?,
+            )?;
+        };
+
         // initialize text layout sink
         tracing::debug!("AdviseTextLayoutSink");
         let doc_mgr = unsafe { thread_mgr.GetFocus() };
@@ -85,8 +99,10 @@ impl ITfTextInputProcessor_Impl for TextServiceFactory_Impl {
             let category_mgr: ITfCategoryMgr =
                 CoCreateInstance(&CLSID_TF_CategoryMgr, None, CLSCTX_INPROC_SERVER)?;
 
-            let atom = category_mgr.RegisterGUID(&GUID_DISPLAY_ATTRIBUTE)?;
-            map.insert(GUID_DISPLAY_ATTRIBUTE, atom);
+            for guid in [GUID_DISPLAY_ATTRIBUTE, GUID_DISPLAY_ATTRIBUTE_FOCUSED] {
+                let atom = category_mgr.RegisterGUID(&guid)?;
+                map.insert(guid, atom);
+            }
             map
         };
 
@@ -100,6 +116,20 @@ impl ITfTextInputProcessor_Impl for TextServiceFactory_Impl {
                 .AddItem(&text_service.this::<ITfLangBarItemButton>()?)?;
         };
 
+        // follow the settings changed while the text service is active
+        tracing::debug!("Start config watcher");
+        watch_config();
+
+        // follow the mouse operations on the candidate window
+        tracing::debug!("Watch candidate window events");
+        let this = text_service.this::<ITfTextInputProcessor>()?;
+        if let Some(ipc_service) = IMEState::get()?.ipc_service.as_mut() {
+            match CandidateEvents::spawn(&this, ipc_service) {
+                Ok(events) => text_service.candidate_events = Some(events),
+                Err(e) => tracing::error!("Failed to watch candidate window events: {:?}", e),
+            }
+        }
+
         tracing::debug!("Activate success");
 
         Ok(())
@@ -148,6 +178,14 @@ impl ITfTextInputProcessor_Impl for TextServiceFactory_Impl {
             }
         };
 
+        // remove function provider
+        tracing::debug!("UnadviseFunctionProvider");
+        unsafe {
+            thread_mgr
+                .cast::<ITfSourceSingle>()?
+                .UnadviseSingleSink(text_service.tid, &ITfFunctionProvider::IID)?;
+        };
+
         // remove text layout sink
         tracing::debug!("UnadviseTextLayoutSink");
         text_service.unadvise_text_layout_sink()?;
@@ -155,6 +193,23 @@ impl ITfTextInputProcessor_Impl for TextServiceFactory_Impl {
         // clear display attribute
         text_service.display_attribute_atom.clear();
 
+        // close the session of the kkc server
+        // the server may be gone already, and the rest has to be torn down anyway
+        tracing::debug!("Close session");
+        if let Some(mut ipc_service) = IMEState::get()?.ipc_service.take() {
+            if let Err(e) = ipc_service.close_session() {
+                tracing::error!("Failed to close session: {:?}", e);
+            }
+        }
+
+        // stop the config watcher
+        tracing::debug!("Stop config watcher");
+        unwatch_config();
+
+        // stop watching the candidate window
+        tracing::debug!("Stop watching candidate window events");
+        text_service.candidate_events = None;
+
         text_service.tid = 0;
         text_service.thread_mgr = None;
 
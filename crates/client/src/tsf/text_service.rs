@@ -5,12 +5,14 @@ use std::{
 
 use windows::{
     core::{Interface, GUID},
-    Win32::UI::TextServices::{ITfContext, ITfTextInputProcessor, ITfThreadMgr},
+    Win32::UI::TextServices::{ITfComposition, ITfContext, ITfTextInputProcessor, ITfThreadMgr},
 };
 
 use anyhow::{Context, Result};
 
-use crate::engine::{composition::Composition, input_mode::InputMode};
+use azookey_engine::{composition::Composition, input_mode::InputMode};
+
+use super::candidate_events::CandidateEvents;
 
 #[derive(Default, Debug)]
 pub struct TextService {
@@ -18,9 +20,13 @@ pub struct TextService {
     pub thread_mgr: Option<ITfThreadMgr>,
     pub context: Option<ITfContext>,
     pub composition: RefCell<Composition>,
+    // the composition started in the document, apart from the platform independent Composition
+    pub tip_composition: RefCell<Option<ITfComposition>>,
     pub display_attribute_atom: HashMap<GUID, u32>,
     pub mode: InputMode,
     pub this: Option<ITfTextInputProcessor>,
+    // destroyed on Deactivate, so that no event is posted to the released text service
+    pub candidate_events: Option<CandidateEvents>,
 }
 
 impl TextService {
@@ -2,7 +2,9 @@ use windows::Win32::UI::TextServices::{ITfContext, ITfDocumentMgr, ITfThreadMgrE
 
 use anyhow::Result;
 
-use crate::engine::{client_action::ClientAction, composition::CompositionState};
+use azookey_engine::{client_action::ClientAction, composition::CompositionState};
+
+use crate::engine::state::{app_input_mode, IMEState};
 
 use super::factory::TextServiceFactory_Impl;
 
@@ -33,6 +35,17 @@ impl ITfThreadMgrEventSink_Impl for TextServiceFactory_Impl {
         let actions = vec![ClientAction::EndComposition];
         self.handle_action(&actions, CompositionState::None)?;
 
+        // restore the input mode of the app
+        if focus.is_some() {
+            if let Some(mode) = app_input_mode() {
+                let current = IMEState::get()?.input_mode.clone();
+                if mode != current {
+                    let actions = vec![ClientAction::SetIMEMode(mode)];
+                    self.handle_action(&actions, CompositionState::None)?;
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -0,0 +1,127 @@
+use shared::proto::{CandidateEntry, CandidateSource, ComposingText, Suggestion};
+
+#[derive(Debug, Clone, Default)]
+pub struct Candidates {
+    pub texts: Vec<String>,
+    pub sub_texts: Vec<String>,
+    pub hiragana: String,
+    pub corresponding_count: Vec<i32>,
+    pub cursor: i32, // caret position in the hiragana
+    pub predictions: Vec<Prediction>,
+
+    // shown in the candidate window, they may be shorter than the texts
+    pub readings: Vec<String>,
+    pub sources: Vec<CandidateSource>,
+    pub annotations: Vec<String>,
+    pub scores: Vec<f32>,
+}
+
+impl From<ComposingText> for Candidates {
+    fn from(composing_text: ComposingText) -> Self {
+        Candidates {
+            hiragana: composing_text.hiragana,
+            cursor: composing_text.cursor,
+            predictions: composing_text
+                .predictions
+                .iter()
+                .map(|p| Prediction {
+                    text: p.text.clone(),
+                    sub_text: p.subtext.clone(),
+                    corresponding_count: p.corresponding_count,
+                })
+                .collect(),
+            ..Candidates::from(composing_text.suggestions.as_slice())
+        }
+    }
+}
+
+impl From<&[Suggestion]> for Candidates {
+    fn from(suggestions: &[Suggestion]) -> Self {
+        Candidates {
+            texts: suggestions.iter().map(|s| s.text.clone()).collect(),
+            sub_texts: suggestions.iter().map(|s| s.subtext.clone()).collect(),
+            corresponding_count: suggestions.iter().map(|s| s.corresponding_count).collect(),
+            readings: suggestions.iter().map(|s| s.reading.clone()).collect(),
+            sources: suggestions.iter().map(|s| s.source()).collect(),
+            annotations: suggestions.iter().map(|s| s.annotation.clone()).collect(),
+            scores: suggestions.iter().map(|s| s.score).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Candidates {
+    // the text, the text after it and the corresponding count of the candidate
+    // the lists from the server may be shorter than expected, so the missing values are empty
+    pub fn candidate(&self, index: i32) -> (String, String, i32) {
+        let index = index as usize;
+        (
+            self.texts.get(index).cloned().unwrap_or_default(),
+            self.sub_texts.get(index).cloned().unwrap_or_default(),
+            self.corresponding_count
+                .get(index)
+                .copied()
+                .unwrap_or_default(),
+        )
+    }
+
+    // the metadata is sent along with the texts, so that the window can show the annotations
+    pub fn entries(&self) -> Vec<CandidateEntry> {
+        self.texts
+            .iter()
+            .enumerate()
+            .map(|(index, text)| CandidateEntry {
+                surface: text.clone(),
+                reading: self.readings.get(index).cloned().unwrap_or_default(),
+                source: self
+                    .sources
+                    .get(index)
+                    .map(|source| source.as_str_name().to_lowercase())
+                    .unwrap_or_default(),
+                annotation: self.annotations.get(index).cloned().unwrap_or_default(),
+                score: self.scores.get(index).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+// completion of the text before the caret, it is shown apart from the candidates
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Prediction {
+    pub text: String,
+    pub sub_text: String,
+    pub corresponding_count: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Clause {
+    pub reading: String,
+    pub surface: String,
+    pub corresponding_count: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_values_of_the_candidate_are_empty() {
+        let candidates = Candidates {
+            texts: vec!["仮名".to_string(), "かな".to_string()],
+            sub_texts: vec![String::new()],
+            corresponding_count: vec![2, 2],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            candidates.candidate(0),
+            ("仮名".to_string(), String::new(), 2)
+        );
+        assert_eq!(
+            candidates.candidate(1),
+            ("かな".to_string(), String::new(), 2)
+        );
+        assert_eq!(candidates.candidate(2), (String::new(), String::new(), 0));
+        assert_eq!(candidates.candidate(-1), (String::new(), String::new(), 0));
+    }
+}
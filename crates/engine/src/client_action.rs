@@ -0,0 +1,151 @@
+use crate::{
+    full_width::{to_fullwidth, to_halfwidth},
+    input_mode::InputMode,
+    text_util::{to_half_katakana, to_katakana},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum ClientAction {
+    StartComposition,
+    EndComposition,
+    CommitCandidate, // report the text to be committed, so that the server can learn it
+
+    AppendText(String),
+    RemoveText,
+    ShrinkText(String),
+    AcceptPrediction, // replace the text before the cursor with the first prediction
+    ChooseCandidate(i32), // replace the text with the candidate without converting by clauses
+
+    SetTextWithType(SetTextType),
+
+    MoveCursor(i32),
+    ShowUnconverted, // show the typed text without the conversion, keeping the reading
+    SetSelection(SetSelectionType),
+
+    FocusClause(i32),  // move the focus to the next (or previous if negative) clause
+    ResizeClause(i32), // extend (or shrink if negative) the focused clause
+
+    Reconvert,  // start the composition with the committed text around the selection
+    UndoCommit, // restore the last committed composition
+
+    SetIMEMode(InputMode),
+    ToggleInputStyle, // switch between the romaji input and the kana input until the settings are changed
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SetSelectionType {
+    Up,
+    Down,
+    Number(i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetTextType {
+    Hiragana,     // F6
+    Katakana,     // F7
+    HalfKatakana, // F8
+    FullLatin,    // F9
+    HalfLatin,    // F10
+}
+
+// text shown by F6-F10, count is how many times the same key was pressed before in a row
+// like MS-IME, F6-F8 convert a shorter prefix each time and leave the rest in the other kana,
+// and F9 and F10 cycle lowercase, UPPERCASE and Capitalized
+pub fn function_text(set_type: &SetTextType, count: usize, hiragana: &str, raw: &str) -> String {
+    let length = hiragana.chars().count();
+    let prefix_length = length - count % length.max(1);
+    let prefix: String = hiragana.chars().take(prefix_length).collect();
+    let rest: String = hiragana.chars().skip(prefix_length).collect();
+
+    let latin = match count % 3 {
+        0 => raw.to_lowercase(),
+        1 => raw.to_uppercase(),
+        _ => {
+            let lowercase = raw.to_lowercase();
+            let mut chars = lowercase.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => lowercase,
+            }
+        }
+    };
+
+    match set_type {
+        SetTextType::Hiragana => format!("{}{}", prefix, to_katakana(&rest)),
+        SetTextType::Katakana => format!("{}{}", to_katakana(&prefix), rest),
+        SetTextType::HalfKatakana => format!("{}{}", to_half_katakana(&prefix), rest),
+        SetTextType::FullLatin => to_fullwidth(&latin, true),
+        SetTextType::HalfLatin => to_halfwidth(&latin),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composition::Composition;
+
+    // "nihon" typed, and the text is converted by the client as the same key is pressed in a row
+    fn press(composition: &mut Composition, set_type: SetTextType) -> String {
+        let count = composition.text_type_count(&set_type);
+        let text = function_text(
+            &set_type,
+            count,
+            &composition.raw_hiragana,
+            &composition.raw_input.concat(),
+        );
+        composition.text_type = Some((set_type, count));
+        text
+    }
+
+    fn nihon() -> Composition {
+        Composition {
+            raw_input: vec!["ni".to_string(), "ho".to_string(), "nn".to_string()],
+            raw_hiragana: "にほん".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn repeated_kana_keys_convert_shorter_prefixes() {
+        let mut composition = nihon();
+        let texts: Vec<_> = (0..4)
+            .map(|_| press(&mut composition, SetTextType::Katakana))
+            .collect();
+        assert_eq!(texts, ["ニホン", "ニホん", "ニほん", "ニホン"]);
+
+        // another key starts from the whole text again
+        assert_eq!(press(&mut composition, SetTextType::HalfKatakana), "ﾆﾎﾝ");
+        assert_eq!(press(&mut composition, SetTextType::HalfKatakana), "ﾆﾎん");
+
+        assert_eq!(press(&mut composition, SetTextType::Hiragana), "にほん");
+        assert_eq!(press(&mut composition, SetTextType::Hiragana), "にほン");
+    }
+
+    #[test]
+    fn repeated_latin_keys_cycle_the_case() {
+        let mut composition = nihon();
+        let texts: Vec<_> = (0..4)
+            .map(|_| press(&mut composition, SetTextType::FullLatin))
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "ｎｉｈｏｎｎ",
+                "ＮＩＨＯＮＮ",
+                "Ｎｉｈｏｎｎ",
+                "ｎｉｈｏｎｎ"
+            ]
+        );
+
+        let texts: Vec<_> = (0..4)
+            .map(|_| press(&mut composition, SetTextType::HalfLatin))
+            .collect();
+        assert_eq!(texts, ["nihonn", "NIHONN", "Nihonn", "nihonn"]);
+    }
+
+    #[test]
+    fn empty_text_stays_empty() {
+        assert_eq!(function_text(&SetTextType::Katakana, 4, "", ""), "");
+        assert_eq!(function_text(&SetTextType::HalfLatin, 2, "", ""), "");
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{
+    candidates::{Candidates, Clause},
+    client_action::SetTextType,
+    undo_commit::LastCommit,
+};
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub enum CompositionState {
+    #[default]
+    None,
+    Composing,
+    Previewing,
+    Selecting,
+}
+
+// the TSF composition is kept by the text service, so that this can be used without windows
+#[derive(Default, Clone, Debug)]
+pub struct Composition {
+    pub preview: String,        // text to be previewed
+    pub suffix: String,         // text to be appended after preview
+    pub raw_input: Vec<String>, // keys typed for each character of the raw_hiragana
+    pub raw_hiragana: String,
+    pub pending: String, // romaji waiting for the next key, placed before the cursor
+
+    pub corresponding_count: i32, // corresponding count of the preview
+    pub cursor: i32,              // caret position in the raw_hiragana
+
+    pub clauses: Vec<Clause>, // clauses of the conversion, empty if not converting by clauses
+    pub clause_index: i32,    // index of the focused clause
+
+    pub selection_index: i32,
+    pub candidates: Candidates,
+
+    pub state: CompositionState,
+
+    pub text_type: Option<(SetTextType, usize)>, // F6-F10 pressed in a row, and how many times before
+    pub last_commit: Option<LastCommit>,         // kept until the next composition, see undo_commit
+}
+
+impl Composition {
+    // how many times the same F6-F10 key was pressed before in a row, another key starts again from 0
+    pub fn text_type_count(&self, set_type: &SetTextType) -> usize {
+        match &self.text_type {
+            Some((previous, count)) if previous == set_type => count + 1,
+            _ => 0,
+        }
+    }
+}
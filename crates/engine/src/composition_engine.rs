@@ -0,0 +1,1431 @@
+use crate::{
+    client_action::{ClientAction, SetSelectionType, SetTextType},
+    composition::{Composition, CompositionState},
+    input_mode::InputMode,
+    user_action::{Function, Navigation, UserAction},
+};
+
+// how far the shown text is from the typed text, Escape goes back one level at a time
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EscapeLevel {
+    ConvertingClauses, // converting by clauses with the live conversion
+    Converting,        // Previewing or Selecting a candidate
+    LiveConverted,     // Composing, and the text before the cursor is shown converted
+    Unconverted,       // Composing, and the typed text is shown as it is
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EscapeStep {
+    ShowConverted, // drop the clauses, and show the whole text converted
+    ShowUnconverted,
+    Cancel,
+}
+
+const ESCAPE_TABLE: &[(EscapeLevel, EscapeStep)] = &[
+    (EscapeLevel::ConvertingClauses, EscapeStep::ShowConverted),
+    (EscapeLevel::Converting, EscapeStep::ShowUnconverted),
+    (EscapeLevel::LiveConverted, EscapeStep::ShowUnconverted),
+    (EscapeLevel::Unconverted, EscapeStep::Cancel),
+];
+
+// platform independent transition table of the composition
+// this doesn't touch TSF or the key state, so every transition can be checked without windows
+#[derive(Default, Clone, Debug)]
+pub struct CompositionEngine {
+    // if false, the hiragana is shown until Space is pressed, and the conversion is done in Selecting
+    live_conversion: bool,
+    // count of the candidates in a page of the candidate window
+    page_size: i32,
+}
+
+impl CompositionEngine {
+    pub fn new(live_conversion: bool, page_size: i32) -> Self {
+        Self {
+            live_conversion,
+            page_size: page_size.max(1),
+        }
+    }
+
+    // returns None if the key should be passed through to the application
+    pub fn process(
+        &self,
+        action: &UserAction,
+        composition: &Composition,
+        mode: &InputMode,
+    ) -> Option<(Vec<ClientAction>, CompositionState)> {
+        let (transition, actions) = match composition.state {
+            CompositionState::None => self.process_none(action, composition, mode)?,
+            CompositionState::Composing
+            | CompositionState::Previewing
+            | CompositionState::Selecting => self.process_composing(action, composition, mode)?,
+        };
+
+        Some((actions, transition))
+    }
+
+    fn process_none(
+        &self,
+        action: &UserAction,
+        composition: &Composition,
+        mode: &InputMode,
+    ) -> Option<(CompositionState, Vec<ClientAction>)> {
+        let result = match action {
+            UserAction::Input(char) if *mode != InputMode::Latin => (
+                CompositionState::Composing,
+                vec![
+                    ClientAction::StartComposition,
+                    ClientAction::AppendText(char.to_string()),
+                ],
+            ),
+            UserAction::Number(number) if *mode != InputMode::Latin => (
+                CompositionState::Composing,
+                vec![
+                    ClientAction::StartComposition,
+                    ClientAction::AppendText(number.to_string()),
+                ],
+            ),
+            UserAction::ToggleInputMode => (
+                CompositionState::None,
+                vec![match mode {
+                    InputMode::Latin => ClientAction::SetIMEMode(InputMode::Kana),
+                    _ => ClientAction::SetIMEMode(InputMode::Latin),
+                }],
+            ),
+            UserAction::SetInputMode(mode) => (
+                CompositionState::None,
+                vec![ClientAction::SetIMEMode(mode.clone())],
+            ),
+            UserAction::ToggleInputStyle => {
+                (CompositionState::None, vec![ClientAction::ToggleInputStyle])
+            }
+            // the committed text is converted by clauses, as if Space was pressed after typing it
+            UserAction::Reconvert if *mode != InputMode::Latin => (
+                self.converting(),
+                vec![
+                    ClientAction::Reconvert,
+                    ClientAction::SetSelection(SetSelectionType::Number(0)),
+                ],
+            ),
+            // the key is passed through if there is nothing to undo, e.g. Backspace bound to UndoCommit
+            UserAction::UndoCommit if composition.last_commit.is_some() => {
+                (CompositionState::Previewing, vec![ClientAction::UndoCommit])
+            }
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    // Composing, Previewing and Selecting share the same table
+    // typing while converting commits the selected candidate first
+    // without the live conversion, the conversion is done in Selecting instead of Previewing
+    fn process_composing(
+        &self,
+        action: &UserAction,
+        composition: &Composition,
+        mode: &InputMode,
+    ) -> Option<(CompositionState, Vec<ClientAction>)> {
+        let previewing = composition.state != CompositionState::Composing;
+        let converting = self.converting();
+
+        // the first candidate of the page which has the selected one
+        let page_start = composition.selection_index / self.page_size * self.page_size;
+        let candidate_count = composition.candidates.texts.len() as i32;
+
+        let result = match action {
+            UserAction::Input(char) => (
+                CompositionState::Composing,
+                vec![Self::input_action(char.to_string(), previewing)],
+            ),
+            // while converting, 1-9 select the candidate in the shown page
+            UserAction::Number(number) if previewing && (1..=9).contains(number) => {
+                let index = page_start + *number as i32 - 1;
+                if *number as i32 <= self.page_size && index < candidate_count {
+                    (
+                        converting.clone(),
+                        vec![Self::select_action(composition, index)],
+                    )
+                } else {
+                    (composition.state.clone(), vec![])
+                }
+            }
+            UserAction::Number(number) => (
+                CompositionState::Composing,
+                vec![Self::input_action(number.to_string(), previewing)],
+            ),
+            UserAction::Backspace => {
+                if composition.cursor == 0 {
+                    // nothing to remove before the cursor
+                    (composition.state.clone(), vec![])
+                } else if composition.raw_hiragana.chars().count() == 1 {
+                    (
+                        CompositionState::None,
+                        vec![ClientAction::RemoveText, ClientAction::EndComposition],
+                    )
+                } else {
+                    (CompositionState::Composing, vec![ClientAction::RemoveText])
+                }
+            }
+            // without the live conversion, the text after the cursor is not a conversion left behind
+            UserAction::Enter => {
+                if composition.suffix.is_empty() || !self.live_conversion {
+                    (
+                        CompositionState::None,
+                        vec![ClientAction::CommitCandidate, ClientAction::EndComposition],
+                    )
+                } else {
+                    (
+                        CompositionState::Composing,
+                        vec![ClientAction::ShrinkText("".to_string())],
+                    )
+                }
+            }
+            UserAction::Escape => {
+                let level = self.escape_level(composition, mode);
+                let step = ESCAPE_TABLE
+                    .iter()
+                    .find(|(table_level, _)| *table_level == level)
+                    .map(|(_, step)| *step)?;
+
+                match step {
+                    // moving the cursor by 0 converts the whole text again without the clauses
+                    EscapeStep::ShowConverted => (
+                        CompositionState::Previewing,
+                        vec![ClientAction::MoveCursor(0)],
+                    ),
+                    EscapeStep::ShowUnconverted => (
+                        CompositionState::Composing,
+                        vec![ClientAction::ShowUnconverted],
+                    ),
+                    EscapeStep::Cancel => (
+                        CompositionState::None,
+                        vec![ClientAction::RemoveText, ClientAction::EndComposition],
+                    ),
+                }
+            }
+            // while converting by clauses, Left/Right moves the focus between clauses
+            UserAction::Navigation(Navigation::Right) if !composition.clauses.is_empty() => {
+                (converting.clone(), vec![ClientAction::FocusClause(1)])
+            }
+            UserAction::Navigation(Navigation::Left) if !composition.clauses.is_empty() => {
+                (converting.clone(), vec![ClientAction::FocusClause(-1)])
+            }
+            UserAction::Navigation(direction) => match direction {
+                Navigation::Right => (
+                    CompositionState::Composing,
+                    vec![ClientAction::MoveCursor(1)],
+                ),
+                Navigation::Left => (
+                    CompositionState::Composing,
+                    vec![ClientAction::MoveCursor(-1)],
+                ),
+                Navigation::Up => (
+                    converting.clone(),
+                    vec![ClientAction::SetSelection(SetSelectionType::Up)],
+                ),
+                Navigation::Down => (
+                    converting.clone(),
+                    vec![ClientAction::SetSelection(SetSelectionType::Down)],
+                ),
+            },
+            UserAction::ShiftNavigation(direction) => match direction {
+                Navigation::Right => (converting.clone(), vec![ClientAction::ResizeClause(1)]),
+                Navigation::Left => (converting.clone(), vec![ClientAction::ResizeClause(-1)]),
+                _ => return None,
+            },
+            UserAction::ToggleInputMode => (
+                CompositionState::None,
+                vec![
+                    ClientAction::CommitCandidate,
+                    ClientAction::EndComposition,
+                    ClientAction::SetIMEMode(InputMode::Latin),
+                ],
+            ),
+            UserAction::SetInputMode(mode) => (
+                CompositionState::None,
+                vec![
+                    ClientAction::CommitCandidate,
+                    ClientAction::EndComposition,
+                    ClientAction::SetIMEMode(mode.clone()),
+                ],
+            ),
+            UserAction::ToggleInputStyle => (
+                composition.state.clone(),
+                vec![ClientAction::ToggleInputStyle],
+            ),
+            // the first Space shows the best candidate, since the preview was the hiragana
+            UserAction::Space
+                if composition.state == CompositionState::Composing && !self.live_conversion =>
+            {
+                (
+                    converting.clone(),
+                    vec![ClientAction::SetSelection(SetSelectionType::Number(0))],
+                )
+            }
+            UserAction::Space => (
+                converting.clone(),
+                vec![ClientAction::SetSelection(SetSelectionType::Down)],
+            ),
+            // paging starts the conversion like Space, and then moves to the first candidate of the page
+            UserAction::NextPage if !previewing => {
+                return self.process_composing(&UserAction::Space, composition, mode)
+            }
+            UserAction::NextPage if page_start + self.page_size < candidate_count => (
+                converting.clone(),
+                vec![Self::select_action(
+                    composition,
+                    page_start + self.page_size,
+                )],
+            ),
+            UserAction::PrevPage if previewing && page_start > 0 => (
+                converting.clone(),
+                vec![Self::select_action(
+                    composition,
+                    page_start - self.page_size,
+                )],
+            ),
+            // no more pages
+            UserAction::NextPage | UserAction::PrevPage => (composition.state.clone(), vec![]),
+            // the prediction completes the text before the cursor, and the rest is left to be composed
+            UserAction::Tab
+                if !composition.candidates.predictions.is_empty()
+                    && composition.clauses.is_empty() =>
+            {
+                if composition.cursor >= composition.raw_hiragana.chars().count() as i32 {
+                    (
+                        CompositionState::None,
+                        vec![
+                            ClientAction::AcceptPrediction,
+                            ClientAction::CommitCandidate,
+                            ClientAction::EndComposition,
+                        ],
+                    )
+                } else {
+                    (
+                        CompositionState::Composing,
+                        vec![
+                            ClientAction::AcceptPrediction,
+                            ClientAction::ShrinkText("".to_string()),
+                        ],
+                    )
+                }
+            }
+            // without predictions, Tab converts like Space
+            UserAction::Tab => {
+                return self.process_composing(&UserAction::Space, composition, mode)
+            }
+            // the shown candidates are selected with the mouse only while converting
+            UserAction::HoverCandidate(index)
+                if previewing && (0..candidate_count).contains(index) =>
+            {
+                (
+                    converting.clone(),
+                    vec![Self::select_action(composition, *index)],
+                )
+            }
+            UserAction::HoverCandidate(_) => (composition.state.clone(), vec![]),
+            UserAction::ClickCandidate(index) if !(0..candidate_count).contains(index) => {
+                (composition.state.clone(), vec![])
+            }
+            UserAction::ClickCandidate(index) if previewing && !composition.clauses.is_empty() => (
+                CompositionState::None,
+                vec![
+                    ClientAction::SetSelection(SetSelectionType::Number(*index)),
+                    ClientAction::CommitCandidate,
+                    ClientAction::EndComposition,
+                ],
+            ),
+            // the candidates of the whole text, e.g. before the clauses are fetched
+            // the candidate may convert only the text before the cursor, and the rest is left like Enter
+            UserAction::ClickCandidate(index) => {
+                let sub_text = composition.candidates.sub_texts.get(*index as usize);
+                if sub_text.is_none_or(String::is_empty) {
+                    (
+                        CompositionState::None,
+                        vec![
+                            ClientAction::ChooseCandidate(*index),
+                            ClientAction::CommitCandidate,
+                            ClientAction::EndComposition,
+                        ],
+                    )
+                } else {
+                    (
+                        CompositionState::Composing,
+                        vec![
+                            ClientAction::ChooseCandidate(*index),
+                            ClientAction::ShrinkText("".to_string()),
+                        ],
+                    )
+                }
+            }
+            UserAction::Function(key) => (
+                converting.clone(),
+                vec![ClientAction::SetTextWithType(match key {
+                    Function::Six => SetTextType::Hiragana,
+                    Function::Seven => SetTextType::Katakana,
+                    Function::Eight => SetTextType::HalfKatakana,
+                    Function::Nine => SetTextType::FullLatin,
+                    Function::Ten => SetTextType::HalfLatin,
+                })],
+            ),
+            UserAction::Reconvert | UserAction::UndoCommit | UserAction::Unknown => return None,
+        };
+
+        Some(result)
+    }
+
+    // without the live conversion, the conversion is done in Selecting instead of Previewing
+    fn converting(&self) -> CompositionState {
+        if self.live_conversion {
+            CompositionState::Previewing
+        } else {
+            CompositionState::Selecting
+        }
+    }
+
+    // the direct input modes and the conversion without the live conversion show the typed text as it is
+    fn escape_level(&self, composition: &Composition, mode: &InputMode) -> EscapeLevel {
+        let shown = format!("{}{}", composition.preview, composition.suffix);
+
+        match composition.state {
+            CompositionState::Previewing | CompositionState::Selecting
+                if self.live_conversion && !mode.is_direct() && !composition.clauses.is_empty() =>
+            {
+                EscapeLevel::ConvertingClauses
+            }
+            CompositionState::Previewing | CompositionState::Selecting => EscapeLevel::Converting,
+            CompositionState::Composing
+                if self.live_conversion
+                    && !mode.is_direct()
+                    && shown != composition.raw_hiragana =>
+            {
+                EscapeLevel::LiveConverted
+            }
+            _ => EscapeLevel::Unconverted,
+        }
+    }
+
+    // the index is in the shown candidates, which are of the whole text until the clauses are fetched
+    // SetSelection fetches the clauses first, so the whole text candidate is chosen without them
+    fn select_action(composition: &Composition, index: i32) -> ClientAction {
+        if composition.clauses.is_empty() {
+            ClientAction::ChooseCandidate(index)
+        } else {
+            ClientAction::SetSelection(SetSelectionType::Number(index))
+        }
+    }
+
+    fn input_action(text: String, previewing: bool) -> ClientAction {
+        if previewing {
+            ClientAction::ShrinkText(text)
+        } else {
+            ClientAction::AppendText(text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        candidates::{Candidates, Clause, Prediction},
+        undo_commit::LastCommit,
+    };
+
+    const STATES: [CompositionState; 3] = [
+        CompositionState::Composing,
+        CompositionState::Previewing,
+        CompositionState::Selecting,
+    ];
+
+    fn all_actions() -> Vec<UserAction> {
+        let mut actions = vec![
+            UserAction::Input('a'),
+            UserAction::Backspace,
+            UserAction::Enter,
+            UserAction::Space,
+            UserAction::Escape,
+            UserAction::Tab,
+            UserAction::NextPage,
+            UserAction::PrevPage,
+            UserAction::Unknown,
+            UserAction::Number(0),
+            UserAction::Number(1),
+            UserAction::ToggleInputMode,
+            UserAction::ToggleInputStyle,
+            UserAction::SetInputMode(InputMode::Katakana),
+            UserAction::Reconvert,
+            UserAction::UndoCommit,
+            UserAction::HoverCandidate(1),
+            UserAction::ClickCandidate(1),
+        ];
+        for direction in [
+            Navigation::Up,
+            Navigation::Down,
+            Navigation::Left,
+            Navigation::Right,
+        ] {
+            actions.push(UserAction::Navigation(direction.clone()));
+            actions.push(UserAction::ShiftNavigation(direction));
+        }
+        for key in [
+            Function::Six,
+            Function::Seven,
+            Function::Eight,
+            Function::Nine,
+            Function::Ten,
+        ] {
+            actions.push(UserAction::Function(key));
+        }
+        actions
+    }
+
+    // "かな" typed and converted to "仮名", with the caret at the end
+    fn composition(state: CompositionState) -> Composition {
+        Composition {
+            preview: "仮名".to_string(),
+            raw_input: vec!["ka".to_string(), "na".to_string()],
+            raw_hiragana: "かな".to_string(),
+            corresponding_count: 2,
+            cursor: 2,
+            candidates: Candidates {
+                texts: vec!["仮名".to_string(), "かな".to_string(), "カナ".to_string()],
+                sub_texts: vec![String::new(), String::new(), String::new()],
+                hiragana: "かな".to_string(),
+                corresponding_count: vec![2, 2, 2],
+                cursor: 2,
+                ..Default::default()
+            },
+            state,
+            ..Default::default()
+        }
+    }
+
+    // converting by clauses, the candidates are of the focused clause
+    fn clause_composition(state: CompositionState) -> Composition {
+        Composition {
+            clauses: vec![Clause {
+                reading: "かな".to_string(),
+                surface: "仮名".to_string(),
+                corresponding_count: 2,
+            }],
+            ..composition(state)
+        }
+    }
+
+    fn live() -> CompositionEngine {
+        CompositionEngine::new(true, 9)
+    }
+
+    fn classic() -> CompositionEngine {
+        CompositionEngine::new(false, 9)
+    }
+
+    fn process(
+        engine: &CompositionEngine,
+        action: UserAction,
+        composition: &Composition,
+    ) -> Option<(Vec<ClientAction>, CompositionState)> {
+        engine.process(&action, composition, &InputMode::Kana)
+    }
+
+    #[test]
+    fn none_starts_the_composition_with_input() {
+        let none = Composition::default();
+
+        assert_eq!(
+            process(&live(), UserAction::Input('a'), &none),
+            Some((
+                vec![
+                    ClientAction::StartComposition,
+                    ClientAction::AppendText("a".to_string()),
+                ],
+                CompositionState::Composing,
+            ))
+        );
+        assert_eq!(
+            process(&live(), UserAction::Number(1), &none),
+            Some((
+                vec![
+                    ClientAction::StartComposition,
+                    ClientAction::AppendText("1".to_string()),
+                ],
+                CompositionState::Composing,
+            ))
+        );
+    }
+
+    #[test]
+    fn none_passes_through_in_latin() {
+        let none = Composition::default();
+
+        for action in [
+            UserAction::Input('a'),
+            UserAction::Number(1),
+            UserAction::Reconvert,
+        ] {
+            assert_eq!(live().process(&action, &none, &InputMode::Latin), None);
+        }
+    }
+
+    #[test]
+    fn none_switches_the_input_mode() {
+        let none = Composition::default();
+
+        assert_eq!(
+            live().process(&UserAction::ToggleInputMode, &none, &InputMode::Latin),
+            Some((
+                vec![ClientAction::SetIMEMode(InputMode::Kana)],
+                CompositionState::None
+            ))
+        );
+        assert_eq!(
+            live().process(&UserAction::ToggleInputMode, &none, &InputMode::Katakana),
+            Some((
+                vec![ClientAction::SetIMEMode(InputMode::Latin)],
+                CompositionState::None
+            ))
+        );
+        assert_eq!(
+            process(
+                &live(),
+                UserAction::SetInputMode(InputMode::FullLatin),
+                &none
+            ),
+            Some((
+                vec![ClientAction::SetIMEMode(InputMode::FullLatin)],
+                CompositionState::None
+            ))
+        );
+        assert_eq!(
+            process(&live(), UserAction::ToggleInputStyle, &none),
+            Some((vec![ClientAction::ToggleInputStyle], CompositionState::None))
+        );
+    }
+
+    #[test]
+    fn none_reconverts_into_the_conversion() {
+        let none = Composition::default();
+        let actions = vec![
+            ClientAction::Reconvert,
+            ClientAction::SetSelection(SetSelectionType::Number(0)),
+        ];
+
+        assert_eq!(
+            process(&live(), UserAction::Reconvert, &none),
+            Some((actions, CompositionState::Previewing))
+        );
+        assert_eq!(
+            process(&classic(), UserAction::Reconvert, &none).map(|(_, state)| state),
+            Some(CompositionState::Selecting)
+        );
+    }
+
+    #[test]
+    fn none_undoes_only_with_the_last_commit() {
+        let mut none = Composition::default();
+        assert_eq!(process(&live(), UserAction::UndoCommit, &none), None);
+
+        none.last_commit = Some(LastCommit {
+            preview: "仮名".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(
+            process(&live(), UserAction::UndoCommit, &none),
+            Some((vec![ClientAction::UndoCommit], CompositionState::Previewing))
+        );
+    }
+
+    #[test]
+    fn undone_commit_chooses_from_the_whole_text_candidates() {
+        // the client restores "カナ" with the candidates of the whole text, and the clauses are not fetched yet
+        let mut undone = composition(CompositionState::Previewing);
+        undone.preview = "カナ".to_string();
+        undone.selection_index = 2;
+
+        for engine in [live(), classic()] {
+            assert_eq!(
+                process(&engine, UserAction::Number(1), &undone),
+                Some((vec![ClientAction::ChooseCandidate(0)], engine.converting()))
+            );
+            assert_eq!(
+                process(&engine, UserAction::HoverCandidate(1), &undone),
+                Some((vec![ClientAction::ChooseCandidate(1)], engine.converting()))
+            );
+            // Space starts the conversion by clauses
+            assert_eq!(
+                process(&engine, UserAction::Space, &undone),
+                Some((
+                    vec![ClientAction::SetSelection(SetSelectionType::Down)],
+                    engine.converting()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn none_passes_through_the_other_keys() {
+        let none = Composition::default();
+
+        for action in all_actions() {
+            let handled = matches!(
+                action,
+                UserAction::Input(_)
+                    | UserAction::Number(_)
+                    | UserAction::ToggleInputMode
+                    | UserAction::ToggleInputStyle
+                    | UserAction::SetInputMode(_)
+                    | UserAction::Reconvert
+            );
+            if !handled {
+                assert_eq!(process(&live(), action.clone(), &none), None, "{action:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn every_action_keeps_the_composition_consistent() {
+        // the composition is never started again, and it ends only with EndComposition
+        for live_conversion in [true, false] {
+            let engine = CompositionEngine::new(live_conversion, 9);
+            for state in STATES {
+                for action in all_actions() {
+                    let Some((actions, transition)) =
+                        process(&engine, action.clone(), &composition(state.clone()))
+                    else {
+                        continue;
+                    };
+
+                    assert!(!actions.contains(&ClientAction::StartComposition));
+                    assert_eq!(
+                        actions.contains(&ClientAction::EndComposition),
+                        transition == CompositionState::None,
+                        "{state:?} {action:?} {actions:?}"
+                    );
+                    // the conversion goes to Previewing or Selecting depending on the setting
+                    let other = if live_conversion {
+                        CompositionState::Selecting
+                    } else {
+                        CompositionState::Previewing
+                    };
+                    assert!(transition == state || transition != other);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn input_appends_while_composing_and_commits_while_converting() {
+        for state in STATES {
+            let expected = if state == CompositionState::Composing {
+                ClientAction::AppendText("a".to_string())
+            } else {
+                ClientAction::ShrinkText("a".to_string())
+            };
+
+            assert_eq!(
+                process(&live(), UserAction::Input('a'), &composition(state)),
+                Some((vec![expected], CompositionState::Composing))
+            );
+        }
+    }
+
+    #[test]
+    fn number_selects_the_candidate_while_converting() {
+        assert_eq!(
+            process(
+                &live(),
+                UserAction::Number(1),
+                &composition(CompositionState::Composing)
+            ),
+            Some((
+                vec![ClientAction::AppendText("1".to_string())],
+                CompositionState::Composing
+            ))
+        );
+
+        for state in [CompositionState::Previewing, CompositionState::Selecting] {
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::Number(2),
+                    &clause_composition(state.clone())
+                ),
+                Some((
+                    vec![ClientAction::SetSelection(SetSelectionType::Number(1))],
+                    CompositionState::Previewing
+                ))
+            );
+            // before the clauses are fetched, the shown candidates are of the whole text
+            assert_eq!(
+                process(&live(), UserAction::Number(2), &composition(state.clone())),
+                Some((
+                    vec![ClientAction::ChooseCandidate(1)],
+                    CompositionState::Previewing
+                ))
+            );
+            // only 3 candidates are shown
+            assert_eq!(
+                process(&live(), UserAction::Number(5), &composition(state.clone())),
+                Some((vec![], state.clone()))
+            );
+            // 0 is not a candidate number, so it is typed
+            assert_eq!(
+                process(&live(), UserAction::Number(0), &composition(state)),
+                Some((
+                    vec![ClientAction::ShrinkText("0".to_string())],
+                    CompositionState::Composing
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn backspace_removes_or_ends_the_composition() {
+        for state in STATES {
+            let mut composition = composition(state.clone());
+            assert_eq!(
+                process(&live(), UserAction::Backspace, &composition),
+                Some((vec![ClientAction::RemoveText], CompositionState::Composing))
+            );
+
+            composition.cursor = 0;
+            assert_eq!(
+                process(&live(), UserAction::Backspace, &composition),
+                Some((vec![], state))
+            );
+
+            composition.cursor = 1;
+            composition.raw_hiragana = "か".to_string();
+            assert_eq!(
+                process(&live(), UserAction::Backspace, &composition),
+                Some((
+                    vec![ClientAction::RemoveText, ClientAction::EndComposition],
+                    CompositionState::None
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn enter_commits_or_leaves_the_rest() {
+        let commit = Some((
+            vec![ClientAction::CommitCandidate, ClientAction::EndComposition],
+            CompositionState::None,
+        ));
+
+        for state in STATES {
+            let mut composition = composition(state);
+            assert_eq!(process(&live(), UserAction::Enter, &composition), commit);
+
+            // the text after the cursor is left to be composed
+            composition.suffix = "な".to_string();
+            assert_eq!(
+                process(&live(), UserAction::Enter, &composition),
+                Some((
+                    vec![ClientAction::ShrinkText("".to_string())],
+                    CompositionState::Composing
+                ))
+            );
+            assert_eq!(process(&classic(), UserAction::Enter, &composition), commit);
+        }
+    }
+
+    #[test]
+    fn escape_from_clauses_shows_the_whole_conversion() {
+        for state in [CompositionState::Previewing, CompositionState::Selecting] {
+            let mut converting = composition(state);
+            converting.clauses = vec![Clause::default(), Clause::default()];
+            assert_eq!(
+                process(&live(), UserAction::Escape, &converting),
+                Some((
+                    vec![ClientAction::MoveCursor(0)],
+                    CompositionState::Previewing
+                ))
+            );
+        }
+
+        // without the live conversion, there is no converted text to go back to
+        let mut converting = composition(CompositionState::Selecting);
+        converting.clauses = vec![Clause::default()];
+        assert_eq!(
+            process(&classic(), UserAction::Escape, &converting),
+            Some((
+                vec![ClientAction::ShowUnconverted],
+                CompositionState::Composing
+            ))
+        );
+    }
+
+    #[test]
+    fn escape_from_previewing_shows_the_reading() {
+        for state in [CompositionState::Previewing, CompositionState::Selecting] {
+            for engine in [live(), classic()] {
+                assert_eq!(
+                    process(&engine, UserAction::Escape, &composition(state.clone())),
+                    Some((
+                        vec![ClientAction::ShowUnconverted],
+                        CompositionState::Composing
+                    ))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn escape_from_composing_shows_the_reading_or_cancels() {
+        // the live converted text goes back to the reading first
+        let mut composing = composition(CompositionState::Composing);
+        assert_eq!(
+            process(&live(), UserAction::Escape, &composing),
+            Some((
+                vec![ClientAction::ShowUnconverted],
+                CompositionState::Composing
+            ))
+        );
+
+        let cancel = Some((
+            vec![ClientAction::RemoveText, ClientAction::EndComposition],
+            CompositionState::None,
+        ));
+        composing.preview = "かな".to_string();
+        assert_eq!(process(&live(), UserAction::Escape, &composing), cancel);
+
+        // the reading split by the cursor is not converted either
+        composing.preview = "か".to_string();
+        composing.suffix = "な".to_string();
+        assert_eq!(process(&live(), UserAction::Escape, &composing), cancel);
+
+        // the classic conversion and the direct input modes show the typed text while composing
+        composing.preview = "仮名".to_string();
+        composing.suffix.clear();
+        assert_eq!(process(&classic(), UserAction::Escape, &composing), cancel);
+        assert_eq!(
+            live().process(&UserAction::Escape, &composing, &InputMode::Katakana),
+            cancel
+        );
+    }
+
+    #[test]
+    fn navigation_moves_the_cursor_or_the_selection() {
+        for state in STATES {
+            let composition = composition(state);
+            let expected = [
+                (Navigation::Left, ClientAction::MoveCursor(-1), false),
+                (Navigation::Right, ClientAction::MoveCursor(1), false),
+                (
+                    Navigation::Up,
+                    ClientAction::SetSelection(SetSelectionType::Up),
+                    true,
+                ),
+                (
+                    Navigation::Down,
+                    ClientAction::SetSelection(SetSelectionType::Down),
+                    true,
+                ),
+            ];
+
+            for (direction, action, converting) in expected {
+                let transition = if converting {
+                    CompositionState::Previewing
+                } else {
+                    CompositionState::Composing
+                };
+                assert_eq!(
+                    process(&live(), UserAction::Navigation(direction), &composition),
+                    Some((vec![action], transition))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn navigation_moves_between_clauses() {
+        for state in STATES {
+            let mut composition = composition(state);
+            composition.clauses = vec![Clause::default(), Clause::default()];
+
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::Navigation(Navigation::Right),
+                    &composition
+                ),
+                Some((
+                    vec![ClientAction::FocusClause(1)],
+                    CompositionState::Previewing
+                ))
+            );
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::Navigation(Navigation::Left),
+                    &composition
+                ),
+                Some((
+                    vec![ClientAction::FocusClause(-1)],
+                    CompositionState::Previewing
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn shift_navigation_resizes_the_clause() {
+        for state in STATES {
+            let composition = composition(state);
+
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::ShiftNavigation(Navigation::Right),
+                    &composition
+                ),
+                Some((
+                    vec![ClientAction::ResizeClause(1)],
+                    CompositionState::Previewing
+                ))
+            );
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::ShiftNavigation(Navigation::Left),
+                    &composition
+                ),
+                Some((
+                    vec![ClientAction::ResizeClause(-1)],
+                    CompositionState::Previewing
+                ))
+            );
+            for direction in [Navigation::Up, Navigation::Down] {
+                assert_eq!(
+                    process(
+                        &live(),
+                        UserAction::ShiftNavigation(direction),
+                        &composition
+                    ),
+                    None
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mode_changes_commit_the_composition() {
+        for state in STATES {
+            let composition = composition(state.clone());
+
+            assert_eq!(
+                process(&live(), UserAction::ToggleInputMode, &composition),
+                Some((
+                    vec![
+                        ClientAction::CommitCandidate,
+                        ClientAction::EndComposition,
+                        ClientAction::SetIMEMode(InputMode::Latin),
+                    ],
+                    CompositionState::None
+                ))
+            );
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::SetInputMode(InputMode::HalfKatakana),
+                    &composition
+                ),
+                Some((
+                    vec![
+                        ClientAction::CommitCandidate,
+                        ClientAction::EndComposition,
+                        ClientAction::SetIMEMode(InputMode::HalfKatakana),
+                    ],
+                    CompositionState::None
+                ))
+            );
+            assert_eq!(
+                process(&live(), UserAction::ToggleInputStyle, &composition),
+                Some((vec![ClientAction::ToggleInputStyle], state))
+            );
+        }
+    }
+
+    #[test]
+    fn space_converts() {
+        for state in STATES {
+            assert_eq!(
+                process(&live(), UserAction::Space, &composition(state)),
+                Some((
+                    vec![ClientAction::SetSelection(SetSelectionType::Down)],
+                    CompositionState::Previewing
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn space_shows_the_best_candidate_first_without_live_conversion() {
+        let engine = classic();
+
+        assert_eq!(
+            process(
+                &engine,
+                UserAction::Space,
+                &composition(CompositionState::Composing)
+            ),
+            Some((
+                vec![ClientAction::SetSelection(SetSelectionType::Number(0))],
+                CompositionState::Selecting
+            ))
+        );
+        assert_eq!(
+            process(
+                &engine,
+                UserAction::Space,
+                &composition(CompositionState::Selecting)
+            ),
+            Some((
+                vec![ClientAction::SetSelection(SetSelectionType::Down)],
+                CompositionState::Selecting
+            ))
+        );
+    }
+
+    #[test]
+    fn paging_moves_to_the_first_candidate_of_the_page() {
+        let engine = CompositionEngine::new(true, 2);
+
+        // paging starts the conversion while composing
+        assert_eq!(
+            process(
+                &engine,
+                UserAction::NextPage,
+                &composition(CompositionState::Composing)
+            ),
+            process(
+                &engine,
+                UserAction::Space,
+                &composition(CompositionState::Composing)
+            )
+        );
+        assert_eq!(
+            process(
+                &engine,
+                UserAction::PrevPage,
+                &composition(CompositionState::Composing)
+            ),
+            Some((vec![], CompositionState::Composing))
+        );
+
+        for state in [CompositionState::Previewing, CompositionState::Selecting] {
+            let mut composition = clause_composition(state.clone());
+            assert_eq!(
+                process(&engine, UserAction::NextPage, &composition),
+                Some((
+                    vec![ClientAction::SetSelection(SetSelectionType::Number(2))],
+                    CompositionState::Previewing
+                ))
+            );
+            assert_eq!(
+                process(&engine, UserAction::PrevPage, &composition),
+                Some((vec![], state.clone()))
+            );
+
+            composition.selection_index = 2;
+            assert_eq!(
+                process(&engine, UserAction::NextPage, &composition),
+                Some((vec![], state))
+            );
+            assert_eq!(
+                process(&engine, UserAction::PrevPage, &composition),
+                Some((
+                    vec![ClientAction::SetSelection(SetSelectionType::Number(0))],
+                    CompositionState::Previewing
+                ))
+            );
+
+            // the second page of the whole text candidates
+            composition.clauses.clear();
+            composition.selection_index = 0;
+            assert_eq!(
+                process(&engine, UserAction::NextPage, &composition),
+                Some((
+                    vec![ClientAction::ChooseCandidate(2)],
+                    CompositionState::Previewing
+                ))
+            );
+            composition.selection_index = 2;
+            assert_eq!(
+                process(&engine, UserAction::Number(1), &composition),
+                Some((
+                    vec![ClientAction::ChooseCandidate(2)],
+                    CompositionState::Previewing
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn tab_accepts_the_prediction() {
+        for state in STATES {
+            let mut composition = composition(state.clone());
+
+            // without predictions, Tab converts like Space
+            assert_eq!(
+                process(&live(), UserAction::Tab, &composition),
+                process(&live(), UserAction::Space, &composition)
+            );
+
+            composition.candidates.predictions = vec![Prediction {
+                text: "仮名遣い".to_string(),
+                ..Default::default()
+            }];
+            assert_eq!(
+                process(&live(), UserAction::Tab, &composition),
+                Some((
+                    vec![
+                        ClientAction::AcceptPrediction,
+                        ClientAction::CommitCandidate,
+                        ClientAction::EndComposition,
+                    ],
+                    CompositionState::None
+                ))
+            );
+
+            composition.cursor = 1;
+            assert_eq!(
+                process(&live(), UserAction::Tab, &composition),
+                Some((
+                    vec![
+                        ClientAction::AcceptPrediction,
+                        ClientAction::ShrinkText("".to_string()),
+                    ],
+                    CompositionState::Composing
+                ))
+            );
+
+            // the predictions are not used while converting by clauses
+            composition.clauses = vec![Clause::default()];
+            assert_eq!(
+                process(&live(), UserAction::Tab, &composition),
+                process(&live(), UserAction::Space, &composition)
+            );
+        }
+    }
+
+    #[test]
+    fn hover_selects_only_while_converting() {
+        assert_eq!(
+            process(
+                &live(),
+                UserAction::HoverCandidate(1),
+                &composition(CompositionState::Composing)
+            ),
+            Some((vec![], CompositionState::Composing))
+        );
+
+        for state in [CompositionState::Previewing, CompositionState::Selecting] {
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::HoverCandidate(1),
+                    &clause_composition(state.clone())
+                ),
+                Some((
+                    vec![ClientAction::SetSelection(SetSelectionType::Number(1))],
+                    CompositionState::Previewing
+                ))
+            );
+            // before the clauses are fetched, the shown candidates are of the whole text
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::HoverCandidate(1),
+                    &composition(state.clone())
+                ),
+                Some((
+                    vec![ClientAction::ChooseCandidate(1)],
+                    CompositionState::Previewing
+                ))
+            );
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::HoverCandidate(3),
+                    &composition(state.clone())
+                ),
+                Some((vec![], state))
+            );
+        }
+    }
+
+    #[test]
+    fn click_commits_the_candidate() {
+        for state in [CompositionState::Previewing, CompositionState::Selecting] {
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::ClickCandidate(2),
+                    &clause_composition(state.clone())
+                ),
+                Some((
+                    vec![
+                        ClientAction::SetSelection(SetSelectionType::Number(2)),
+                        ClientAction::CommitCandidate,
+                        ClientAction::EndComposition,
+                    ],
+                    CompositionState::None
+                ))
+            );
+            // before the clauses are fetched, the shown candidates are of the whole text
+            assert_eq!(
+                process(
+                    &live(),
+                    UserAction::ClickCandidate(2),
+                    &composition(state.clone())
+                ),
+                Some((
+                    vec![
+                        ClientAction::ChooseCandidate(2),
+                        ClientAction::CommitCandidate,
+                        ClientAction::EndComposition,
+                    ],
+                    CompositionState::None
+                ))
+            );
+        }
+
+        let mut composing = composition(CompositionState::Composing);
+        assert_eq!(
+            process(&live(), UserAction::ClickCandidate(1), &composing),
+            Some((
+                vec![
+                    ClientAction::ChooseCandidate(1),
+                    ClientAction::CommitCandidate,
+                    ClientAction::EndComposition,
+                ],
+                CompositionState::None
+            ))
+        );
+
+        // the candidate converts only "か", and "な" is left
+        composing.candidates.sub_texts[1] = "な".to_string();
+        assert_eq!(
+            process(&live(), UserAction::ClickCandidate(1), &composing),
+            Some((
+                vec![
+                    ClientAction::ChooseCandidate(1),
+                    ClientAction::ShrinkText("".to_string()),
+                ],
+                CompositionState::Composing
+            ))
+        );
+    }
+
+    #[test]
+    fn click_with_missing_sub_texts_commits_the_candidate() {
+        let mut composing = composition(CompositionState::Composing);
+        composing.candidates.sub_texts.clear();
+
+        assert_eq!(
+            process(&live(), UserAction::ClickCandidate(1), &composing),
+            Some((
+                vec![
+                    ClientAction::ChooseCandidate(1),
+                    ClientAction::CommitCandidate,
+                    ClientAction::EndComposition,
+                ],
+                CompositionState::None
+            ))
+        );
+    }
+
+    #[test]
+    fn click_outside_of_the_candidates_is_ignored() {
+        for state in STATES {
+            for index in [-1, 3] {
+                assert_eq!(
+                    process(
+                        &live(),
+                        UserAction::ClickCandidate(index),
+                        &composition(state.clone())
+                    ),
+                    Some((vec![], state.clone()))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn function_keys_set_the_text_type() {
+        let expected = [
+            (Function::Six, SetTextType::Hiragana),
+            (Function::Seven, SetTextType::Katakana),
+            (Function::Eight, SetTextType::HalfKatakana),
+            (Function::Nine, SetTextType::FullLatin),
+            (Function::Ten, SetTextType::HalfLatin),
+        ];
+
+        for state in STATES {
+            for (key, text_type) in expected.clone() {
+                assert_eq!(
+                    process(
+                        &live(),
+                        UserAction::Function(key),
+                        &composition(state.clone())
+                    ),
+                    Some((
+                        vec![ClientAction::SetTextWithType(text_type)],
+                        CompositionState::Previewing
+                    ))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn partially_cycled_text_is_committed_as_shown() {
+        // F7 was pressed twice after typing "nihon", and the client converts the whole text
+        let mut composition = Composition {
+            preview: "ニほん".to_string(),
+            raw_input: vec!["ni".to_string(), "ho".to_string(), "nn".to_string()],
+            raw_hiragana: "にほん".to_string(),
+            corresponding_count: 3,
+            cursor: 3,
+            state: CompositionState::Previewing,
+            text_type: Some((SetTextType::Katakana, 1)),
+            ..Default::default()
+        };
+        assert_eq!(composition.text_type_count(&SetTextType::Katakana), 2);
+
+        for engine in [live(), classic()] {
+            composition.state = engine.converting();
+            assert_eq!(
+                process(&engine, UserAction::Enter, &composition),
+                Some((
+                    vec![ClientAction::CommitCandidate, ClientAction::EndComposition],
+                    CompositionState::None
+                ))
+            );
+            // typing commits the shown text first
+            assert_eq!(
+                process(&engine, UserAction::Input('a'), &composition),
+                Some((
+                    vec![ClientAction::ShrinkText("a".to_string())],
+                    CompositionState::Composing
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn composition_passes_through_the_other_keys() {
+        for state in STATES {
+            for action in [
+                UserAction::Reconvert,
+                UserAction::UndoCommit,
+                UserAction::Unknown,
+            ] {
+                assert_eq!(process(&live(), action, &composition(state.clone())), None);
+            }
+        }
+    }
+}
@@ -131,6 +131,32 @@ static HALF_FULL: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(
         ("x", "ｘ"),
         ("y", "ｙ"),
         ("z", "ｚ"),
+        ("A", "Ａ"),
+        ("B", "Ｂ"),
+        ("C", "Ｃ"),
+        ("D", "Ｄ"),
+        ("E", "Ｅ"),
+        ("F", "Ｆ"),
+        ("G", "Ｇ"),
+        ("H", "Ｈ"),
+        ("I", "Ｉ"),
+        ("J", "Ｊ"),
+        ("K", "Ｋ"),
+        ("L", "Ｌ"),
+        ("M", "Ｍ"),
+        ("N", "Ｎ"),
+        ("O", "Ｏ"),
+        ("P", "Ｐ"),
+        ("Q", "Ｑ"),
+        ("R", "Ｒ"),
+        ("S", "Ｓ"),
+        ("T", "Ｔ"),
+        ("U", "Ｕ"),
+        ("V", "Ｖ"),
+        ("W", "Ｗ"),
+        ("X", "Ｘ"),
+        ("Y", "Ｙ"),
+        ("Z", "Ｚ"),
     ])
 });
 
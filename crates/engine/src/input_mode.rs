@@ -0,0 +1,56 @@
+use shared::InputModeSetting;
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub enum InputMode {
+    #[default]
+    Latin,
+    Kana,
+    // the direct input modes, the typed text is shown as it is instead of the conversion candidate
+    Katakana,
+    HalfKatakana,
+    FullLatin,
+}
+
+impl InputMode {
+    pub fn is_direct(&self) -> bool {
+        matches!(
+            self,
+            InputMode::Katakana | InputMode::HalfKatakana | InputMode::FullLatin
+        )
+    }
+
+    // text shown in the mode indicator
+    pub fn indicator(&self) -> &'static str {
+        match self {
+            InputMode::Latin => "A",
+            InputMode::Kana => "あ",
+            InputMode::Katakana => "カ",
+            InputMode::HalfKatakana => "ｶ",
+            InputMode::FullLatin => "Ａ",
+        }
+    }
+}
+
+impl From<InputModeSetting> for InputMode {
+    fn from(mode: InputModeSetting) -> Self {
+        match mode {
+            InputModeSetting::Hiragana => InputMode::Kana,
+            InputModeSetting::Katakana => InputMode::Katakana,
+            InputModeSetting::HalfKatakana => InputMode::HalfKatakana,
+            InputModeSetting::FullLatin => InputMode::FullLatin,
+            InputModeSetting::Latin => InputMode::Latin,
+        }
+    }
+}
+
+impl From<&InputMode> for InputModeSetting {
+    fn from(mode: &InputMode) -> Self {
+        match mode {
+            InputMode::Kana => InputModeSetting::Hiragana,
+            InputMode::Katakana => InputModeSetting::Katakana,
+            InputMode::HalfKatakana => InputModeSetting::HalfKatakana,
+            InputMode::FullLatin => InputModeSetting::FullLatin,
+            InputMode::Latin => InputModeSetting::Latin,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::input_mode::InputMode;
+
+// platform independent model of the drop-down menu of the language bar item
+// the tsf side only converts the items to ITfMenu and dispatches the selected command
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuCommand {
+    SetInputMode(InputMode),
+    ToggleZenzai,
+    OpenSettings,
+    AddWord,
+    RestartServer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuItemKind {
+    // one of the group is selected
+    Radio(bool),
+    Check(bool),
+    Button,
+    Separator,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuItem {
+    pub id: u32,
+    pub label: &'static str,
+    pub kind: MenuItemKind,
+}
+
+// the ids are fixed, so the selected id can be mapped to the command without the menu state
+const INPUT_MODES: &[(u32, &str, InputMode)] = &[
+    (1, "ひらがな", InputMode::Kana),
+    (2, "全角カタカナ", InputMode::Katakana),
+    (3, "半角カタカナ", InputMode::HalfKatakana),
+    (4, "全角英数", InputMode::FullLatin),
+    (5, "半角英数", InputMode::Latin),
+];
+
+const ZENZAI_ID: u32 = 10;
+const OPEN_SETTINGS_ID: u32 = 20;
+const ADD_WORD_ID: u32 = 21;
+const RESTART_SERVER_ID: u32 = 30;
+
+pub fn menu_items(mode: &InputMode, zenzai_enabled: bool) -> Vec<MenuItem> {
+    let mut items: Vec<MenuItem> = INPUT_MODES
+        .iter()
+        .map(|(id, label, item_mode)| MenuItem {
+            id: *id,
+            label,
+            kind: MenuItemKind::Radio(item_mode == mode),
+        })
+        .collect();
+
+    items.push(separator());
+    items.push(MenuItem {
+        id: ZENZAI_ID,
+        label: "Zenzai",
+        kind: MenuItemKind::Check(zenzai_enabled),
+    });
+    items.push(separator());
+    items.push(MenuItem {
+        id: OPEN_SETTINGS_ID,
+        label: "設定を開く",
+        kind: MenuItemKind::Button,
+    });
+    items.push(MenuItem {
+        id: ADD_WORD_ID,
+        label: "単語の登録",
+        kind: MenuItemKind::Button,
+    });
+    items.push(separator());
+    items.push(MenuItem {
+        id: RESTART_SERVER_ID,
+        label: "変換サーバーを再起動",
+        kind: MenuItemKind::Button,
+    });
+
+    items
+}
+
+pub fn menu_command(id: u32) -> Option<MenuCommand> {
+    if let Some((_, _, mode)) = INPUT_MODES.iter().find(|(mode_id, _, _)| *mode_id == id) {
+        return Some(MenuCommand::SetInputMode(mode.clone()));
+    }
+
+    match id {
+        ZENZAI_ID => Some(MenuCommand::ToggleZenzai),
+        OPEN_SETTINGS_ID => Some(MenuCommand::OpenSettings),
+        ADD_WORD_ID => Some(MenuCommand::AddWord),
+        RESTART_SERVER_ID => Some(MenuCommand::RestartServer),
+        _ => None,
+    }
+}
+
+// separators are never selected, so they share the id 0
+fn separator() -> MenuItem {
+    MenuItem {
+        id: 0,
+        label: "",
+        kind: MenuItemKind::Separator,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [InputMode; 5] = [
+        InputMode::Latin,
+        InputMode::Kana,
+        InputMode::Katakana,
+        InputMode::HalfKatakana,
+        InputMode::FullLatin,
+    ];
+
+    #[test]
+    fn one_radio_item_is_selected() {
+        for mode in MODES {
+            let selected: Vec<u32> = menu_items(&mode, false)
+                .into_iter()
+                .filter(|item| item.kind == MenuItemKind::Radio(true))
+                .map(|item| item.id)
+                .collect();
+
+            assert_eq!(selected.len(), 1);
+            assert_eq!(
+                menu_command(selected[0]),
+                Some(MenuCommand::SetInputMode(mode))
+            );
+        }
+    }
+
+    #[test]
+    fn zenzai_is_checked_by_the_flag() {
+        for enabled in [true, false] {
+            let zenzai = menu_items(&InputMode::Kana, enabled)
+                .into_iter()
+                .find(|item| menu_command(item.id) == Some(MenuCommand::ToggleZenzai))
+                .unwrap();
+            assert_eq!(zenzai.kind, MenuItemKind::Check(enabled));
+        }
+    }
+
+    #[test]
+    fn every_item_has_a_command() {
+        let items = menu_items(&InputMode::Kana, true);
+        let mut commands = Vec::new();
+        for item in items
+            .iter()
+            .filter(|item| item.kind != MenuItemKind::Separator)
+        {
+            let command = menu_command(item.id);
+            assert!(command.is_some(), "{} has no command", item.label);
+            assert!(!commands.contains(&command), "{} is duplicated", item.label);
+            commands.push(command);
+        }
+
+        // the separators are never dispatched
+        assert_eq!(menu_command(separator().id), None);
+        assert_eq!(commands.len(), MODES.len() + 4);
+    }
+}
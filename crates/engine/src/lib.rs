@@ -0,0 +1,13 @@
+// platform independent part of the composition, used by the client dll
+// this must not depend on windows, so that it can be tested on any platform
+pub mod candidates;
+pub mod client_action;
+pub mod composition;
+pub mod composition_engine;
+pub mod full_width;
+pub mod input_mode;
+pub mod lang_bar_menu;
+pub mod reconversion;
+pub mod text_util;
+pub mod undo_commit;
+pub mod user_action;
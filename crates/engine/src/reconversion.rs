@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+
+// platform independent part of the reconversion of the committed text
+// the text service reads the text around the selection, and this decides the range and the reading
+
+// the readings of this many commits are kept
+const HISTORY_SIZE: usize = 32;
+// the selection longer than this is not reconverted, since it is not a phrase anymore
+const MAX_SURFACE_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Committed {
+    pub surface: String,
+    pub reading: String,
+}
+
+// texts committed by this client, the latest one comes last
+#[derive(Debug, Clone, Default)]
+pub struct CommitHistory {
+    entries: VecDeque<Committed>,
+}
+
+impl CommitHistory {
+    pub fn push(&mut self, surface: &str, reading: &str) {
+        if surface.is_empty() || reading.is_empty() {
+            return;
+        }
+
+        self.entries.push_back(Committed {
+            surface: surface.to_string(),
+            reading: reading.to_string(),
+        });
+        if self.entries.len() > HISTORY_SIZE {
+            self.entries.pop_front();
+        }
+    }
+
+    // the surface may be made of the consecutive commits, e.g. the clauses committed at once
+    pub fn reading_of(&self, surface: &str) -> Option<String> {
+        let entries: Vec<&Committed> = self.entries.iter().collect();
+
+        for end in (0..entries.len()).rev() {
+            let mut joined = String::new();
+            for start in (0..=end).rev() {
+                joined.insert_str(0, &entries[start].surface);
+                if joined == surface {
+                    return Some(
+                        entries[start..=end]
+                            .iter()
+                            .map(|entry| entry.reading.as_str())
+                            .collect(),
+                    );
+                }
+                if !surface.ends_with(&joined) {
+                    break;
+                }
+            }
+        }
+
+        None
+    }
+
+    // the last commit, if the caret is still right after it
+    pub fn last_before(&self, preceding: &str) -> Option<&Committed> {
+        self.entries
+            .back()
+            .filter(|entry| preceding.ends_with(&entry.surface))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reconversion {
+    pub surface: String,
+    // count of the characters before the selection which are reconverted together
+    pub preceding_count: i32,
+    // None if the reading has to be looked up by the server
+    pub reading: Option<String>,
+}
+
+// the selected text is reconverted as it is
+// without the selection, the last commit before the caret is reconverted,
+// or the japanese text before the caret if the caret has been moved since then
+pub fn find_target(
+    preceding: &str,
+    selected: &str,
+    history: &CommitHistory,
+) -> Option<Reconversion> {
+    if !selected.is_empty() {
+        if selected.chars().count() > MAX_SURFACE_LENGTH || selected.contains(['\r', '\n']) {
+            return None;
+        }
+
+        return Some(Reconversion {
+            surface: selected.to_string(),
+            preceding_count: 0,
+            reading: history
+                .reading_of(selected)
+                .or_else(|| kana_reading(selected)),
+        });
+    }
+
+    if let Some(committed) = history.last_before(preceding) {
+        return Some(Reconversion {
+            surface: committed.surface.clone(),
+            preceding_count: committed.surface.chars().count() as i32,
+            reading: Some(committed.reading.clone()),
+        });
+    }
+
+    let surface: String = {
+        let mut chars: Vec<char> = preceding
+            .chars()
+            .rev()
+            .take_while(|c| is_japanese(*c))
+            .collect();
+        chars.reverse();
+        chars.into_iter().collect()
+    };
+    if surface.is_empty() {
+        return None;
+    }
+
+    Some(Reconversion {
+        preceding_count: surface.chars().count() as i32,
+        reading: kana_reading(&surface),
+        surface,
+    })
+}
+
+// the kana are read as they are, and the katakana is shifted to the hiragana
+pub fn kana_reading(text: &str) -> Option<String> {
+    text.chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' | 'ー' => Some(c),
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60),
+            _ => None,
+        })
+        .collect()
+}
+
+// the kanji and the kana, the punctuations and the latin letters end the phrase
+fn is_japanese(c: char) -> bool {
+    matches!(c, 'ぁ'..='ゖ' | 'ァ'..='ヶ' | 'ー' | '々' | '〆' | '一'..='鿿' | '㐀'..='䶿')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(commits: &[(&str, &str)]) -> CommitHistory {
+        let mut history = CommitHistory::default();
+        for (surface, reading) in commits {
+            history.push(surface, reading);
+        }
+        history
+    }
+
+    #[test]
+    fn reading_of_joins_consecutive_commits() {
+        let history = history(&[("今日", "きょう"), ("天気", "てんき"), ("は", "は")]);
+
+        assert_eq!(history.reading_of("は"), Some("は".to_string()));
+        assert_eq!(history.reading_of("天気は"), Some("てんきは".to_string()));
+        assert_eq!(
+            history.reading_of("今日天気は"),
+            Some("きょうてんきは".to_string())
+        );
+        // the commits which are not next to each other are not joined
+        assert_eq!(history.reading_of("今日は"), None);
+        // a part of a commit has no reading
+        assert_eq!(history.reading_of("気は"), None);
+    }
+
+    #[test]
+    fn empty_commits_are_not_kept() {
+        let history = history(&[("天気", ""), ("", "てんき")]);
+        assert_eq!(history.last_before("天気"), None);
+    }
+
+    #[test]
+    fn old_commits_are_dropped() {
+        let mut history = history(&[("最初", "さいしょ")]);
+        for _ in 0..HISTORY_SIZE {
+            history.push("字", "じ");
+        }
+        assert_eq!(history.reading_of("最初"), None);
+        assert_eq!(history.reading_of("字"), Some("じ".to_string()));
+    }
+
+    #[test]
+    fn last_before_needs_the_caret_after_the_commit() {
+        let history = history(&[("天気", "てんき"), ("は", "は")]);
+
+        let last = history.last_before("今日の天気は").unwrap();
+        assert_eq!(last.surface, "は");
+        assert_eq!(history.last_before("天気は。"), None);
+        assert_eq!(history.last_before(""), None);
+    }
+
+    #[test]
+    fn selection_is_reconverted_as_it_is() {
+        let history = history(&[("天気", "てんき"), ("は", "は")]);
+
+        assert_eq!(
+            find_target("", "天気は", &history),
+            Some(Reconversion {
+                surface: "天気は".to_string(),
+                preceding_count: 0,
+                reading: Some("てんきは".to_string()),
+            })
+        );
+        // the reading of the text which was not committed by this client is looked up by the server
+        assert_eq!(find_target("", "漢字", &history).unwrap().reading, None);
+        assert_eq!(
+            find_target("", "カナ", &history).unwrap().reading,
+            Some("かな".to_string())
+        );
+        assert_eq!(find_target("", "一行目\r\n二行目", &history), None);
+        let long = "字".repeat(MAX_SURFACE_LENGTH + 1);
+        assert_eq!(find_target("", &long, &history), None);
+    }
+
+    #[test]
+    fn last_commit_is_reconverted_without_the_selection() {
+        let history = history(&[("天気", "てんき")]);
+
+        assert_eq!(
+            find_target("今日の天気", "", &history),
+            Some(Reconversion {
+                surface: "天気".to_string(),
+                preceding_count: 2,
+                reading: Some("てんき".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn japanese_text_before_the_caret_is_scanned_backwards() {
+        let history = history(&[("天気", "てんき")]);
+
+        // the caret has been moved since the last commit
+        assert_eq!(
+            find_target("abc 漢字カナ", "", &history),
+            Some(Reconversion {
+                surface: "漢字カナ".to_string(),
+                preceding_count: 4,
+                reading: None,
+            })
+        );
+        assert_eq!(
+            find_target("これは、ひらがなー", "", &history),
+            Some(Reconversion {
+                surface: "ひらがなー".to_string(),
+                preceding_count: 5,
+                reading: Some("ひらがなー".to_string()),
+            })
+        );
+        assert_eq!(find_target("abc", "", &history), None);
+        assert_eq!(find_target("", "", &history), None);
+    }
+
+    #[test]
+    fn kana_reading_shifts_the_katakana() {
+        assert_eq!(kana_reading("カタカナ"), Some("かたかな".to_string()));
+        assert_eq!(kana_reading("ァヶ"), Some("ぁゖ".to_string()));
+        assert_eq!(
+            kana_reading("ひらがなとカナー"),
+            Some("ひらがなとかなー".to_string())
+        );
+        assert_eq!(kana_reading("漢字"), None);
+        assert_eq!(kana_reading("ｶﾅ"), None);
+        assert_eq!(kana_reading(""), Some(String::new()));
+    }
+}
@@ -2,7 +2,7 @@
 use std::collections::HashMap;
 use std::sync::LazyLock;
 
-use super::full_width::to_halfwidth;
+use crate::full_width::to_halfwidth;
 
 #[allow(dead_code)]
 static KANA_MAP: LazyLock<HashMap<&'static str, (&'static str, &'static str)>> =
@@ -119,9 +119,7 @@ static KANA_MAP: LazyLock<HashMap<&'static str, (&'static str, &'static str)>> =
 
 pub fn to_katakana(s: &str) -> String {
     let mut result = String::new();
-    let mut iter = s.chars().peekable();
-
-    while let Some(c) = iter.next() {
+    for c in s.chars() {
         if let Some(&(katakana, _)) = KANA_MAP.get(&c.to_string().as_str()) {
             result.push_str(katakana);
         } else {
@@ -134,9 +132,7 @@ pub fn to_katakana(s: &str) -> String {
 
 pub fn to_half_katakana(s: &str) -> String {
     let mut result = String::new();
-    let mut iter = s.chars().peekable();
-
-    while let Some(c) = iter.next() {
+    for c in s.chars() {
         let c = to_halfwidth(&c.to_string()).chars().next().unwrap();
 
         if let Some(&(_, hankaku_katakana)) = KANA_MAP.get(&c.to_string().as_str()) {
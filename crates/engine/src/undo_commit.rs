@@ -0,0 +1,29 @@
+// platform independent part of undoing the last commit
+// the composition is kept when it is committed, and restored by UndoCommit
+// while the caret is still right after the committed text
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct LastCommit {
+    pub preview: String, // selected candidate
+    pub suffix: String,  // unconverted rest, committed as it is
+    pub raw_input: Vec<String>,
+    pub raw_hiragana: String,
+    pub corresponding_count: i32,
+}
+
+impl LastCommit {
+    // the text inserted into the document by the commit
+    pub fn text(&self) -> String {
+        format!("{}{}", self.preview, self.suffix)
+    }
+
+    // the text before the caret is read with a limited length, so it may be a part of the committed text
+    pub fn is_before_caret(&self, preceding: &str, selected: &str) -> bool {
+        let text = self.text();
+        if text.is_empty() || !selected.is_empty() || preceding.is_empty() {
+            return false;
+        }
+
+        preceding.ends_with(&text) || text.ends_with(preceding)
+    }
+}
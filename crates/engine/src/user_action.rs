@@ -0,0 +1,79 @@
+use shared::keymap::KeyCommand;
+
+use crate::input_mode::InputMode;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserAction {
+    Input(char),
+    Backspace,
+    Enter,
+    Space,
+    Escape,
+    Tab,
+    NextPage,
+    PrevPage,
+    Unknown,
+    Navigation(Navigation),
+    ShiftNavigation(Navigation), // Shift+Left/Right
+    Function(Function),
+    Number(i8),
+    ToggleInputMode,
+    ToggleInputStyle,
+    SetInputMode(InputMode),
+    Reconvert,
+    UndoCommit,
+    // the mouse operations on the candidate window, with the index of the candidate
+    HoverCandidate(i32),
+    ClickCandidate(i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Navigation {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Function {
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+}
+
+impl From<KeyCommand> for UserAction {
+    fn from(command: KeyCommand) -> UserAction {
+        match command {
+            KeyCommand::Commit => UserAction::Enter,
+            KeyCommand::Cancel => UserAction::Escape,
+            KeyCommand::Backspace => UserAction::Backspace,
+            KeyCommand::NextCandidate => UserAction::Space,
+            KeyCommand::PrevCandidate => UserAction::Navigation(Navigation::Up),
+            KeyCommand::AcceptPrediction => UserAction::Tab,
+            KeyCommand::NextPage => UserAction::NextPage,
+            KeyCommand::PrevPage => UserAction::PrevPage,
+            KeyCommand::MoveLeft => UserAction::Navigation(Navigation::Left),
+            KeyCommand::MoveRight => UserAction::Navigation(Navigation::Right),
+            KeyCommand::ShrinkClause => UserAction::ShiftNavigation(Navigation::Left),
+            KeyCommand::ExtendClause => UserAction::ShiftNavigation(Navigation::Right),
+            KeyCommand::ToHiragana => UserAction::Function(Function::Six),
+            KeyCommand::ToKatakana => UserAction::Function(Function::Seven),
+            KeyCommand::ToHalfKatakana => UserAction::Function(Function::Eight),
+            KeyCommand::ToFullLatin => UserAction::Function(Function::Nine),
+            KeyCommand::ToHalfLatin => UserAction::Function(Function::Ten),
+            KeyCommand::ToggleInputMode => UserAction::ToggleInputMode,
+            KeyCommand::ToggleInputStyle => UserAction::ToggleInputStyle,
+            KeyCommand::HiraganaMode => UserAction::SetInputMode(InputMode::Kana),
+            KeyCommand::KatakanaMode => UserAction::SetInputMode(InputMode::Katakana),
+            KeyCommand::HalfKatakanaMode => UserAction::SetInputMode(InputMode::HalfKatakana),
+            KeyCommand::FullLatinMode => UserAction::SetInputMode(InputMode::FullLatin),
+            KeyCommand::LatinMode => UserAction::SetInputMode(InputMode::Latin),
+            KeyCommand::Reconvert => UserAction::Reconvert,
+            KeyCommand::UndoCommit => UserAction::UndoCommit,
+            KeyCommand::PassThrough => UserAction::Unknown,
+        }
+    }
+}
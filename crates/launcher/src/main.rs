@@ -1,18 +1,18 @@
-use shared::AppConfig;
+use shared::config_watcher::{self, ConfigWatcher};
+use shared::{AppConfig, SERVER_RESTART_EXIT_CODE};
 use std::io::{BufRead, BufReader};
-use std::process::{Child, Command, Stdio};
-use std::{env, thread};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::{env, io, thread};
 
 fn main() -> anyhow::Result<()> {
-    let config = AppConfig::new();
+    // the engine should start even if the settings can't be saved
+    let config = AppConfig::new().unwrap_or_else(|e| {
+        eprintln!("[launcher]: failed to load settings: {}", e);
+        AppConfig::default()
+    });
 
     let exe_path = env::current_exe()?.parent().unwrap().to_path_buf();
-    let backend_dir = match config.zenzai.backend.as_str() {
-        "cpu" => "llama_cpu",
-        "cuda" => "llama_cuda",
-        "vulkan" => "llama_vulkan",
-        _ => "llama_cpu",
-    };
+    let backend_dir = config.zenzai.backend.library_dir();
 
     let backend_path = exe_path.join(backend_dir);
     let backend_path_str = backend_path.to_string_lossy();
@@ -21,11 +21,36 @@ fn main() -> anyhow::Result<()> {
     new_path = format!("{};{}", backend_path_str, new_path);
     env::set_var("PATH", &new_path);
 
+    // the other settings are applied by each process, but the backend is fixed by PATH at startup
+    let mut backend = config.zenzai.backend;
+    let _config_watcher = ConfigWatcher::spawn(config_watcher::DEFAULT_INTERVAL, move |config| {
+        if config.zenzai.backend != backend {
+            backend = config.zenzai.backend;
+            println!("[launcher]: zenzai backend changed, restart to apply");
+        }
+    });
+
     let server_process = start_process("azookey-server.exe", "[server]");
     let ui_process = start_process("ui.exe", "[ui]");
 
     if let (Some(mut server), Some(mut ui)) = (server_process, ui_process) {
-        let server_handle = thread::spawn(move || server.wait());
+        // the server falls back to the mock backend by itself if the swift runtime fails to load
+        let server_handle = thread::spawn(move || -> io::Result<ExitStatus> {
+            loop {
+                let status = server.wait()?;
+
+                // restart requested from the language bar
+                if status.code() != Some(SERVER_RESTART_EXIT_CODE) {
+                    return Ok(status);
+                }
+                println!("[server]: restarting");
+
+                server = match start_process("azookey-server.exe", "[server]") {
+                    Some(server) => server,
+                    None => return Ok(status),
+                };
+            }
+        });
         let ui_handle = thread::spawn(move || ui.wait());
 
         let _ = server_handle.join();
@@ -1,8 +1,17 @@
 use std::env;
 
 fn main() {
-    // link dll
+    // link dll only when the swift backend is enabled, the swift library is built only for windows
+    if env::var("CARGO_FEATURE_SWIFT").is_err() || env::var("CARGO_CFG_WINDOWS").is_err() {
+        return;
+    }
+
     let project_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
     println!("cargo:rustc-link-search={}/target/", project_dir);
     println!("cargo:rustc-link-lib=azookey-server");
+
+    // the dll is loaded on the first call instead of at startup
+    // so that the server can fall back to the mock backend if the swift runtime is missing
+    println!("cargo:rustc-link-arg=/DELAYLOAD:azookey-server.dll");
+    println!("cargo:rustc-link-lib=delayimp");
 }
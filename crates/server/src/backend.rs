@@ -0,0 +1,67 @@
+pub mod mock;
+#[cfg(all(windows, feature = "swift"))]
+pub mod swift;
+
+use shared::proto::{Clause, Suggestion};
+use shared::user_dict::UserDictEntry;
+use shared::AppConfig;
+
+pub struct RawComposingText {
+    pub text: String,
+    // caret position in the text, in characters
+    pub cursor: i32,
+}
+
+// kana-kanji conversion engine used by the AzookeyService
+// the swift library is the real one, and the mock is a pure rust engine for testing or fallback
+// one instance holds the composing text of one session
+pub trait ConversionBackend: Send {
+    fn append_text(&mut self, input: &str) -> RawComposingText;
+    fn remove_text(&mut self) -> RawComposingText;
+    // remove characters before the cursor and append the input, without converting in between
+    fn replace_text(&mut self, remove_count: usize, input: &str) -> RawComposingText {
+        for _ in 0..remove_count {
+            self.remove_text();
+        }
+        self.append_text(input)
+    }
+    fn move_cursor(&mut self, offset: i32) -> RawComposingText;
+    fn shrink_text(&mut self, offset: i32) -> RawComposingText;
+    fn clear_text(&mut self);
+    fn get_composed_text(&mut self) -> Vec<Suggestion>;
+    // words which complete the text before the cursor, the best first
+    fn get_predictions(&mut self) -> Vec<Suggestion>;
+    fn set_context(&mut self, context: &str);
+
+    // split the best path into clauses
+    // the lengths of the resized clauses are kept until the composing text is changed
+    fn get_clauses(&mut self) -> Vec<Clause>;
+    fn resize_clause(&mut self, index: usize, offset: i32) -> Vec<Clause>;
+    fn get_clause_candidates(&mut self, index: usize) -> Vec<Suggestion>;
+
+    // the reading of a committed text, used by the reconversion
+    // None if a part of the surface is not found in the dictionaries
+    fn reverse_lookup(&mut self, surface: &str) -> Option<String>;
+
+    // learn the candidate committed by the user
+    // it is ignored if the learning is disabled or read only
+    fn commit_candidate(&mut self, reading: &str, surface: &str, corresponding_count: i32);
+}
+
+// the kana are read as they are, and the katakana is shifted to the hiragana
+pub fn kana_reading(c: char) -> Option<char> {
+    match c {
+        'ぁ'..='ゖ' | 'ー' => Some(c),
+        'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60),
+        _ => None,
+    }
+}
+
+// creates a backend for each session, and handles the state shared between sessions
+pub trait BackendFactory: Send {
+    fn create(&self, session_id: u32) -> Box<dyn ConversionBackend>;
+    fn load_config(&self, config: &AppConfig);
+    // replace the words registered by the user, they are shared between sessions
+    fn set_user_dictionary(&self, entries: &[UserDictEntry]);
+    fn clear_learning_history(&self);
+}
@@ -0,0 +1,724 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use shared::proto::{CandidateSource, Clause, Suggestion};
+use shared::romaji::RomajiTable;
+use shared::user_dict::UserDictEntry;
+use shared::{AppConfig, LearningConfig};
+
+use super::{kana_reading, BackendFactory, ConversionBackend, RawComposingText};
+
+// the same romaji table as the client, the real table lives in AzooKeyKanaKanjiConverter
+static ROMAJI_TABLE: LazyLock<RomajiTable> = LazyLock::new(RomajiTable::default);
+
+// small static dictionary: reading and its surfaces ordered by priority
+const DICTIONARY: &[(&str, &[&str])] = &[
+    ("にほん", &["日本", "二本"]),
+    ("にほんご", &["日本語"]),
+    ("かんじ", &["漢字", "感じ", "幹事"]),
+    ("へんかん", &["変換", "返還"]),
+    ("にゅうりょく", &["入力"]),
+    ("もじ", &["文字"]),
+    ("わたし", &["私"]),
+    ("きょう", &["今日", "京"]),
+    ("てんき", &["天気", "転機"]),
+    ("あめ", &["雨", "飴"]),
+    ("はし", &["橋", "箸", "端"]),
+    ("き", &["木", "気"]),
+    ("ご", &["語", "後"]),
+    ("は", &["葉", "歯"]),
+    ("せかい", &["世界"]),
+    ("こんにちは", &["こんにちは", "今日は"]),
+    ("たかはし", &["高橋", "髙橋"]),
+    ("さくら", &["桜", "櫻", "🌸"]),
+    ("すし", &["寿司", "鮨", "🍣"]),
+];
+
+// notes shown beside the candidates, to tell the homophones apart
+const ANNOTATIONS: &[(&str, &str)] = &[
+    ("髙橋", "[環境依存]"),
+    ("櫻", "旧字体"),
+    ("鮨", "異体字"),
+    ("🌸", "[環境依存]"),
+    ("🍣", "[環境依存]"),
+];
+
+// committed surfaces of each reading, the latest one comes first
+// the mock keeps them in memory only, the swift backend persists them under the config root
+#[derive(Debug, Default)]
+pub struct Learning {
+    config: LearningConfig,
+    history: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default)]
+pub struct MockBackendFactory {
+    // shared with every session, so that the registered words are used at once
+    user_dictionary: Arc<RwLock<Vec<UserDictEntry>>>,
+    learning: Arc<RwLock<Learning>>,
+}
+
+impl MockBackendFactory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl BackendFactory for MockBackendFactory {
+    fn create(&self, _session_id: u32) -> Box<dyn ConversionBackend> {
+        Box::new(MockBackend::new(
+            self.user_dictionary.clone(),
+            self.learning.clone(),
+        ))
+    }
+
+    fn load_config(&self, config: &AppConfig) {
+        if let Ok(mut learning) = self.learning.write() {
+            learning.config = config.learning.clone();
+        }
+    }
+
+    fn set_user_dictionary(&self, entries: &[UserDictEntry]) {
+        if let Ok(mut user_dictionary) = self.user_dictionary.write() {
+            *user_dictionary = entries.to_vec();
+        }
+    }
+
+    fn clear_learning_history(&self) {
+        if let Ok(mut learning) = self.learning.write() {
+            learning.history.clear();
+        }
+    }
+}
+
+struct Segment {
+    kana: String,
+    raw_count: i32,
+}
+
+// pure rust conversion engine
+// it converts romaji with a small table and looks up candidates in a static dictionary
+#[derive(Default)]
+pub struct MockBackend {
+    segments: Vec<Segment>,
+    // romaji which is not converted yet, it is always placed at the cursor
+    pending: String,
+    // count of the segments before the cursor
+    cursor: usize,
+    // reading lengths of the clauses resized by the user
+    clause_lengths: Vec<usize>,
+    user_dictionary: Arc<RwLock<Vec<UserDictEntry>>>,
+    learning: Arc<RwLock<Learning>>,
+}
+
+impl MockBackend {
+    pub fn new(
+        user_dictionary: Arc<RwLock<Vec<UserDictEntry>>>,
+        learning: Arc<RwLock<Learning>>,
+    ) -> Self {
+        Self {
+            user_dictionary,
+            learning,
+            ..Self::default()
+        }
+    }
+
+    fn composing_text(&self) -> RawComposingText {
+        RawComposingText {
+            text: self.hiragana(),
+            cursor: self.cursor_position() as i32,
+        }
+    }
+
+    // kana and raw count of each unit, the pending romaji is split into characters
+    fn units(&self) -> Vec<(String, i32)> {
+        let before = self.segments[..self.cursor]
+            .iter()
+            .map(|s| (s.kana.clone(), s.raw_count));
+        let pending = self.pending.chars().map(|c| (c.to_string(), 1));
+        let after = self.segments[self.cursor..]
+            .iter()
+            .map(|s| (s.kana.clone(), s.raw_count));
+
+        before.chain(pending).chain(after).collect()
+    }
+
+    fn hiragana(&self) -> String {
+        self.units().into_iter().map(|(kana, _)| kana).collect()
+    }
+
+    // cursor position in characters
+    fn cursor_position(&self) -> usize {
+        self.segments[..self.cursor]
+            .iter()
+            .map(|s| s.kana.chars().count())
+            .sum::<usize>()
+            + self.pending.chars().count()
+    }
+
+    fn push_segment(&mut self, kana: &str, raw_count: i32) {
+        self.segments.insert(
+            self.cursor,
+            Segment {
+                kana: kana.to_string(),
+                raw_count,
+            },
+        );
+        self.cursor += 1;
+    }
+
+    // insert the pending romaji as they are
+    fn flush_pending(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        for p in pending.chars() {
+            self.push_segment(&p.to_string(), 1);
+        }
+    }
+
+    // converts a key with the default romaji table of the client
+    fn feed(&mut self, c: char) {
+        let pending = std::mem::take(&mut self.pending);
+        let (output, next) = ROMAJI_TABLE.feed(&pending, c);
+        if !output.is_empty() {
+            // the keys which are not kept as the next pending input belong to the output
+            let raw_count = (pending.chars().count() + 1).saturating_sub(next.chars().count());
+            self.push_segment(&output, raw_count.max(1) as i32);
+        }
+        self.pending = next;
+    }
+
+    fn raw_count(&self, kana_count: usize) -> Option<i32> {
+        // count of raw inputs which correspond to the first `kana_count` characters
+        let mut count = 0;
+        let mut raw_count = 0;
+        for (kana, unit_raw_count) in self.units() {
+            if count == kana_count {
+                return Some(raw_count);
+            }
+            count += kana.chars().count();
+            raw_count += unit_raw_count;
+        }
+
+        if count == kana_count {
+            Some(raw_count)
+        } else {
+            None
+        }
+    }
+
+    // the learned surfaces come first, then the words registered by the user and the static dictionary
+    fn lookup(&self, reading: &str) -> Vec<String> {
+        let mut surfaces: Vec<String> = self
+            .learning
+            .read()
+            .ok()
+            .filter(|learning| learning.config.enable)
+            .and_then(|learning| learning.history.get(reading).cloned())
+            .unwrap_or_default();
+
+        let registered: Vec<String> = self
+            .user_dictionary
+            .read()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|e| e.reading == reading)
+                    .map(|e| e.surface.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let builtin = DICTIONARY
+            .iter()
+            .find(|(r, _)| *r == reading)
+            .map(|(_, surfaces)| *surfaces)
+            .unwrap_or(&[])
+            .iter()
+            .map(|s| s.to_string());
+
+        for surface in registered.into_iter().chain(builtin) {
+            if !surfaces.contains(&surface) {
+                surfaces.push(surface);
+            }
+        }
+
+        surfaces
+    }
+
+    // the learned surfaces and the registered words are marked, so that the window can show where they come from
+    fn suggestion(
+        &self,
+        reading: &str,
+        text: String,
+        subtext: String,
+        corresponding_count: i32,
+        rank: usize,
+    ) -> Suggestion {
+        let learned = self
+            .learning
+            .read()
+            .ok()
+            .filter(|learning| learning.config.enable)
+            .and_then(|learning| learning.history.get(reading).cloned())
+            .is_some_and(|surfaces| surfaces.contains(&text));
+        let registered = self
+            .user_dictionary
+            .read()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .any(|e| e.reading == reading && e.surface == text)
+            })
+            .unwrap_or_default();
+
+        let source = if is_emoji(&text) {
+            CandidateSource::Emoji
+        } else if learned {
+            CandidateSource::Learned
+        } else if registered {
+            CandidateSource::User
+        } else {
+            CandidateSource::Dictionary
+        };
+        let annotation = ANNOTATIONS
+            .iter()
+            .find(|(surface, _)| *surface == text)
+            .map(|(_, annotation)| annotation.to_string())
+            .unwrap_or_default();
+
+        Suggestion {
+            text,
+            subtext,
+            corresponding_count,
+            reading: reading.to_string(),
+            source: source as i32,
+            annotation,
+            // the mock has no cost, so the earlier one gets the higher score
+            score: -(rank as f32),
+        }
+    }
+
+    // readings which start with the given reading and are longer than it, the shortest first
+    fn completions(&self, reading: &str) -> Vec<String> {
+        let learned: Vec<String> = self
+            .learning
+            .read()
+            .ok()
+            .filter(|learning| learning.config.enable)
+            .map(|learning| learning.history.keys().cloned().collect())
+            .unwrap_or_default();
+
+        let registered: Vec<String> = self
+            .user_dictionary
+            .read()
+            .map(|entries| entries.iter().map(|e| e.reading.clone()).collect())
+            .unwrap_or_default();
+
+        let builtin = DICTIONARY.iter().map(|(r, _)| r.to_string());
+
+        let mut readings: Vec<String> = vec![];
+        for candidate in learned.into_iter().chain(registered).chain(builtin) {
+            if candidate.len() > reading.len()
+                && candidate.starts_with(reading)
+                && !readings.contains(&candidate)
+            {
+                readings.push(candidate);
+            }
+        }
+        readings.sort_by_key(|r| r.chars().count());
+
+        readings
+    }
+
+    // the reading of a word, the learned and registered words are looked up first as in lookup
+    fn reading_of(&self, surface: &str) -> Option<String> {
+        let learned = self
+            .learning
+            .read()
+            .ok()
+            .filter(|learning| learning.config.enable)
+            .and_then(|learning| {
+                learning
+                    .history
+                    .iter()
+                    .find(|(_, surfaces)| surfaces.iter().any(|s| s == surface))
+                    .map(|(reading, _)| reading.clone())
+            });
+
+        let registered = || {
+            self.user_dictionary.read().ok().and_then(|entries| {
+                entries
+                    .iter()
+                    .find(|e| e.surface == surface)
+                    .map(|e| e.reading.clone())
+            })
+        };
+
+        let builtin = || {
+            DICTIONARY
+                .iter()
+                .find(|(_, surfaces)| surfaces.contains(&surface))
+                .map(|(reading, _)| reading.to_string())
+        };
+
+        learned.or_else(registered).or_else(builtin)
+    }
+
+    // reading lengths of the clauses, the resized ones come first
+    fn segment(&self) -> Vec<usize> {
+        let chars: Vec<char> = self.hiragana().chars().collect();
+        let mut lengths = vec![];
+        let mut start = 0;
+
+        for length in &self.clause_lengths {
+            if start >= chars.len() {
+                break;
+            }
+            let length = (*length).clamp(1, chars.len() - start);
+            lengths.push(length);
+            start += length;
+        }
+
+        // the rest is split at the longest dictionary matches
+        // and the unknown characters are grouped into one clause
+        let mut unknown = 0;
+        while start + unknown < chars.len() {
+            let position = start + unknown;
+            let matched = (position + 1..=chars.len()).rev().find(|end| {
+                let part: String = chars[position..*end].iter().collect();
+                !self.lookup(&part).is_empty()
+            });
+
+            match matched {
+                Some(end) => {
+                    if unknown > 0 {
+                        lengths.push(unknown);
+                    }
+                    lengths.push(end - position);
+                    start = end;
+                    unknown = 0;
+                }
+                None => unknown += 1,
+            }
+        }
+        if unknown > 0 {
+            lengths.push(unknown);
+        }
+
+        lengths
+    }
+
+    fn clauses(&self) -> Vec<Clause> {
+        let chars: Vec<char> = self.hiragana().chars().collect();
+        let mut clauses = vec![];
+        let mut start = 0;
+
+        for length in self.segment() {
+            let reading: String = chars[start..start + length].iter().collect();
+            let corresponding_count = self.raw_count(start + length).unwrap_or_default()
+                - self.raw_count(start).unwrap_or_default();
+
+            clauses.push(Clause {
+                surface: self.convert_greedy(&reading),
+                reading,
+                corresponding_count,
+            });
+            start += length;
+        }
+
+        clauses
+    }
+
+    // convert the reading greedily with the longest dictionary match
+    fn convert_greedy(&self, reading: &str) -> String {
+        let chars: Vec<char> = reading.chars().collect();
+        let mut result = String::new();
+        let mut start = 0;
+
+        while start < chars.len() {
+            let matched = (start + 1..=chars.len()).rev().find_map(|end| {
+                let part: String = chars[start..end].iter().collect();
+                self.lookup(&part)
+                    .into_iter()
+                    .next()
+                    .map(|surface| (end, surface))
+            });
+
+            match matched {
+                Some((end, surface)) => {
+                    result.push_str(&surface);
+                    start = end;
+                }
+                None => {
+                    result.push(chars[start]);
+                    start += 1;
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl ConversionBackend for MockBackend {
+    fn append_text(&mut self, input: &str) -> RawComposingText {
+        self.clause_lengths.clear();
+        for c in input.chars() {
+            if c.is_ascii_graphic() {
+                self.feed(c.to_ascii_lowercase());
+            } else {
+                // full width characters are inserted as they are
+                self.flush_pending();
+                self.push_segment(&c.to_string(), 1);
+            }
+        }
+
+        self.composing_text()
+    }
+
+    fn remove_text(&mut self) -> RawComposingText {
+        self.clause_lengths.clear();
+        if self.pending.pop().is_none() && self.cursor > 0 {
+            self.cursor -= 1;
+            let mut segment = self.segments.remove(self.cursor);
+            segment.kana.pop();
+            if !segment.kana.is_empty() {
+                // e.g. "きゃ" -> "き", the rest can't be mapped back to romaji
+                segment.raw_count = segment.kana.chars().count() as i32;
+                self.segments.insert(self.cursor, segment);
+                self.cursor += 1;
+            }
+        }
+
+        self.composing_text()
+    }
+
+    // the cursor moves by segments, so "きゃ" is skipped at once
+    fn move_cursor(&mut self, offset: i32) -> RawComposingText {
+        self.clause_lengths.clear();
+        self.flush_pending();
+        self.cursor = (self.cursor as i32 + offset).clamp(0, self.segments.len() as i32) as usize;
+
+        self.composing_text()
+    }
+
+    fn shrink_text(&mut self, offset: i32) -> RawComposingText {
+        self.clause_lengths.clear();
+        let mut remaining = offset;
+        while remaining > 0 {
+            if self.cursor == 0 && !self.pending.is_empty() {
+                // the pending romaji is at the start
+                self.pending.remove(0);
+                remaining -= 1;
+            } else if !self.segments.is_empty() {
+                let segment = self.segments.remove(0);
+                self.cursor = self.cursor.saturating_sub(1);
+                remaining -= segment.raw_count;
+            } else {
+                break;
+            }
+        }
+
+        self.composing_text()
+    }
+
+    fn clear_text(&mut self) {
+        self.segments.clear();
+        self.pending.clear();
+        self.cursor = 0;
+        self.clause_lengths.clear();
+    }
+
+    fn get_composed_text(&mut self) -> Vec<Suggestion> {
+        let hiragana = self.hiragana();
+        let chars: Vec<char> = hiragana.chars().collect();
+
+        // only the text before the cursor is converted, and the rest is the subtext
+        let cursor = self.cursor_position();
+        let reading: String = chars[..cursor].iter().collect();
+        let rest: String = chars[cursor..].iter().collect();
+
+        if reading.is_empty() {
+            return vec![Suggestion {
+                text: "".to_string(),
+                subtext: hiragana,
+                corresponding_count: 0,
+                ..Default::default()
+            }];
+        }
+
+        let reading_count = self.raw_count(cursor).unwrap_or_default();
+
+        let mut suggestions: Vec<Suggestion> = vec![];
+        let mut push = |reading: &str, text: String, subtext: String, corresponding_count: i32| {
+            if !text.is_empty() && !suggestions.iter().any(|s| s.text == text) {
+                let rank = suggestions.len();
+                suggestions.push(self.suggestion(
+                    reading,
+                    text,
+                    subtext,
+                    corresponding_count,
+                    rank,
+                ));
+            }
+        };
+
+        // whole reading
+        push(
+            &reading,
+            self.convert_greedy(&reading),
+            rest.clone(),
+            reading_count,
+        );
+        for surface in self.lookup(&reading) {
+            push(&reading, surface, rest.clone(), reading_count);
+        }
+        push(&reading, reading.clone(), rest.clone(), reading_count);
+
+        // prefixes of the reading, longest first
+        for end in (1..cursor).rev() {
+            let prefix: String = chars[..end].iter().collect();
+            let suffix: String = chars[end..].iter().collect();
+            let Some(count) = self.raw_count(end) else {
+                continue;
+            };
+
+            for surface in self.lookup(&prefix) {
+                push(&prefix, surface, suffix.clone(), count);
+            }
+        }
+
+        suggestions
+    }
+
+    fn get_predictions(&mut self) -> Vec<Suggestion> {
+        let chars: Vec<char> = self.hiragana().chars().collect();
+        let cursor = self.cursor_position();
+        let reading: String = chars[..cursor].iter().collect();
+        let rest: String = chars[cursor..].iter().collect();
+
+        if reading.is_empty() {
+            return vec![];
+        }
+
+        let reading_count = self.raw_count(cursor).unwrap_or_default();
+
+        let mut predictions: Vec<Suggestion> = vec![];
+        for completion in self.completions(&reading) {
+            for text in self.lookup(&completion) {
+                if !predictions.iter().any(|p| p.text == text) {
+                    let rank = predictions.len();
+                    predictions.push(self.suggestion(
+                        &completion,
+                        text,
+                        rest.clone(),
+                        reading_count,
+                        rank,
+                    ));
+                }
+            }
+        }
+
+        predictions
+    }
+
+    // the mock doesn't use the left side context
+    fn set_context(&mut self, _context: &str) {}
+
+    fn get_clauses(&mut self) -> Vec<Clause> {
+        self.clauses()
+    }
+
+    fn resize_clause(&mut self, index: usize, offset: i32) -> Vec<Clause> {
+        let lengths = self.segment();
+        if let Some(length) = lengths.get(index) {
+            // the clause can take the characters up to the end of the text
+            let available: usize = lengths[index..].iter().sum();
+            let length = (*length as i32 + offset).clamp(1, available as i32) as usize;
+
+            self.clause_lengths = lengths[..index].to_vec();
+            self.clause_lengths.push(length);
+        }
+
+        self.clauses()
+    }
+
+    fn get_clause_candidates(&mut self, index: usize) -> Vec<Suggestion> {
+        let Some(clause) = self.clauses().into_iter().nth(index) else {
+            return vec![];
+        };
+
+        let mut suggestions: Vec<Suggestion> = vec![];
+        let surfaces = self
+            .lookup(&clause.reading)
+            .into_iter()
+            .chain([self.convert_greedy(&clause.reading), clause.reading.clone()]);
+        for text in surfaces {
+            if !suggestions.iter().any(|s| s.text == text) {
+                let rank = suggestions.len();
+                suggestions.push(self.suggestion(
+                    &clause.reading,
+                    text,
+                    "".to_string(),
+                    clause.corresponding_count,
+                    rank,
+                ));
+            }
+        }
+
+        suggestions
+    }
+
+    // split the surface greedily with the longest known word, and read the kana as they are
+    fn reverse_lookup(&mut self, surface: &str) -> Option<String> {
+        let chars: Vec<char> = surface.chars().collect();
+        let mut reading = String::new();
+        let mut start = 0;
+
+        while start < chars.len() {
+            let matched = (start + 1..=chars.len()).rev().find_map(|end| {
+                let part: String = chars[start..end].iter().collect();
+                self.reading_of(&part).map(|reading| (end, reading))
+            });
+
+            match matched {
+                Some((end, part)) => {
+                    reading.push_str(&part);
+                    start = end;
+                }
+                None => {
+                    reading.push(kana_reading(chars[start])?);
+                    start += 1;
+                }
+            }
+        }
+
+        Some(reading)
+    }
+
+    fn commit_candidate(&mut self, reading: &str, surface: &str, _corresponding_count: i32) {
+        if reading.is_empty() || surface.is_empty() {
+            return;
+        }
+
+        if let Ok(mut learning) = self.learning.write() {
+            if !learning.config.enable || learning.config.read_only {
+                return;
+            }
+
+            let surfaces = learning.history.entry(reading.to_string()).or_default();
+            surfaces.retain(|s| s != surface);
+            surfaces.insert(0, surface.to_string());
+        }
+    }
+}
+
+// pictographs and dingbats, they depend on the font of the application
+fn is_emoji(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0xFE0F | 0x200D))
+}
@@ -0,0 +1,345 @@
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    sync::{Arc, RwLock},
+};
+
+use shared::proto::{Clause, Suggestion};
+use shared::user_dict::UserDictEntry;
+use shared::AppConfig;
+
+use super::{kana_reading, BackendFactory, ConversionBackend, RawComposingText};
+
+const USE_ZENZAI: bool = true;
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+struct FFICandidate {
+    text: *mut c_char,
+    subtext: *mut c_char,
+    hiragana: *mut c_char,
+    corresponding_count: c_int,
+    source: c_int,
+    annotation: *mut c_char,
+    score: f32,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+struct FFIClause {
+    reading: *mut c_char,
+    surface: *mut c_char,
+    corresponding_count: c_int,
+}
+
+unsafe extern "C" {
+    fn Initialize(path: *const c_char, use_zenzai: bool);
+    fn CreateSession(session: c_int);
+    fn CloseSession(session: c_int);
+    fn SetContext(session: c_int, context: *const c_char);
+    fn AppendText(session: c_int, input: *const c_char, cursorPtr: *mut c_int) -> *mut c_char;
+    fn RemoveText(session: c_int, cursorPtr: *mut c_int) -> *mut c_char;
+    fn MoveCursor(session: c_int, offset: c_int, cursorPtr: *mut c_int) -> *mut c_char;
+    fn ShrinkText(session: c_int, offset: c_int, cursorPtr: *mut c_int) -> *mut c_char;
+    fn ClearText(session: c_int);
+    fn GetComposedText(session: c_int, lengthPtr: *mut c_int) -> *mut *mut FFICandidate;
+    fn GetPredictions(session: c_int, lengthPtr: *mut c_int) -> *mut *mut FFICandidate;
+    fn GetClauses(session: c_int, lengthPtr: *mut c_int) -> *mut *mut FFIClause;
+    fn ResizeClause(
+        session: c_int,
+        index: c_int,
+        offset: c_int,
+        lengthPtr: *mut c_int,
+    ) -> *mut *mut FFIClause;
+    fn GetClauseCandidates(
+        session: c_int,
+        index: c_int,
+        lengthPtr: *mut c_int,
+    ) -> *mut *mut FFICandidate;
+    fn CommitCandidate(
+        session: c_int,
+        reading: *const c_char,
+        surface: *const c_char,
+        correspondingCount: c_int,
+    );
+    fn ClearLearningHistory();
+    fn LoadConfig(json: *const c_char);
+    fn SetUserDictionary(json: *const c_char);
+}
+
+// the candidates with the same text are merged into the first one
+unsafe fn to_suggestions(result: *mut *mut FFICandidate, length: c_int) -> Vec<Suggestion> {
+    let mut suggestions: Vec<Suggestion> = Vec::with_capacity(length as usize);
+
+    for index in 0..length as usize {
+        let candidate = (**result.add(index)).clone();
+        let suggestion = Suggestion {
+            text: CStr::from_ptr(candidate.text)
+                .to_string_lossy()
+                .into_owned(),
+            subtext: CStr::from_ptr(candidate.subtext)
+                .to_string_lossy()
+                .into_owned(),
+            corresponding_count: candidate.corresponding_count,
+            reading: CStr::from_ptr(candidate.hiragana)
+                .to_string_lossy()
+                .into_owned(),
+            source: candidate.source,
+            annotation: CStr::from_ptr(candidate.annotation)
+                .to_string_lossy()
+                .into_owned(),
+            score: candidate.score,
+        };
+
+        if suggestions.iter().any(|s| s.text == suggestion.text) {
+            continue;
+        }
+        suggestions.push(suggestion);
+    }
+
+    suggestions
+}
+
+unsafe fn to_clauses(result: *mut *mut FFIClause, length: c_int) -> Vec<Clause> {
+    let mut clauses = Vec::with_capacity(length as usize);
+
+    for index in 0..length as usize {
+        let clause = (**result.add(index)).clone();
+        clauses.push(Clause {
+            reading: CStr::from_ptr(clause.reading)
+                .to_string_lossy()
+                .into_owned(),
+            surface: CStr::from_ptr(clause.surface)
+                .to_string_lossy()
+                .into_owned(),
+            corresponding_count: clause.corresponding_count,
+        });
+    }
+
+    clauses
+}
+
+#[derive(Debug)]
+pub struct SwiftBackendFactory {
+    // the dictionary of the converter can't be looked up by the surface,
+    // so the registered words are kept here for the reverse lookup
+    user_dictionary: Arc<RwLock<Vec<UserDictEntry>>>,
+}
+
+impl SwiftBackendFactory {
+    pub fn new(path: &str) -> Self {
+        unsafe {
+            let path = CString::new(path).expect("CString::new failed");
+            Initialize(path.as_ptr(), USE_ZENZAI);
+        }
+
+        Self {
+            user_dictionary: Arc::new(RwLock::new(vec![])),
+        }
+    }
+}
+
+impl BackendFactory for SwiftBackendFactory {
+    fn create(&self, session_id: u32) -> Box<dyn ConversionBackend> {
+        Box::new(SwiftBackend::new(
+            session_id as c_int,
+            self.user_dictionary.clone(),
+        ))
+    }
+
+    // the swift side doesn't read settings.json, the config is passed as json
+    fn load_config(&self, config: &AppConfig) {
+        let json = serde_json::to_string(config).expect("failed to serialize config");
+        let json = CString::new(json).expect("CString::new failed");
+
+        unsafe { LoadConfig(json.as_ptr()) };
+    }
+
+    fn set_user_dictionary(&self, entries: &[UserDictEntry]) {
+        let json = serde_json::to_string(entries).expect("failed to serialize user dictionary");
+        let json = CString::new(json).expect("CString::new failed");
+
+        unsafe { SetUserDictionary(json.as_ptr()) };
+
+        if let Ok(mut user_dictionary) = self.user_dictionary.write() {
+            *user_dictionary = entries.to_vec();
+        }
+    }
+
+    fn clear_learning_history(&self) {
+        unsafe { ClearLearningHistory() };
+    }
+}
+
+// backend which calls AzooKeyKanaKanjiConverter through azookey-server.dll
+// the converter is shared, but the composing text is kept per session on the swift side
+#[derive(Debug)]
+pub struct SwiftBackend {
+    session: c_int,
+    user_dictionary: Arc<RwLock<Vec<UserDictEntry>>>,
+}
+
+impl SwiftBackend {
+    fn new(session: c_int, user_dictionary: Arc<RwLock<Vec<UserDictEntry>>>) -> Self {
+        unsafe { CreateSession(session) };
+
+        Self {
+            session,
+            user_dictionary,
+        }
+    }
+}
+
+impl Drop for SwiftBackend {
+    fn drop(&mut self) {
+        unsafe { CloseSession(self.session) };
+    }
+}
+
+impl ConversionBackend for SwiftBackend {
+    fn append_text(&mut self, input: &str) -> RawComposingText {
+        unsafe {
+            let input = CString::new(input).expect("CString::new failed");
+            let mut cursor: c_int = 0;
+
+            let result = AppendText(self.session, input.as_ptr(), &mut cursor);
+
+            let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
+
+            RawComposingText {
+                text: text.to_string(),
+                cursor,
+            }
+        }
+    }
+
+    fn remove_text(&mut self) -> RawComposingText {
+        unsafe {
+            let mut cursor: c_int = 0;
+
+            let result = RemoveText(self.session, &mut cursor);
+
+            let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
+
+            RawComposingText {
+                text: text.to_string(),
+                cursor,
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, offset: i32) -> RawComposingText {
+        unsafe {
+            let mut cursor: c_int = 0;
+
+            let result = MoveCursor(self.session, offset, &mut cursor);
+
+            let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
+
+            RawComposingText {
+                text: text.to_string(),
+                cursor,
+            }
+        }
+    }
+
+    fn shrink_text(&mut self, offset: i32) -> RawComposingText {
+        unsafe {
+            let mut cursor: c_int = 0;
+
+            let result = ShrinkText(self.session, offset, &mut cursor);
+
+            let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
+
+            RawComposingText {
+                text: text.to_string(),
+                cursor,
+            }
+        }
+    }
+
+    fn clear_text(&mut self) {
+        unsafe {
+            ClearText(self.session);
+        }
+    }
+
+    fn get_composed_text(&mut self) -> Vec<Suggestion> {
+        unsafe {
+            let mut length: c_int = 0;
+            let result = GetComposedText(self.session, &mut length);
+
+            to_suggestions(result, length)
+        }
+    }
+
+    // GetComposedText splits the predictions from the candidates, so this should be called after it
+    fn get_predictions(&mut self) -> Vec<Suggestion> {
+        unsafe {
+            let mut length: c_int = 0;
+            let result = GetPredictions(self.session, &mut length);
+
+            to_suggestions(result, length)
+        }
+    }
+
+    fn set_context(&mut self, context: &str) {
+        let context = CString::new(context).expect("CString::new failed");
+
+        unsafe { SetContext(self.session, context.as_ptr()) };
+    }
+
+    fn get_clauses(&mut self) -> Vec<Clause> {
+        unsafe {
+            let mut length: c_int = 0;
+            let result = GetClauses(self.session, &mut length);
+
+            to_clauses(result, length)
+        }
+    }
+
+    fn resize_clause(&mut self, index: usize, offset: i32) -> Vec<Clause> {
+        unsafe {
+            let mut length: c_int = 0;
+            let result = ResizeClause(self.session, index as c_int, offset, &mut length);
+
+            to_clauses(result, length)
+        }
+    }
+
+    fn get_clause_candidates(&mut self, index: usize) -> Vec<Suggestion> {
+        unsafe {
+            let mut length: c_int = 0;
+            let result = GetClauseCandidates(self.session, index as c_int, &mut length);
+
+            to_suggestions(result, length)
+        }
+    }
+
+    // only the registered words and the kana are found, the client keeps the readings of its own commits
+    fn reverse_lookup(&mut self, surface: &str) -> Option<String> {
+        if let Some(entry) = self.user_dictionary.read().ok().and_then(|entries| {
+            entries
+                .iter()
+                .find(|e| e.surface == surface)
+                .map(|e| e.reading.clone())
+        }) {
+            return Some(entry);
+        }
+
+        surface.chars().map(kana_reading).collect()
+    }
+
+    fn commit_candidate(&mut self, reading: &str, surface: &str, corresponding_count: i32) {
+        let reading = CString::new(reading).expect("CString::new failed");
+        let surface = CString::new(surface).expect("CString::new failed");
+
+        unsafe {
+            CommitCandidate(
+                self.session,
+                reading.as_ptr(),
+                surface.as_ptr(),
+                corresponding_count,
+            )
+        };
+    }
+}
@@ -1,251 +1,49 @@
-use azookey_server::TonicNamedPipeServer;
-use tonic::{transport::Server, Request, Response, Status};
+use azookey_server::backend::{mock::MockBackendFactory, BackendFactory};
+use azookey_server::service::{load_user_dictionary, MyAzookeyService};
+use azookey_server::session::{SessionManager, SESSION_IDLE_TIMEOUT};
+use tonic::transport::Server;
 use tonic_reflection::server::Builder as ReflectionBuilder;
 
-use shared::proto::azookey_service_server::{AzookeyService, AzookeyServiceServer};
-use shared::proto::{
-    AppendTextRequest, AppendTextResponse, ClearTextRequest, ClearTextResponse, ComposingText,
-    MoveCursorRequest, MoveCursorResponse, RemoveTextRequest, RemoveTextResponse,
-    ShrinkTextRequest, ShrinkTextResponse, Suggestion,
-};
-
-use std::ffi::{c_char, c_int, CStr, CString};
-
-const USE_ZENZAI: bool = true;
-
-struct RawComposingText {
-    text: String,
-    cursor: i8,
-}
-
-#[derive(Debug, Clone)]
-#[repr(C)]
-struct FFICandidate {
-    text: *mut c_char,
-    subtext: *mut c_char,
-    hiragana: *mut c_char,
-    corresponding_count: c_int,
-}
-
-unsafe extern "C" {
-    fn Initialize(path: *const c_char, use_zenzai: bool);
-    fn SetContext(context: *const c_char);
-    fn AppendText(input: *const c_char, cursorPtr: *mut c_int) -> *mut c_char;
-    fn RemoveText(cursorPtr: *mut c_int) -> *mut c_char;
-    fn MoveCursor(offset: c_int, cursorPtr: *mut c_int) -> *mut c_char;
-    fn ShrinkText(offset: c_int) -> *mut c_char;
-    fn ClearText();
-    fn GetComposedText(lengthPtr: *mut c_int) -> *mut *mut FFICandidate;
-    fn LoadConfig();
-}
-
-fn initialize(path: &str) {
-    unsafe {
-        let path = CString::new(path).expect("CString::new failed");
-        Initialize(path.as_ptr(), USE_ZENZAI);
-    }
-}
-
-fn add_text(input: &str) -> RawComposingText {
-    unsafe {
-        let input = CString::new(input).expect("CString::new failed");
-        let mut cursor: c_int = 0;
-
-        let result = AppendText(input.as_ptr(), &mut cursor);
-
-        let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
-
-        RawComposingText {
-            text: text.to_string(),
-            cursor: cursor as i8,
-        }
-    }
-}
-
-fn move_cursor(offset: i8) -> RawComposingText {
-    unsafe {
-        let offset = c_int::from(offset);
-        println!("Offset: {}", offset);
-        let mut cursor: c_int = 0;
-
-        let result = MoveCursor(offset, &mut cursor);
-
-        let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
-
-        RawComposingText {
-            text: text.to_string(),
-            cursor: cursor as i8,
-        }
-    }
-}
-
-fn remove_text() -> RawComposingText {
-    unsafe {
-        let mut cursor: c_int = 0;
-
-        let result = RemoveText(&mut cursor);
-
-        let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
-
-        RawComposingText {
-            text: text.to_string(),
-            cursor: cursor as i8,
-        }
-    }
-}
-
-fn clear_text() {
-    unsafe {
-        ClearText();
-    }
-}
-
-fn get_composed_text() -> Vec<Suggestion> {
-    unsafe {
-        let mut length: c_int = 0;
-        let result = GetComposedText(&mut length);
-        let mut suggestions = Vec::with_capacity(length as usize);
+use shared::config_watcher::{self, ConfigWatcher};
+use shared::proto::azookey_service_server::AzookeyServiceServer;
+use shared::AppConfig;
 
-        for index in 0..length as usize {
-            let candidate = (**result.add(index)).clone();
-            let text = CStr::from_ptr(candidate.text)
-                .to_string_lossy()
-                .into_owned();
-            let subtext = CStr::from_ptr(candidate.subtext)
-                .to_string_lossy()
-                .into_owned();
-            let corresponding_count = candidate.corresponding_count;
+#[cfg(all(windows, feature = "swift"))]
+use azookey_server::backend::swift::SwiftBackendFactory;
+#[cfg(windows)]
+use azookey_server::TonicNamedPipeServer;
 
-            let suggestion = Suggestion {
-                text,
-                subtext,
-                corresponding_count,
-            };
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-            // check if suggestions have the same text
-            if suggestions
-                .iter()
-                .any(|s: &Suggestion| s.text == suggestion.text)
-            {
-                continue;
-            }
-            suggestions.push(suggestion);
+// interval to check the idle sessions
+const SESSION_EXPIRE_INTERVAL: Duration = Duration::from_secs(60);
+
+// the mock backend is used if the swift library is not available, or AZOOKEY_BACKEND=mock is set
+#[allow(unused_variables)]
+fn create_backend_factory(path: &str) -> Box<dyn BackendFactory> {
+    #[cfg(all(windows, feature = "swift"))]
+    if std::env::var("AZOOKEY_BACKEND").as_deref() != Ok("mock") {
+        if swift_library_available() {
+            println!("Using swift backend");
+            return Box::new(SwiftBackendFactory::new(path));
         }
-
-        suggestions
+        println!("Failed to load the swift runtime");
     }
-}
-
-fn shrink_text(offset: i8) -> RawComposingText {
-    unsafe {
-        let offset = c_int::from(offset);
-        let result = ShrinkText(offset);
 
-        let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
-
-        RawComposingText {
-            text: text.to_string(),
-            cursor: 0,
-        }
-    }
+    println!("Using mock backend");
+    Box::new(MockBackendFactory::new())
 }
 
-#[derive(Debug, Default)]
-pub struct MyAzookeyService;
-
-#[tonic::async_trait]
-impl AzookeyService for MyAzookeyService {
-    async fn append_text(
-        &self,
-        request: Request<AppendTextRequest>,
-    ) -> Result<Response<AppendTextResponse>, Status> {
-        let input = request.into_inner().text_to_append;
-        let composing_text = add_text(&input);
-
-        Ok(Response::new(AppendTextResponse {
-            composing_text: Some(ComposingText {
-                hiragana: composing_text.text,
-                suggestions: get_composed_text().to_vec(),
-            }),
-        }))
-    }
-
-    async fn remove_text(
-        &self,
-        _: Request<RemoveTextRequest>,
-    ) -> Result<Response<RemoveTextResponse>, Status> {
-        let composing_text = remove_text();
-
-        Ok(Response::new(RemoveTextResponse {
-            composing_text: Some(ComposingText {
-                hiragana: composing_text.text,
-                suggestions: get_composed_text().to_vec(),
-            }),
-        }))
-    }
+// azookey-server.dll is delay loaded, so the server starts even if it or the swift runtime is missing
+// it is loaded here first, and the first call to the library resolves to the loaded one
+#[cfg(all(windows, feature = "swift"))]
+fn swift_library_available() -> bool {
+    use windows::{core::w, Win32::System::LibraryLoader::LoadLibraryW};
 
-    async fn move_cursor(
-        &self,
-        request: Request<MoveCursorRequest>,
-    ) -> Result<Response<MoveCursorResponse>, Status> {
-        let offset = request.into_inner().offset as i8;
-        let composing_text = move_cursor(offset);
-
-        Ok(Response::new(MoveCursorResponse {
-            composing_text: Some(ComposingText {
-                hiragana: composing_text.text,
-                suggestions: get_composed_text().to_vec(),
-            }),
-        }))
-    }
-
-    async fn clear_text(
-        &self,
-        _: Request<ClearTextRequest>,
-    ) -> Result<Response<ClearTextResponse>, Status> {
-        clear_text();
-        Ok(Response::new(ClearTextResponse {}))
-    }
-
-    async fn shrink_text(
-        &self,
-        request: Request<ShrinkTextRequest>,
-    ) -> Result<Response<ShrinkTextResponse>, Status> {
-        let offset = request.into_inner().offset as i8;
-        let composing_text = shrink_text(offset);
-
-        Ok(Response::new(ShrinkTextResponse {
-            composing_text: Some(ComposingText {
-                hiragana: composing_text.text,
-                suggestions: get_composed_text().to_vec(),
-            }),
-        }))
-    }
-
-    async fn set_context(
-        &self,
-        request: Request<shared::proto::SetContextRequest>,
-    ) -> Result<Response<shared::proto::SetContextResponse>, Status> {
-        let context = request.into_inner().context;
-        let trimmed_context = context
-            .split('\r')
-            .filter(|s| !s.is_empty())
-            .last()
-            .unwrap_or_default();
-
-        let context = CString::new(trimmed_context).expect("CString::new failed");
-
-        unsafe { SetContext(context.as_ptr()) };
-        Ok(Response::new(shared::proto::SetContextResponse {}))
-    }
-
-    async fn update_config(
-        &self,
-        _: Request<shared::proto::UpdateConfigRequest>,
-    ) -> Result<Response<shared::proto::UpdateConfigResponse>, Status> {
-        unsafe { LoadConfig() };
-        Ok(Response::new(shared::proto::UpdateConfigResponse {}))
-    }
+    unsafe { LoadLibraryW(w!("azookey-server.dll")) }.is_ok()
 }
 
 #[tokio::main]
@@ -254,22 +52,59 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // get executable directory
     let current_exe = std::env::current_exe()?;
     let parent_dir = current_exe.parent().unwrap();
-    initialize(parent_dir.to_str().unwrap());
+    let factory = create_backend_factory(parent_dir.to_str().unwrap());
+    let mut sessions = SessionManager::new(factory);
+    sessions.load_config(&AppConfig::read().unwrap_or_else(|e| {
+        println!("Failed to read the settings: {}", e);
+        AppConfig::default()
+    }));
+    load_user_dictionary(&sessions);
+    let sessions = Arc::new(Mutex::new(sessions));
+
+    // apply the settings changed by the settings app or by hand
+    let watch_sessions = sessions.clone();
+    let _config_watcher = ConfigWatcher::spawn(config_watcher::DEFAULT_INTERVAL, move |config| {
+        println!("Settings changed");
+        if let Ok(mut sessions) = watch_sessions.lock() {
+            sessions.load_config(&config);
+        }
+    });
+
+    // close the sessions of the clients which are gone without calling CloseSession
+    let expire_sessions = sessions.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SESSION_EXPIRE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Ok(mut sessions) = expire_sessions.lock() {
+                let expired = sessions.expire_idle_sessions(SESSION_IDLE_TIMEOUT);
+                if expired > 0 {
+                    println!("Expired {} idle sessions", expired);
+                }
+            }
+        }
+    });
 
-    let service = MyAzookeyService::default();
+    let service = MyAzookeyService::new(sessions);
 
     println!("AzookeyServer listening");
 
-    Server::builder()
+    let server = Server::builder()
         .add_service(AzookeyServiceServer::new(service))
         .add_service(
             ReflectionBuilder::configure()
                 .register_encoded_file_descriptor_set(shared::proto::FILE_DESCRIPTOR_SET)
                 .build_v1()
                 .unwrap(),
-        )
+        );
+
+    #[cfg(windows)]
+    server
         .serve_with_incoming(TonicNamedPipeServer::new("azookey_server"))
         .await?;
+    // the named pipe is only on windows, the mock backend can be tried over tcp elsewhere
+    #[cfg(not(windows))]
+    server.serve("[::1]:50051".parse()?).await?;
 
     Ok(())
 }
@@ -0,0 +1,357 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tonic::{Request, Response, Status};
+
+use shared::proto::azookey_service_server::AzookeyService;
+use shared::proto::{
+    AddUserDictEntriesRequest, AddUserDictEntriesResponse, ListUserDictEntriesRequest,
+    ListUserDictEntriesResponse, RemoveUserDictEntriesRequest, RemoveUserDictEntriesResponse,
+};
+use shared::proto::{
+    AppendTextRequest, AppendTextResponse, Clause, ClearTextRequest, ClearTextResponse,
+    CloseSessionRequest, CloseSessionResponse, ComposingText, CreateSessionRequest,
+    CreateSessionResponse, GetClauseCandidatesRequest, GetClauseCandidatesResponse,
+    GetClausesRequest, GetClausesResponse, MoveCursorRequest, MoveCursorResponse,
+    RemoveTextRequest, RemoveTextResponse, ReplaceTextRequest, ReplaceTextResponse,
+    ResizeClauseRequest, ResizeClauseResponse, ShrinkTextRequest, ShrinkTextResponse,
+};
+use shared::proto::{
+    ClearLearningHistoryRequest, ClearLearningHistoryResponse, CommitCandidateRequest,
+    CommitCandidateResponse, ReverseLookupRequest, ReverseLookupResponse, ShutdownRequest,
+    ShutdownResponse,
+};
+use shared::user_dict::UserDictionary;
+use shared::AppConfig;
+
+use crate::session::SessionManager;
+
+// delay before exiting on Shutdown, so that the response reaches the client
+const SHUTDOWN_DELAY: Duration = Duration::from_millis(100);
+
+pub struct MyAzookeyService {
+    sessions: Arc<Mutex<SessionManager>>,
+}
+
+impl MyAzookeyService {
+    pub fn new(sessions: Arc<Mutex<SessionManager>>) -> Self {
+        Self { sessions }
+    }
+
+    // a panic in a request shouldn't make the server unusable, so recover from the poisoned lock
+    fn sessions(&self) -> MutexGuard<'_, SessionManager> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[tonic::async_trait]
+impl AzookeyService for MyAzookeyService {
+    async fn create_session(
+        &self,
+        _: Request<CreateSessionRequest>,
+    ) -> Result<Response<CreateSessionResponse>, Status> {
+        let session_id = self.sessions().create_session();
+        println!("Session created: {}", session_id);
+
+        Ok(Response::new(CreateSessionResponse { session_id }))
+    }
+
+    async fn close_session(
+        &self,
+        request: Request<CloseSessionRequest>,
+    ) -> Result<Response<CloseSessionResponse>, Status> {
+        let session_id = request.into_inner().session_id;
+        self.sessions().close_session(session_id);
+        println!("Session closed: {}", session_id);
+
+        Ok(Response::new(CloseSessionResponse {}))
+    }
+
+    async fn append_text(
+        &self,
+        request: Request<AppendTextRequest>,
+    ) -> Result<Response<AppendTextResponse>, Status> {
+        let request = request.into_inner();
+        let mut sessions = self.sessions();
+        let composing_text = sessions
+            .backend(request.session_id)
+            .append_text(&request.text_to_append);
+
+        Ok(Response::new(AppendTextResponse {
+            composing_text: Some(sessions.composing_text(request.session_id, composing_text)),
+        }))
+    }
+
+    async fn remove_text(
+        &self,
+        request: Request<RemoveTextRequest>,
+    ) -> Result<Response<RemoveTextResponse>, Status> {
+        let request = request.into_inner();
+        let mut sessions = self.sessions();
+        let composing_text = sessions.backend(request.session_id).remove_text();
+
+        Ok(Response::new(RemoveTextResponse {
+            composing_text: Some(sessions.composing_text(request.session_id, composing_text)),
+        }))
+    }
+
+    async fn replace_text(
+        &self,
+        request: Request<ReplaceTextRequest>,
+    ) -> Result<Response<ReplaceTextResponse>, Status> {
+        let request = request.into_inner();
+        let mut sessions = self.sessions();
+        let composing_text = sessions
+            .backend(request.session_id)
+            .replace_text(request.remove_count as usize, &request.text_to_append);
+
+        Ok(Response::new(ReplaceTextResponse {
+            composing_text: Some(sessions.composing_text(request.session_id, composing_text)),
+        }))
+    }
+
+    async fn move_cursor(
+        &self,
+        request: Request<MoveCursorRequest>,
+    ) -> Result<Response<MoveCursorResponse>, Status> {
+        let request = request.into_inner();
+        let mut sessions = self.sessions();
+        let composing_text = sessions
+            .backend(request.session_id)
+            .move_cursor(request.offset);
+
+        Ok(Response::new(MoveCursorResponse {
+            composing_text: Some(sessions.composing_text(request.session_id, composing_text)),
+        }))
+    }
+
+    async fn clear_text(
+        &self,
+        request: Request<ClearTextRequest>,
+    ) -> Result<Response<ClearTextResponse>, Status> {
+        let session_id = request.into_inner().session_id;
+        self.sessions().backend(session_id).clear_text();
+        Ok(Response::new(ClearTextResponse {}))
+    }
+
+    async fn shrink_text(
+        &self,
+        request: Request<ShrinkTextRequest>,
+    ) -> Result<Response<ShrinkTextResponse>, Status> {
+        let request = request.into_inner();
+        let mut sessions = self.sessions();
+        let composing_text = sessions
+            .backend(request.session_id)
+            .shrink_text(request.offset);
+
+        Ok(Response::new(ShrinkTextResponse {
+            composing_text: Some(sessions.composing_text(request.session_id, composing_text)),
+        }))
+    }
+
+    async fn set_context(
+        &self,
+        request: Request<shared::proto::SetContextRequest>,
+    ) -> Result<Response<shared::proto::SetContextResponse>, Status> {
+        let request = request.into_inner();
+        let trimmed_context = request
+            .context
+            .split('\r')
+            .rfind(|s| !s.is_empty())
+            .unwrap_or_default();
+
+        self.sessions()
+            .backend(request.session_id)
+            .set_context(trimmed_context);
+        Ok(Response::new(shared::proto::SetContextResponse {}))
+    }
+
+    async fn get_clauses(
+        &self,
+        request: Request<GetClausesRequest>,
+    ) -> Result<Response<GetClausesResponse>, Status> {
+        let session_id = request.into_inner().session_id;
+        let clauses = self.sessions().backend(session_id).get_clauses();
+
+        Ok(Response::new(GetClausesResponse {
+            composing_text: Some(clauses_to_composing_text(clauses)),
+        }))
+    }
+
+    async fn resize_clause(
+        &self,
+        request: Request<ResizeClauseRequest>,
+    ) -> Result<Response<ResizeClauseResponse>, Status> {
+        let request = request.into_inner();
+        let index = usize::try_from(request.index)
+            .map_err(|_| Status::invalid_argument("index must not be negative"))?;
+        let clauses = self
+            .sessions()
+            .backend(request.session_id)
+            .resize_clause(index, request.offset);
+
+        Ok(Response::new(ResizeClauseResponse {
+            composing_text: Some(clauses_to_composing_text(clauses)),
+        }))
+    }
+
+    async fn get_clause_candidates(
+        &self,
+        request: Request<GetClauseCandidatesRequest>,
+    ) -> Result<Response<GetClauseCandidatesResponse>, Status> {
+        let request = request.into_inner();
+        let index = usize::try_from(request.index)
+            .map_err(|_| Status::invalid_argument("index must not be negative"))?;
+        let suggestions = self.sessions().clause_candidates(request.session_id, index);
+
+        Ok(Response::new(GetClauseCandidatesResponse { suggestions }))
+    }
+
+    async fn commit_candidate(
+        &self,
+        request: Request<CommitCandidateRequest>,
+    ) -> Result<Response<CommitCandidateResponse>, Status> {
+        let request = request.into_inner();
+        self.sessions()
+            .backend(request.session_id)
+            .commit_candidate(
+                &request.reading,
+                &request.surface,
+                request.corresponding_count,
+            );
+
+        Ok(Response::new(CommitCandidateResponse {}))
+    }
+
+    async fn reverse_lookup(
+        &self,
+        request: Request<ReverseLookupRequest>,
+    ) -> Result<Response<ReverseLookupResponse>, Status> {
+        let request = request.into_inner();
+        let reading = self
+            .sessions()
+            .backend(request.session_id)
+            .reverse_lookup(&request.surface)
+            .unwrap_or_default();
+
+        Ok(Response::new(ReverseLookupResponse { reading }))
+    }
+
+    async fn clear_learning_history(
+        &self,
+        _: Request<ClearLearningHistoryRequest>,
+    ) -> Result<Response<ClearLearningHistoryResponse>, Status> {
+        self.sessions().clear_learning_history();
+        println!("Learning history cleared");
+
+        Ok(Response::new(ClearLearningHistoryResponse {}))
+    }
+
+    async fn update_config(
+        &self,
+        _: Request<shared::proto::UpdateConfigRequest>,
+    ) -> Result<Response<shared::proto::UpdateConfigResponse>, Status> {
+        let config = AppConfig::read().map_err(|e| Status::internal(e.to_string()))?;
+        let mut sessions = self.sessions();
+        sessions.load_config(&config);
+        // the dictionary may be edited by the settings app directly
+        load_user_dictionary(&sessions);
+        Ok(Response::new(shared::proto::UpdateConfigResponse {}))
+    }
+
+    async fn add_user_dict_entries(
+        &self,
+        request: Request<AddUserDictEntriesRequest>,
+    ) -> Result<Response<AddUserDictEntriesResponse>, Status> {
+        // hold the lock while the file is updated, so that the concurrent requests don't lose entries
+        let sessions = self.sessions();
+        let mut dictionary = UserDictionary::read().map_err(|e| Status::internal(e.to_string()))?;
+
+        let mut added = 0;
+        for entry in request.into_inner().entries {
+            if dictionary.add(entry.into()) {
+                added += 1;
+            }
+        }
+
+        dictionary
+            .write()
+            .map_err(|e| Status::internal(e.to_string()))?;
+        sessions.set_user_dictionary(&dictionary.entries);
+
+        Ok(Response::new(AddUserDictEntriesResponse { added }))
+    }
+
+    async fn remove_user_dict_entries(
+        &self,
+        request: Request<RemoveUserDictEntriesRequest>,
+    ) -> Result<Response<RemoveUserDictEntriesResponse>, Status> {
+        let sessions = self.sessions();
+        let mut dictionary = UserDictionary::read().map_err(|e| Status::internal(e.to_string()))?;
+
+        let mut removed = 0;
+        for entry in request.into_inner().entries {
+            if dictionary.remove(&entry.reading, &entry.surface) {
+                removed += 1;
+            }
+        }
+
+        dictionary
+            .write()
+            .map_err(|e| Status::internal(e.to_string()))?;
+        sessions.set_user_dictionary(&dictionary.entries);
+
+        Ok(Response::new(RemoveUserDictEntriesResponse { removed }))
+    }
+
+    async fn list_user_dict_entries(
+        &self,
+        _: Request<ListUserDictEntriesRequest>,
+    ) -> Result<Response<ListUserDictEntriesResponse>, Status> {
+        let dictionary = UserDictionary::read().map_err(|e| Status::internal(e.to_string()))?;
+        let entries = dictionary.entries.into_iter().map(Into::into).collect();
+
+        Ok(Response::new(ListUserDictEntriesResponse { entries }))
+    }
+
+    async fn shutdown(
+        &self,
+        _: Request<ShutdownRequest>,
+    ) -> Result<Response<ShutdownResponse>, Status> {
+        println!("Shutdown requested");
+        tokio::spawn(async {
+            tokio::time::sleep(SHUTDOWN_DELAY).await;
+            std::process::exit(shared::SERVER_RESTART_EXIT_CODE);
+        });
+
+        Ok(Response::new(ShutdownResponse {}))
+    }
+}
+
+// a broken dictionary file shouldn't stop the conversion, so it is treated as empty
+pub fn load_user_dictionary(sessions: &SessionManager) {
+    match UserDictionary::read() {
+        Ok(dictionary) => sessions.set_user_dictionary(&dictionary.entries),
+        Err(e) => {
+            println!("Failed to read the user dictionary: {}", e);
+            sessions.set_user_dictionary(&[]);
+        }
+    }
+}
+
+// the clause conversion covers the whole text, so the cursor is placed at the end
+fn clauses_to_composing_text(clauses: Vec<Clause>) -> ComposingText {
+    let hiragana: String = clauses.iter().map(|c| c.reading.as_str()).collect();
+
+    ComposingText {
+        cursor: hiragana.chars().count() as i32,
+        hiragana,
+        suggestions: vec![],
+        clauses,
+        predictions: vec![],
+    }
+}
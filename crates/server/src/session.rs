@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use shared::proto::{ComposingText, Suggestion};
+use shared::user_dict::UserDictEntry;
+use shared::{AppConfig, PredictionConfig};
+
+use crate::backend::{BackendFactory, ConversionBackend, RawComposingText};
+use crate::symbols::SymbolTable;
+
+// sessions which are not used for this duration will be closed
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+struct Session {
+    backend: Box<dyn ConversionBackend>,
+    last_active: Instant,
+}
+
+// holds the composing text of each client
+// so that two clients typing at once don't clobber each other's composition
+pub struct SessionManager {
+    factory: Box<dyn BackendFactory>,
+    sessions: HashMap<u32, Session>,
+    next_id: u32,
+    prediction: PredictionConfig,
+    symbols: SymbolTable,
+}
+
+impl SessionManager {
+    pub fn new(factory: Box<dyn BackendFactory>) -> Self {
+        Self {
+            factory,
+            sessions: HashMap::new(),
+            next_id: 0,
+            prediction: PredictionConfig::default(),
+            symbols: SymbolTable::new(),
+        }
+    }
+
+    pub fn create_session(&mut self) -> u32 {
+        // 0 is reserved for the clients which don't create a session
+        let session_id = loop {
+            self.next_id = self.next_id.wrapping_add(1).max(1);
+            if !self.sessions.contains_key(&self.next_id) {
+                break self.next_id;
+            }
+        };
+
+        self.sessions.insert(
+            session_id,
+            Session {
+                backend: self.factory.create(session_id),
+                last_active: Instant::now(),
+            },
+        );
+
+        session_id
+    }
+
+    pub fn close_session(&mut self, session_id: u32) {
+        self.sessions.remove(&session_id);
+    }
+
+    // if the session is unknown (e.g. expired, or the server was restarted), it will be recreated
+    pub fn backend(&mut self, session_id: u32) -> &mut dyn ConversionBackend {
+        let session = self.sessions.entry(session_id).or_insert_with(|| Session {
+            backend: self.factory.create(session_id),
+            last_active: Instant::now(),
+        });
+        session.last_active = Instant::now();
+
+        session.backend.as_mut()
+    }
+
+    pub fn expire_idle_sessions(&mut self, timeout: Duration) -> usize {
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|_, session| session.last_active.elapsed() < timeout);
+
+        before - self.sessions.len()
+    }
+
+    pub fn load_config(&mut self, config: &AppConfig) {
+        self.prediction = config.prediction.clone();
+        self.symbols = load_symbol_table(&config.input.symbol_table);
+        self.factory.load_config(config);
+    }
+
+    // the predictions are limited by the settings, so that they don't hide the conversion candidates
+    // reading_length is the count of the hiragana before the cursor
+    pub fn predictions(&mut self, session_id: u32, reading_length: i32) -> Vec<Suggestion> {
+        if !self.prediction.enable || reading_length < self.prediction.min_reading_length as i32 {
+            return vec![];
+        }
+
+        let max_count = self.prediction.max_count;
+        let mut predictions = self.backend(session_id).get_predictions();
+        predictions.truncate(max_count);
+        predictions
+    }
+
+    // the candidates after the composing text is edited
+    // the symbols are added here, so that every request which returns the candidates offers them
+    pub fn composing_text(&mut self, session_id: u32, text: RawComposingText) -> ComposingText {
+        let mut suggestions = self.backend(session_id).get_composed_text();
+        self.symbols.append_to(&mut suggestions);
+        let predictions = self.predictions(session_id, text.cursor);
+
+        ComposingText {
+            hiragana: text.text,
+            suggestions,
+            cursor: text.cursor,
+            clauses: vec![],
+            predictions,
+        }
+    }
+
+    pub fn clause_candidates(&mut self, session_id: u32, index: usize) -> Vec<Suggestion> {
+        let mut suggestions = self.backend(session_id).get_clause_candidates(index);
+        self.symbols.append_to(&mut suggestions);
+        suggestions
+    }
+
+    pub fn set_user_dictionary(&self, entries: &[UserDictEntry]) {
+        self.factory.set_user_dictionary(entries);
+    }
+
+    pub fn clear_learning_history(&self) {
+        self.factory.clear_learning_history();
+    }
+}
+
+// a broken symbol table shouldn't stop the conversion, so the default one is used instead
+fn load_symbol_table(path: &str) -> SymbolTable {
+    if path.is_empty() {
+        return SymbolTable::new();
+    }
+
+    match SymbolTable::load(Path::new(path)) {
+        Ok(table) => table,
+        Err(e) => {
+            println!("Failed to read the symbol table: {}", e);
+            SymbolTable::new()
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, io, path::Path};
+
+use shared::proto::{CandidateSource, Suggestion};
+
+// the categories of the symbol palette, see symbols.tsv for the format
+const DEFAULT_TABLE: &str = include_str!("symbols.tsv");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolCategory {
+    pub name: String,
+    pub symbols: Vec<String>,
+}
+
+// the categories of each reading, in the order of the table
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    categories: HashMap<String, Vec<SymbolCategory>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        let mut table = SymbolTable::default();
+        table.extend(DEFAULT_TABLE);
+        table
+    }
+
+    // the user table is added to the default one
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        let mut table = SymbolTable::new();
+        table.extend(&text);
+        Ok(table)
+    }
+
+    // each line is "readings<TAB>category<TAB>symbols"
+    // the symbols of the existing category are appended, so that the user can add to the default ones
+    pub fn extend(&mut self, text: &str) {
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut columns = line.split('\t');
+            let (Some(readings), Some(name), Some(symbols)) =
+                (columns.next(), columns.next(), columns.next())
+            else {
+                continue;
+            };
+            let symbols: Vec<String> = symbols.split_whitespace().map(str::to_string).collect();
+
+            for reading in readings.split(',').map(str::trim) {
+                if reading.is_empty() {
+                    continue;
+                }
+
+                let categories = self.categories.entry(reading.to_string()).or_default();
+                match categories.iter_mut().find(|category| category.name == name) {
+                    Some(category) => {
+                        for symbol in &symbols {
+                            if !category.symbols.contains(symbol) {
+                                category.symbols.push(symbol.clone());
+                            }
+                        }
+                    }
+                    None => categories.push(SymbolCategory {
+                        name: name.to_string(),
+                        symbols: symbols.clone(),
+                    }),
+                }
+            }
+        }
+    }
+
+    pub fn lookup(&self, reading: &str) -> &[SymbolCategory] {
+        self.categories
+            .get(reading)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // the symbols follow the conversion candidates, when the whole reading is the name of a category
+    // the category is shown as the annotation in the candidate window
+    pub fn append_to(&self, suggestions: &mut Vec<Suggestion>) {
+        let Some(whole) = suggestions.iter().find(|s| s.subtext.is_empty()).cloned() else {
+            return;
+        };
+
+        for category in self.lookup(&whole.reading) {
+            for symbol in &category.symbols {
+                if suggestions.iter().any(|s| s.text == *symbol) {
+                    continue;
+                }
+
+                suggestions.push(Suggestion {
+                    text: symbol.clone(),
+                    subtext: String::new(),
+                    corresponding_count: whole.corresponding_count,
+                    reading: whole.reading.clone(),
+                    source: CandidateSource::Symbol as i32,
+                    annotation: category.name.clone(),
+                    // the symbols are not ranked by the converter
+                    score: f32::MIN,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(text: &str) -> SymbolTable {
+        let mut table = SymbolTable::default();
+        table.extend(text);
+        table
+    }
+
+    fn names(categories: &[SymbolCategory]) -> Vec<&str> {
+        categories
+            .iter()
+            .map(|category| category.name.as_str())
+            .collect()
+    }
+
+    fn suggestion(text: &str, subtext: &str, reading: &str) -> Suggestion {
+        Suggestion {
+            text: text.to_string(),
+            subtext: subtext.to_string(),
+            corresponding_count: reading.chars().count() as i32,
+            reading: reading.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_readings_and_symbols() {
+        let table = table("やじるし, きごう\t矢印\t→  ←\r\nきごう\t記号\t※\n");
+
+        assert_eq!(
+            table.lookup("やじるし"),
+            &[SymbolCategory {
+                name: "矢印".to_string(),
+                symbols: vec!["→".to_string(), "←".to_string()],
+            }]
+        );
+        // the categories are in the order of the table
+        assert_eq!(names(table.lookup("きごう")), vec!["矢印", "記号"]);
+        assert!(table.lookup("かっこ").is_empty());
+    }
+
+    #[test]
+    fn user_symbols_are_merged_into_the_category() {
+        let mut table = table("やじるし\t矢印\t→ ←\n");
+        table.extend("やじるし\t矢印\t← ⇒\nやじるし\t絵文字\t👉\n");
+
+        let categories = table.lookup("やじるし");
+        assert_eq!(names(categories), vec!["矢印", "絵文字"]);
+        assert_eq!(categories[0].symbols, vec!["→", "←", "⇒"]);
+    }
+
+    #[test]
+    fn comments_and_malformed_lines_are_skipped() {
+        let table =
+            table("# やじるし\t矢印\t→\n\nやじるし\t矢印\nやじるし\n\t空\t○\nまる\t丸\t○\n");
+
+        assert!(table.lookup("やじるし").is_empty());
+        assert!(table.lookup("").is_empty());
+        assert_eq!(names(table.lookup("まる")), vec!["丸"]);
+    }
+
+    #[test]
+    fn default_table_is_loaded() {
+        let table = SymbolTable::new();
+        assert!(names(table.lookup("きごう")).contains(&"矢印"));
+        assert!(table.lookup("やじるし")[0]
+            .symbols
+            .contains(&"→".to_string()));
+    }
+
+    #[test]
+    fn symbols_follow_the_candidates_without_duplicates() {
+        let table = table("やじるし\t矢印\t→ ← ↑\n");
+        let mut suggestions = vec![
+            suggestion("矢印", "", "やじるし"),
+            suggestion("→", "", "やじるし"),
+            suggestion("矢", "しるし", "や"),
+        ];
+        table.append_to(&mut suggestions);
+
+        let texts: Vec<&str> = suggestions.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["矢印", "→", "矢", "←", "↑"]);
+
+        let symbol = &suggestions[3];
+        assert_eq!(symbol.source, CandidateSource::Symbol as i32);
+        assert_eq!(symbol.annotation, "矢印");
+        assert_eq!(symbol.reading, "やじるし");
+        assert_eq!(symbol.corresponding_count, 4);
+    }
+
+    #[test]
+    fn symbols_need_the_whole_reading() {
+        let table = table("やじるし\t矢印\t→\n");
+
+        // only a part of the reading is converted
+        let mut suggestions = vec![suggestion("矢", "じるし", "や")];
+        table.append_to(&mut suggestions);
+        assert_eq!(suggestions.len(), 1);
+
+        let mut suggestions = vec![];
+        table.append_to(&mut suggestions);
+        assert!(suggestions.is_empty());
+    }
+}
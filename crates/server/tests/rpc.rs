@@ -0,0 +1,259 @@
+// the AzookeyService RPCs against the mock backend, served over tcp instead of the named pipe
+use std::sync::{Arc, Mutex};
+
+use azookey_server::{
+    backend::mock::MockBackendFactory, service::MyAzookeyService, session::SessionManager,
+};
+use shared::proto::{
+    azookey_service_client::AzookeyServiceClient, azookey_service_server::AzookeyServiceServer,
+    AppendTextRequest, ClearTextRequest, CloseSessionRequest, CommitCandidateRequest,
+    ComposingText, CreateSessionRequest, GetClauseCandidatesRequest, GetClausesRequest,
+    MoveCursorRequest, RemoveTextRequest, ReplaceTextRequest, ResizeClauseRequest,
+    ReverseLookupRequest, ShrinkTextRequest,
+};
+use tokio::net::TcpListener;
+use tonic::{
+    transport::{server::TcpIncoming, Channel, Server},
+    Code,
+};
+
+type Client = AzookeyServiceClient<Channel>;
+
+async fn start_server() -> Client {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+
+    let sessions = SessionManager::new(Box::new(MockBackendFactory::new()));
+    let service = MyAzookeyService::new(Arc::new(Mutex::new(sessions)));
+    tokio::spawn(
+        Server::builder()
+            .add_service(AzookeyServiceServer::new(service))
+            .serve_with_incoming(incoming),
+    );
+
+    AzookeyServiceClient::connect(format!("http://{address}"))
+        .await
+        .unwrap()
+}
+
+async fn create_session(client: &mut Client) -> u32 {
+    client
+        .create_session(CreateSessionRequest {})
+        .await
+        .unwrap()
+        .into_inner()
+        .session_id
+}
+
+async fn append_text(client: &mut Client, session_id: u32, text: &str) -> ComposingText {
+    client
+        .append_text(AppendTextRequest {
+            text_to_append: text.to_string(),
+            session_id,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .composing_text
+        .unwrap()
+}
+
+fn best(composing_text: &ComposingText) -> &str {
+    &composing_text.suggestions[0].text
+}
+
+#[tokio::test]
+async fn append_and_remove_text() {
+    let mut client = start_server().await;
+    let session_id = create_session(&mut client).await;
+    assert_ne!(session_id, 0);
+
+    let composing_text = append_text(&mut client, session_id, "hasi").await;
+    assert_eq!(composing_text.hiragana, "はし");
+    assert_eq!(composing_text.cursor, 2);
+    assert_eq!(best(&composing_text), "橋");
+
+    let composing_text = client
+        .remove_text(RemoveTextRequest { session_id })
+        .await
+        .unwrap()
+        .into_inner()
+        .composing_text
+        .unwrap();
+    assert_eq!(composing_text.hiragana, "は");
+    assert_eq!(composing_text.cursor, 1);
+}
+
+#[tokio::test]
+async fn replace_pending_romaji() {
+    let mut client = start_server().await;
+    let session_id = create_session(&mut client).await;
+
+    // the client shows the pending romaji as full width alphabets, and replaces them with the kana
+    append_text(&mut client, session_id, "はｓ").await;
+    let composing_text = client
+        .replace_text(ReplaceTextRequest {
+            remove_count: 1,
+            text_to_append: "し".to_string(),
+            session_id,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .composing_text
+        .unwrap();
+    assert_eq!(composing_text.hiragana, "はし");
+    assert_eq!(composing_text.cursor, 2);
+    assert_eq!(best(&composing_text), "橋");
+}
+
+#[tokio::test]
+async fn move_cursor_and_shrink_text() {
+    let mut client = start_server().await;
+    let session_id = create_session(&mut client).await;
+    append_text(&mut client, session_id, "hasi").await;
+
+    let composing_text = client
+        .move_cursor(MoveCursorRequest {
+            offset: -1,
+            session_id,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .composing_text
+        .unwrap();
+    assert_eq!(composing_text.cursor, 1);
+
+    // the first character is committed, and the rest is left to be composed
+    let composing_text = client
+        .shrink_text(ShrinkTextRequest {
+            offset: 2,
+            session_id,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .composing_text
+        .unwrap();
+    assert_eq!(composing_text.hiragana, "し");
+
+    client
+        .clear_text(ClearTextRequest { session_id })
+        .await
+        .unwrap();
+    let composing_text = append_text(&mut client, session_id, "a").await;
+    assert_eq!(composing_text.hiragana, "あ");
+}
+
+#[tokio::test]
+async fn sessions_keep_their_own_text() {
+    let mut client = start_server().await;
+    let first = create_session(&mut client).await;
+    let second = create_session(&mut client).await;
+    assert_ne!(first, second);
+
+    append_text(&mut client, first, "ha").await;
+    append_text(&mut client, second, "a").await;
+    assert_eq!(append_text(&mut client, first, "si").await.hiragana, "はし");
+    assert_eq!(append_text(&mut client, second, "i").await.hiragana, "あい");
+
+    // the closed session starts again from the empty text
+    client
+        .close_session(CloseSessionRequest { session_id: first })
+        .await
+        .unwrap();
+    assert_eq!(append_text(&mut client, first, "a").await.hiragana, "あ");
+}
+
+#[tokio::test]
+async fn clauses() {
+    let mut client = start_server().await;
+    let session_id = create_session(&mut client).await;
+    append_text(&mut client, session_id, "hasinihasi").await;
+
+    let composing_text = client
+        .get_clauses(GetClausesRequest { session_id })
+        .await
+        .unwrap()
+        .into_inner()
+        .composing_text
+        .unwrap();
+    assert!(!composing_text.clauses.is_empty());
+    let reading: String = composing_text
+        .clauses
+        .iter()
+        .map(|clause| clause.reading.as_str())
+        .collect();
+    assert_eq!(reading, "はしにはし");
+    assert_eq!(composing_text.cursor, 5);
+
+    let resized = client
+        .resize_clause(ResizeClauseRequest {
+            session_id,
+            index: 0,
+            offset: -1,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .composing_text
+        .unwrap();
+    assert_eq!(resized.clauses[0].reading, "は");
+
+    let suggestions = client
+        .get_clause_candidates(GetClauseCandidatesRequest {
+            session_id,
+            index: 0,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .suggestions;
+    assert!(!suggestions.is_empty());
+
+    let error = client
+        .get_clause_candidates(GetClauseCandidatesRequest {
+            session_id,
+            index: -1,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn committed_candidate_is_learned_and_looked_up() {
+    let mut client = start_server().await;
+    let session_id = create_session(&mut client).await;
+    assert_eq!(
+        best(&append_text(&mut client, session_id, "hasi").await),
+        "橋"
+    );
+
+    client
+        .commit_candidate(CommitCandidateRequest {
+            session_id,
+            reading: "はし".to_string(),
+            surface: "箸".to_string(),
+            corresponding_count: 4,
+        })
+        .await
+        .unwrap();
+
+    // the learning is shared between sessions
+    let other = create_session(&mut client).await;
+    assert_eq!(best(&append_text(&mut client, other, "hasi").await), "箸");
+
+    let reading = client
+        .reverse_lookup(ReverseLookupRequest {
+            session_id,
+            surface: "橋".to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .reading;
+    assert_eq!(reading, "はし");
+}
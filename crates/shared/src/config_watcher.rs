@@ -0,0 +1,75 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use crate::{get_config_root, AppConfig, SETTINGS_FILENAME};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+// watches settings.json and calls the callback with the new config when it is changed
+// polling is used instead of the file system notification,
+// since AppConfig::write replaces the file and the notification is not delivered reliably for it
+// the thread is stopped when the watcher is dropped
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+// modified time and length of the file, to avoid reading it on every poll
+fn file_stamp() -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(get_config_root().join(SETTINGS_FILENAME)).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl ConfigWatcher {
+    pub fn spawn<F>(interval: Duration, mut on_change: F) -> Self
+    where
+        F: FnMut(AppConfig) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let handle = thread::spawn(move || {
+            let mut stamp = file_stamp();
+
+            loop {
+                thread::park_timeout(interval);
+                if thread_stop.load(Ordering::Acquire) {
+                    return;
+                }
+
+                let current = file_stamp();
+                if current.is_none() || current == stamp {
+                    continue;
+                }
+                stamp = current;
+
+                // the file may be in the middle of writing, it will be read again on the next change
+                if let Ok(config) = AppConfig::read() {
+                    on_change(config);
+                }
+            }
+        });
+
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io};
+
+use crate::{get_config_root, InputModeSetting};
+
+const INPUT_MODE_MEMORY_FILENAME: &str = "input_modes.json";
+
+// last input mode of each app, keyed by the lowercase executable name
+// this is a file, since every app loads its own copy of the text service
+// and the mode should be kept after the app is closed
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct InputModeMemory {
+    modes: HashMap<String, InputModeSetting>,
+}
+
+impl InputModeMemory {
+    pub fn read() -> io::Result<Self> {
+        let memory_path = get_config_root().join(INPUT_MODE_MEMORY_FILENAME);
+        if !memory_path.exists() {
+            return Ok(InputModeMemory::default());
+        }
+        let memory_str = std::fs::read_to_string(memory_path)?;
+        serde_json::from_str(&memory_str).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // the apps may write at the same time, so the file is replaced instead of being overwritten
+    pub fn write(&self) -> io::Result<()> {
+        let config_root = get_config_root();
+        if !config_root.exists() {
+            std::fs::create_dir_all(&config_root)?;
+        }
+
+        let memory_path = config_root.join(INPUT_MODE_MEMORY_FILENAME);
+        let temp_path = memory_path.with_extension(format!("json.{}.tmp", std::process::id()));
+        let memory_str = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        std::fs::write(&temp_path, memory_str)?;
+        std::fs::rename(&temp_path, &memory_path)
+    }
+
+    pub fn get(&self, app: &str) -> Option<InputModeSetting> {
+        self.modes.get(&app.to_lowercase()).copied()
+    }
+
+    // returns true if the mode is changed
+    pub fn set(&mut self, app: &str, mode: InputModeSetting) -> bool {
+        self.modes.insert(app.to_lowercase(), mode) != Some(mode)
+    }
+}
@@ -0,0 +1,484 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+// names of the keys used in the keymap, and their virtual key codes
+const KEY_NAMES: &[(&str, u16)] = &[
+    ("Backspace", 0x08),
+    ("Tab", 0x09),
+    ("Enter", 0x0D),
+    ("Escape", 0x1B),
+    ("Space", 0x20),
+    ("PageUp", 0x21),
+    ("PageDown", 0x22),
+    ("End", 0x23),
+    ("Home", 0x24),
+    ("Left", 0x25),
+    ("Up", 0x26),
+    ("Right", 0x27),
+    ("Down", 0x28),
+    ("Delete", 0x2E),
+    ("Henkan", 0x1C),
+    ("Muhenkan", 0x1D),
+    ("Kana", 0x15),
+    ("Eisu", 0xF0),
+    ("Zenkaku", 0xF3),
+    ("Hankaku", 0xF4),
+    ("F1", 0x70),
+    ("F2", 0x71),
+    ("F3", 0x72),
+    ("F4", 0x73),
+    ("F5", 0x74),
+    ("F6", 0x75),
+    ("F7", 0x76),
+    ("F8", 0x77),
+    ("F9", 0x78),
+    ("F10", 0x79),
+    ("F11", 0x7A),
+    ("F12", 0x7B),
+    // the oem keys of the jis keyboard
+    (";", 0xBB),
+    (":", 0xBA),
+    (",", 0xBC),
+    (".", 0xBE),
+    ("/", 0xBF),
+    ("@", 0xC0),
+    ("[", 0xDB),
+    ("]", 0xDD),
+];
+
+// a key with modifiers, written as "Ctrl+Shift+U" in the settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyStroke {
+    pub vk: u16,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyStroke {
+    pub fn new(vk: u16, ctrl: bool, shift: bool, alt: bool) -> Self {
+        Self {
+            vk,
+            ctrl,
+            shift,
+            alt,
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut stroke = KeyStroke::new(0, false, false, false);
+        let mut parts = text.split('+').peekable();
+
+        while let Some(part) = parts.next() {
+            // the last part is the key, so "Ctrl++" is not supported but "Ctrl+;" is
+            if parts.peek().is_some() {
+                match part.trim().to_ascii_lowercase().as_str() {
+                    "ctrl" => stroke.ctrl = true,
+                    "shift" => stroke.shift = true,
+                    "alt" => stroke.alt = true,
+                    _ => return None,
+                }
+                continue;
+            }
+
+            let key = part.trim();
+            stroke.vk = match KEY_NAMES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            {
+                Some((_, vk)) => *vk,
+                // letters and digits are the same as their ascii codes
+                None => match key.chars().collect::<Vec<_>>()[..] {
+                    [c] if c.is_ascii_alphanumeric() => c.to_ascii_uppercase() as u16,
+                    _ => return None,
+                },
+            };
+        }
+
+        Some(stroke)
+    }
+}
+
+impl fmt::Display for KeyStroke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+
+        match KEY_NAMES.iter().find(|(_, vk)| *vk == self.vk) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => match char::from_u32(self.vk as u32) {
+                Some(c) if c.is_ascii_alphanumeric() => write!(f, "{}", c),
+                _ => write!(f, "0x{:02X}", self.vk),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for KeyStroke {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        KeyStroke::parse(&text).ok_or_else(|| format!("unknown key: {}", text))
+    }
+}
+
+impl From<KeyStroke> for String {
+    fn from(stroke: KeyStroke) -> Self {
+        stroke.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeymapState {
+    // nothing is being composed
+    Precomposition,
+    // composing or previewing a candidate
+    Composition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyCommand {
+    Commit,
+    Cancel,
+    Backspace,
+    NextCandidate,
+    PrevCandidate,
+    // complete the text with the first prediction
+    AcceptPrediction,
+    NextPage,
+    PrevPage,
+    MoveLeft,
+    MoveRight,
+    ShrinkClause,
+    ExtendClause,
+    ToHiragana,
+    ToKatakana,
+    ToHalfKatakana,
+    ToFullLatin,
+    ToHalfLatin,
+    ToggleInputMode,
+    // switch between the romaji input and the kana input
+    ToggleInputStyle,
+    HiraganaMode,
+    KatakanaMode,
+    HalfKatakanaMode,
+    FullLatinMode,
+    LatinMode,
+    // convert the committed text before the caret or the selected text again
+    Reconvert,
+    // restore the last committed text as the composition, while the caret is right after it
+    UndoCommit,
+    // pass the key to the application, used to disable a binding of the preset
+    PassThrough,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub state: KeymapState,
+    pub key: KeyStroke,
+    pub command: KeyCommand,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeymapPreset {
+    #[default]
+    MsIme,
+    Atok,
+    Kotoeri,
+}
+
+// bindings shared by all the presets
+const PRECOMPOSITION_BINDINGS: &[(&str, KeyCommand)] = &[
+    ("Zenkaku", KeyCommand::ToggleInputMode),
+    ("Hankaku", KeyCommand::ToggleInputMode),
+    ("Kana", KeyCommand::ToggleInputStyle),
+    ("Shift+Kana", KeyCommand::KatakanaMode),
+    ("Henkan", KeyCommand::Reconvert),
+    ("Ctrl+Backspace", KeyCommand::UndoCommit),
+];
+
+const COMPOSITION_BINDINGS: &[(&str, KeyCommand)] = &[
+    ("Zenkaku", KeyCommand::ToggleInputMode),
+    ("Hankaku", KeyCommand::ToggleInputMode),
+    ("Kana", KeyCommand::ToggleInputStyle),
+    ("Shift+Kana", KeyCommand::KatakanaMode),
+    ("Enter", KeyCommand::Commit),
+    ("Escape", KeyCommand::Cancel),
+    ("Backspace", KeyCommand::Backspace),
+    ("Space", KeyCommand::NextCandidate),
+    ("Tab", KeyCommand::AcceptPrediction),
+    ("Down", KeyCommand::NextCandidate),
+    ("Up", KeyCommand::PrevCandidate),
+    ("PageDown", KeyCommand::NextPage),
+    ("PageUp", KeyCommand::PrevPage),
+    ("Shift+Space", KeyCommand::PrevPage),
+    ("Left", KeyCommand::MoveLeft),
+    ("Right", KeyCommand::MoveRight),
+    ("Shift+Left", KeyCommand::ShrinkClause),
+    ("Shift+Right", KeyCommand::ExtendClause),
+    ("F6", KeyCommand::ToHiragana),
+    ("F7", KeyCommand::ToKatakana),
+    ("F8", KeyCommand::ToHalfKatakana),
+    ("F9", KeyCommand::ToFullLatin),
+    ("F10", KeyCommand::ToHalfLatin),
+];
+
+// bindings of each preset, used while composing
+const MS_IME_BINDINGS: &[(&str, KeyCommand)] = &[
+    ("Ctrl+U", KeyCommand::ToHiragana),
+    ("Ctrl+I", KeyCommand::ToKatakana),
+    ("Ctrl+O", KeyCommand::ToHalfKatakana),
+    ("Ctrl+P", KeyCommand::ToFullLatin),
+    ("Ctrl+T", KeyCommand::ToHalfLatin),
+    ("Ctrl+H", KeyCommand::Backspace),
+    ("Ctrl+M", KeyCommand::Commit),
+    ("Ctrl+N", KeyCommand::Commit),
+    ("Ctrl+K", KeyCommand::MoveLeft),
+    ("Ctrl+L", KeyCommand::MoveRight),
+    ("Ctrl+Z", KeyCommand::Cancel),
+];
+
+const ATOK_BINDINGS: &[(&str, KeyCommand)] = &[
+    ("Ctrl+U", KeyCommand::ToHiragana),
+    ("Ctrl+I", KeyCommand::ToKatakana),
+    ("Ctrl+O", KeyCommand::ToHalfKatakana),
+    ("Ctrl+P", KeyCommand::ToFullLatin),
+    ("Ctrl+@", KeyCommand::ToHalfLatin),
+    ("Ctrl+H", KeyCommand::Backspace),
+    ("Ctrl+M", KeyCommand::Commit),
+    ("Ctrl+N", KeyCommand::Commit),
+    ("Ctrl+K", KeyCommand::ShrinkClause),
+    ("Ctrl+L", KeyCommand::ExtendClause),
+    ("Ctrl+G", KeyCommand::Cancel),
+    ("Henkan", KeyCommand::NextCandidate),
+    ("Muhenkan", KeyCommand::ToHiragana),
+];
+
+const KOTOERI_BINDINGS: &[(&str, KeyCommand)] = &[
+    ("Ctrl+J", KeyCommand::ToHiragana),
+    ("Ctrl+K", KeyCommand::ToKatakana),
+    ("Ctrl+;", KeyCommand::ToHalfKatakana),
+    ("Ctrl+L", KeyCommand::ToFullLatin),
+    ("Ctrl+:", KeyCommand::ToHalfLatin),
+    ("Ctrl+H", KeyCommand::Backspace),
+    ("Ctrl+M", KeyCommand::Commit),
+    ("Ctrl+G", KeyCommand::Cancel),
+    ("Ctrl+B", KeyCommand::MoveLeft),
+    ("Ctrl+F", KeyCommand::MoveRight),
+    ("Ctrl+N", KeyCommand::NextCandidate),
+    ("Ctrl+P", KeyCommand::PrevCandidate),
+    ("Ctrl+Shift+B", KeyCommand::ShrinkClause),
+    ("Ctrl+Shift+F", KeyCommand::ExtendClause),
+];
+
+impl KeymapPreset {
+    pub fn bindings(&self) -> Vec<KeyBinding> {
+        let preset = match self {
+            KeymapPreset::MsIme => MS_IME_BINDINGS,
+            KeymapPreset::Atok => ATOK_BINDINGS,
+            KeymapPreset::Kotoeri => KOTOERI_BINDINGS,
+        };
+
+        let precomposition = PRECOMPOSITION_BINDINGS
+            .iter()
+            .map(|binding| (KeymapState::Precomposition, binding));
+        let composition = COMPOSITION_BINDINGS
+            .iter()
+            .chain(preset)
+            .map(|binding| (KeymapState::Composition, binding));
+
+        precomposition
+            .chain(composition)
+            .map(|(state, (key, command))| KeyBinding {
+                state,
+                key: KeyStroke::parse(key).expect("invalid key in the preset"),
+                command: *command,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeymapConfig {
+    pub preset: KeymapPreset,
+    // bindings added by the user, they take precedence over the preset
+    #[serde(deserialize_with = "crate::lenient_list")]
+    pub overrides: Vec<KeyBinding>,
+}
+
+// lookup table built from the config
+#[derive(Debug, Clone, Default)]
+pub struct Keymap {
+    bindings: HashMap<(KeymapState, KeyStroke), KeyCommand>,
+}
+
+impl Keymap {
+    pub fn new(config: &KeymapConfig) -> Self {
+        let bindings = config
+            .preset
+            .bindings()
+            .into_iter()
+            .chain(config.overrides.iter().cloned())
+            .map(|binding| ((binding.state, binding.key), binding.command))
+            .collect();
+
+        Self { bindings }
+    }
+
+    pub fn lookup(&self, state: KeymapState, key: &KeyStroke) -> Option<KeyCommand> {
+        self.bindings.get(&(state, *key)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: [KeymapPreset; 3] = [
+        KeymapPreset::MsIme,
+        KeymapPreset::Atok,
+        KeymapPreset::Kotoeri,
+    ];
+
+    fn key(text: &str) -> KeyStroke {
+        KeyStroke::parse(text).unwrap()
+    }
+
+    fn binding(key_text: &str, command: KeyCommand) -> KeyBinding {
+        KeyBinding {
+            state: KeymapState::Composition,
+            key: key(key_text),
+            command,
+        }
+    }
+
+    #[test]
+    fn every_preset_key_parses() {
+        let tables = [
+            PRECOMPOSITION_BINDINGS,
+            COMPOSITION_BINDINGS,
+            MS_IME_BINDINGS,
+            ATOK_BINDINGS,
+            KOTOERI_BINDINGS,
+        ];
+        for (text, _) in tables.into_iter().flatten() {
+            let stroke = KeyStroke::parse(text);
+            assert!(stroke.is_some(), "{} doesn't parse", text);
+            assert_eq!(stroke.unwrap().to_string(), *text);
+        }
+    }
+
+    #[test]
+    fn presets_have_no_conflicts() {
+        for preset in PRESETS {
+            let bindings = preset.bindings();
+            let keymap = Keymap::new(&KeymapConfig {
+                preset,
+                overrides: vec![],
+            });
+            assert_eq!(keymap.bindings.len(), bindings.len(), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(key("Ctrl+Shift+u"), KeyStroke::new(0x55, true, true, false));
+        assert_eq!(key("alt + F10"), KeyStroke::new(0x79, false, false, true));
+        assert_eq!(key("Ctrl+;"), KeyStroke::new(0xBB, true, false, false));
+        assert_eq!(key("3"), KeyStroke::new(0x33, false, false, false));
+        assert_eq!(KeyStroke::parse("Ctrl+Nope"), None);
+        assert_eq!(KeyStroke::parse("Hyper+A"), None);
+        assert_eq!(KeyStroke::parse("Ctrl+"), None);
+        assert_eq!(KeyStroke::parse(""), None);
+    }
+
+    #[test]
+    fn overrides_are_merged_with_the_preset() {
+        let keymap = Keymap::new(&KeymapConfig {
+            preset: KeymapPreset::MsIme,
+            overrides: vec![
+                binding("Ctrl+J", KeyCommand::ToHiragana),
+                binding("Ctrl+U", KeyCommand::ToKatakana),
+                binding("Tab", KeyCommand::PassThrough),
+            ],
+        });
+        let lookup = |text| keymap.lookup(KeymapState::Composition, &key(text));
+
+        // a new binding, and the ones which replace the preset
+        assert_eq!(lookup("Ctrl+J"), Some(KeyCommand::ToHiragana));
+        assert_eq!(lookup("Ctrl+U"), Some(KeyCommand::ToKatakana));
+        assert_eq!(lookup("Tab"), Some(KeyCommand::PassThrough));
+        // the rest of the preset is kept
+        assert_eq!(lookup("Ctrl+I"), Some(KeyCommand::ToKatakana));
+        assert_eq!(lookup("Space"), Some(KeyCommand::NextCandidate));
+        // the override is only for its state
+        assert_eq!(
+            keymap.lookup(KeymapState::Precomposition, &key("Ctrl+J")),
+            None
+        );
+    }
+
+    #[test]
+    fn conflicting_overrides_take_the_last() {
+        let keymap = Keymap::new(&KeymapConfig {
+            preset: KeymapPreset::Atok,
+            overrides: vec![
+                binding("Ctrl+J", KeyCommand::ToHiragana),
+                binding("Ctrl+J", KeyCommand::Commit),
+            ],
+        });
+
+        assert_eq!(
+            keymap.lookup(KeymapState::Composition, &key("Ctrl+J")),
+            Some(KeyCommand::Commit)
+        );
+    }
+
+    #[test]
+    fn invalid_overrides_are_dropped() {
+        let config: KeymapConfig = serde_json::from_str(
+            r#"{
+                "preset": "kotoeri",
+                "overrides": [
+                    {"state": "composition", "key": "Ctrl+Nope", "command": "commit"},
+                    {"state": "composition", "key": "Ctrl+Q", "command": "no_such_command"},
+                    {"state": "somewhere", "key": "Ctrl+Q", "command": "commit"},
+                    {"state": "composition", "key": "Ctrl+Q", "command": "cancel"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.preset, KeymapPreset::Kotoeri);
+        assert_eq!(
+            config.overrides,
+            vec![binding("Ctrl+Q", KeyCommand::Cancel)]
+        );
+    }
+
+    #[test]
+    fn bindings_round_trip() {
+        let json =
+            serde_json::to_string(&binding("Ctrl+Shift+F", KeyCommand::ExtendClause)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"state":"composition","key":"Ctrl+Shift+F","command":"extend_clause"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<KeyBinding>(&json).unwrap(),
+            binding("Ctrl+Shift+F", KeyCommand::ExtendClause)
+        );
+    }
+}
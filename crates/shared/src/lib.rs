@@ -1,5 +1,5 @@
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use std::{io, path::PathBuf};
 
 pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/azookey.rs"));
@@ -8,6 +8,12 @@ pub mod proto {
         tonic::include_file_descriptor_set!("azookey_service_descriptor");
 }
 
+pub mod config_watcher;
+pub mod input_mode_memory;
+pub mod keymap;
+pub mod romaji;
+pub mod user_dict;
+
 fn get_config_root() -> PathBuf {
     let appdata = PathBuf::from(std::env::var("APPDATA").unwrap());
     appdata.join("Azookey")
@@ -15,55 +21,439 @@ fn get_config_root() -> PathBuf {
 
 const SETTINGS_FILENAME: &str = "settings.json";
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+// exit code of the server stopped by the Shutdown request, the launcher starts it again
+pub const SERVER_RESTART_EXIT_CODE: i32 = 3;
+
+// schema version of settings.json, bump this and add a migration when the layout changes
+pub const CONFIG_VERSION: u32 = 1;
+
+// migrations[n] converts the settings of version n into version n + 1
+const MIGRATIONS: &[fn(&mut serde_json::Value)] = &[migrate_v0_to_v1];
+
+// v0: the settings before the schema version was introduced, whose version is a free string like "0.1.0"
+// the backend was a free string too, so the unknown ones fall back to cpu
+fn migrate_v0_to_v1(value: &mut serde_json::Value) {
+    if let Some(zenzai) = value.get_mut("zenzai").and_then(|z| z.as_object_mut()) {
+        let known = matches!(
+            zenzai.get("backend").and_then(|b| b.as_str()),
+            Some("cpu" | "cuda" | "vulkan")
+        );
+        if !known {
+            zenzai.insert("backend".to_string(), "cpu".into());
+        }
+    }
+}
+
+// the fields are deserialized one by one, and a field of the wrong type is left to the default value
+// e.g. "page_size": "9" doesn't throw away the other settings
+fn from_value_lenient<T: DeserializeOwned>(value: serde_json::Value) -> serde_json::Result<T> {
+    if let Ok(parsed) = serde_json::from_value(value.clone()) {
+        return Ok(parsed);
+    }
+    let serde_json::Value::Object(fields) = value else {
+        return serde_json::from_value(value);
+    };
+
+    let mut valid = serde_json::Map::new();
+    for (key, field) in fields {
+        valid.insert(key.clone(), field);
+        if serde_json::from_value::<T>(valid.clone().into()).is_err() {
+            valid.remove(&key);
+        }
+    }
+    serde_json::from_value(valid.into())
+}
+
+// for the nested settings, the invalid fields inside are dropped instead of the whole section
+pub(crate) fn lenient<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    from_value_lenient(value).map_err(serde::de::Error::custom)
+}
+
+// the invalid items of the list are dropped, e.g. a key binding with an unknown key name
+pub(crate) fn lenient_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let items = Vec::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(items
+        .into_iter()
+        .filter_map(|item| from_value_lenient(item).ok())
+        .collect())
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ZenzaiBackend {
+    #[default]
+    Cpu,
+    Cuda,
+    Vulkan,
+}
+
+impl ZenzaiBackend {
+    // directory of llama.cpp built for the backend, next to the executables
+    pub fn library_dir(&self) -> &'static str {
+        match self {
+            ZenzaiBackend::Cpu => "llama_cpu",
+            ZenzaiBackend::Cuda => "llama_cuda",
+            ZenzaiBackend::Vulkan => "llama_vulkan",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
 pub struct ZenzaiConfig {
     pub enable: bool,
     pub profile: String,
-    pub backend: String,
+    pub backend: ZenzaiBackend,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct LearningConfig {
+    pub enable: bool,
+    // use the learned candidates, but don't learn the new ones
+    pub read_only: bool,
+}
+
+impl Default for LearningConfig {
+    fn default() -> Self {
+        LearningConfig {
+            enable: true,
+            read_only: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct PredictionConfig {
+    pub enable: bool,
+    // the predictions are shown after this many hiragana are typed
+    pub min_reading_length: usize,
+    pub max_count: usize,
+}
+
+impl Default for PredictionConfig {
+    fn default() -> Self {
+        PredictionConfig {
+            enable: true,
+            min_reading_length: 2,
+            max_count: 3,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CandidateWindowConfig {
+    // count of the candidates in a page, they are selected with the number keys 1-9
+    pub page_size: usize,
+}
+
+impl Default for CandidateWindowConfig {
+    fn default() -> Self {
+        CandidateWindowConfig { page_size: 9 }
+    }
+}
+
+impl CandidateWindowConfig {
+    pub fn page_size(&self) -> usize {
+        self.page_size.clamp(1, 9)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum InputStyle {
+    #[default]
+    Romaji,
+    // kana printed on the keys of the JIS keyboard
+    Kana,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct InputConfig {
+    pub style: InputStyle,
+    // path of the user romaji table, its rules are added to the default table
+    pub romaji_table: String,
+    // path of the user symbol table, its categories are added to the default symbol palette
+    pub symbol_table: String,
+    // restore the last input mode of the app when it is focused
+    pub remember_mode: bool,
+    // show the conversion while typing, or show the hiragana until Space is pressed
+    pub live_conversion: bool,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        InputConfig {
+            style: InputStyle::default(),
+            romaji_table: String::new(),
+            symbol_table: String::new(),
+            remember_mode: true,
+            live_conversion: true,
+        }
+    }
+}
+
+// the input modes of the client, used by the settings
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputModeSetting {
+    Hiragana,
+    Katakana,
+    HalfKatakana,
+    FullLatin,
+    Latin,
+}
+
+// settings for an app, matched by the executable name such as "WindowsTerminal.exe"
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct AppRule {
+    pub app: String,
+    // the input mode when the app is focused, instead of the remembered one
+    pub input_mode: Option<InputModeSetting>,
+    // overrides input.live_conversion
+    pub live_conversion: Option<bool>,
+}
+
+// the terminals start in latin, since most of the input there is commands
+const TERMINAL_APPS: &[&str] = &[
+    "WindowsTerminal.exe",
+    "cmd.exe",
+    "powershell.exe",
+    "pwsh.exe",
+    "conhost.exe",
+];
+
+// the live conversion rewrites the cell while typing, which is confusing in the spreadsheet
+const CLASSIC_CONVERSION_APPS: &[&str] = &["EXCEL.EXE"];
+
+fn default_app_rules() -> Vec<AppRule> {
+    let terminals = TERMINAL_APPS.iter().map(|app| AppRule {
+        app: app.to_string(),
+        input_mode: Some(InputModeSetting::Latin),
+        live_conversion: None,
+    });
+    let classic_conversion = CLASSIC_CONVERSION_APPS.iter().map(|app| AppRule {
+        app: app.to_string(),
+        input_mode: None,
+        live_conversion: Some(false),
+    });
+
+    terminals.chain(classic_conversion).collect()
+}
+
+// the missing fields are filled with the default values, so that the older settings can be loaded
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct AppConfig {
-    pub version: String,
+    pub version: u32,
+    #[serde(deserialize_with = "lenient")]
     pub zenzai: ZenzaiConfig,
+    #[serde(deserialize_with = "lenient")]
+    pub learning: LearningConfig,
+    #[serde(deserialize_with = "lenient")]
+    pub prediction: PredictionConfig,
+    #[serde(deserialize_with = "lenient")]
+    pub candidate_window: CandidateWindowConfig,
+    #[serde(deserialize_with = "lenient")]
+    pub keymap: keymap::KeymapConfig,
+    #[serde(deserialize_with = "lenient")]
+    pub input: InputConfig,
+    #[serde(deserialize_with = "lenient_list")]
+    pub app_rules: Vec<AppRule>,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         AppConfig {
-            version: "0.1.0".to_string(),
-            zenzai: ZenzaiConfig {
-                enable: false,
-                profile: "".to_string(),
-                backend: "cpu".to_string(),
-            },
+            version: CONFIG_VERSION,
+            zenzai: ZenzaiConfig::default(),
+            learning: LearningConfig::default(),
+            prediction: PredictionConfig::default(),
+            candidate_window: CandidateWindowConfig::default(),
+            keymap: keymap::KeymapConfig::default(),
+            input: InputConfig::default(),
+            app_rules: default_app_rules(),
         }
     }
 }
 
 impl AppConfig {
-    pub fn write(&self) {
-        let config_path = get_config_root().join(SETTINGS_FILENAME);
-        let config_str = serde_json::to_string_pretty(self).unwrap();
-        std::fs::write(config_path, config_str).unwrap();
+    // the executable name is compared case-insensitively, like the file system
+    pub fn app_rule(&self, app: &str) -> Option<&AppRule> {
+        self.app_rules
+            .iter()
+            .find(|rule| rule.app.eq_ignore_ascii_case(app))
+    }
+
+    pub fn live_conversion(&self, app: &str) -> bool {
+        self.app_rule(app)
+            .and_then(|rule| rule.live_conversion)
+            .unwrap_or(self.input.live_conversion)
     }
 
-    pub fn read() -> Self {
+    // write to a temporary file and replace, so that a crash while writing doesn't break the settings
+    pub fn write(&self) -> io::Result<()> {
+        let config_root = get_config_root();
+        if !config_root.exists() {
+            std::fs::create_dir_all(&config_root)?;
+        }
+
+        let config_path = config_root.join(SETTINGS_FILENAME);
+        let temp_path = config_path.with_extension("json.tmp");
+        let config_str = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        std::fs::write(&temp_path, config_str)?;
+        std::fs::rename(&temp_path, &config_path)
+    }
+
+    pub fn read() -> io::Result<Self> {
         let config_path = get_config_root().join(SETTINGS_FILENAME);
         if !config_path.exists() {
-            return AppConfig::default();
+            return Ok(AppConfig::default());
         }
-        let config_str = std::fs::read_to_string(config_path).unwrap();
-        serde_json::from_str(&config_str).unwrap()
+        let config_str = std::fs::read_to_string(config_path)?;
+        Self::parse(&config_str)
     }
 
-    pub fn new() -> Self {
-        let config_path = get_config_root();
-        if !config_path.exists() {
-            std::fs::create_dir_all(&config_path).unwrap();
+    // parse the settings of any version, and migrate them to the current one
+    pub fn parse(config_str: &str) -> io::Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_str(config_str)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // the settings written by a newer version are loaded as they are
+        let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        for migration in MIGRATIONS.iter().skip(version) {
+            migration(&mut value);
         }
-        let config = AppConfig::read();
-        config.write();
-        config
+        if let Some(object) = value.as_object_mut() {
+            object.insert(
+                "version".to_string(),
+                version.max(CONFIG_VERSION as usize).into(),
+            );
+        }
+
+        from_value_lenient(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // load the settings and save them in the current version
+    // the invalid fields are reset to the default values by parse
+    // a file which is not json at all is kept as settings.json.broken, and the default settings are used instead
+    pub fn new() -> io::Result<Self> {
+        let config = match AppConfig::read() {
+            Ok(config) => config,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                let config_path = get_config_root().join(SETTINGS_FILENAME);
+                std::fs::rename(&config_path, config_path.with_extension("json.broken"))?;
+                AppConfig::default()
+            }
+            Err(e) => return Err(e),
+        };
+        config.write()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keymap::{KeyBinding, KeyCommand, KeyStroke, KeymapState};
+
+    #[test]
+    fn v0_backend_falls_back_to_cpu() {
+        let config = AppConfig::parse(
+            r#"{"version": "0.1.0", "zenzai": {"enable": true, "profile": "猫", "backend": "metal"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert!(config.zenzai.enable);
+        assert_eq!(config.zenzai.profile, "猫");
+        assert_eq!(config.zenzai.backend, ZenzaiBackend::Cpu);
+
+        let config = AppConfig::parse(r#"{"zenzai": {"backend": "vulkan"}}"#).unwrap();
+        assert_eq!(config.zenzai.backend, ZenzaiBackend::Vulkan);
+    }
+
+    #[test]
+    fn missing_fields_are_filled_with_the_defaults() {
+        let config =
+            AppConfig::parse(r#"{"version": 1, "learning": {"read_only": true}}"#).unwrap();
+
+        assert!(config.learning.enable);
+        assert!(config.learning.read_only);
+        assert_eq!(config.prediction.min_reading_length, 2);
+        assert_eq!(config.candidate_window.page_size, 9);
+        assert!(config.input.live_conversion);
+        assert_eq!(config.app_rules.len(), default_app_rules().len());
+    }
+
+    #[test]
+    fn newer_version_is_kept() {
+        let config = AppConfig::parse(
+            r#"{"version": 99, "input": {"style": "kana"}, "added_later": {"enable": true}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.version, 99);
+        assert_eq!(config.input.style, InputStyle::Kana);
+    }
+
+    #[test]
+    fn invalid_fields_are_reset_one_by_one() {
+        let config = AppConfig::parse(
+            r#"{
+                "version": 1,
+                "zenzai": 5,
+                "candidate_window": {"page_size": "7"},
+                "input": {"live_conversion": "no", "remember_mode": false, "style": "kana"},
+                "keymap": {
+                    "preset": "atok",
+                    "overrides": [
+                        {"state": "composition", "key": "Ctrl+Nope", "command": "commit"},
+                        {"state": "composition", "key": "Ctrl+J", "command": "to_hiragana"}
+                    ]
+                },
+                "app_rules": [{"app": "notepad.exe", "live_conversion": "yes"}, 3]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(!config.zenzai.enable);
+        assert_eq!(config.candidate_window.page_size, 9);
+        assert!(config.input.live_conversion);
+        assert!(!config.input.remember_mode);
+        assert_eq!(config.input.style, InputStyle::Kana);
+        assert_eq!(config.keymap.preset, keymap::KeymapPreset::Atok);
+        assert_eq!(
+            config.keymap.overrides,
+            vec![KeyBinding {
+                state: KeymapState::Composition,
+                key: KeyStroke::parse("Ctrl+J").unwrap(),
+                command: KeyCommand::ToHiragana,
+            }]
+        );
+        assert_eq!(config.app_rules.len(), 1);
+        assert_eq!(config.app_rules[0].app, "notepad.exe");
+        assert_eq!(config.app_rules[0].live_conversion, None);
+    }
+
+    #[test]
+    fn broken_json_is_an_error() {
+        let error = AppConfig::parse(r#"{"version": 1,"#).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
     }
 }
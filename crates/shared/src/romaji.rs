@@ -0,0 +1,479 @@
+// table driven romaji to kana converter
+// the rules are (input, output, next), next is kept as the pending input after the output
+// e.g. "kk" -> ("っ", "k"), the same as the romaji table of Mozc and Google Japanese Input
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::Path,
+};
+
+const DEFAULT_TABLE: &[(&str, &str)] = &[
+    ("a", "あ"),
+    ("i", "い"),
+    ("u", "う"),
+    ("e", "え"),
+    ("o", "お"),
+    ("ka", "か"),
+    ("ki", "き"),
+    ("ku", "く"),
+    ("ke", "け"),
+    ("ko", "こ"),
+    ("ca", "か"),
+    ("cu", "く"),
+    ("co", "こ"),
+    ("qa", "くぁ"),
+    ("qi", "くぃ"),
+    ("qu", "く"),
+    ("qe", "くぇ"),
+    ("qo", "くぉ"),
+    ("kya", "きゃ"),
+    ("kyi", "きぃ"),
+    ("kyu", "きゅ"),
+    ("kye", "きぇ"),
+    ("kyo", "きょ"),
+    ("ga", "が"),
+    ("gi", "ぎ"),
+    ("gu", "ぐ"),
+    ("ge", "げ"),
+    ("go", "ご"),
+    ("gya", "ぎゃ"),
+    ("gyi", "ぎぃ"),
+    ("gyu", "ぎゅ"),
+    ("gye", "ぎぇ"),
+    ("gyo", "ぎょ"),
+    ("sa", "さ"),
+    ("si", "し"),
+    ("shi", "し"),
+    ("ci", "し"),
+    ("su", "す"),
+    ("se", "せ"),
+    ("ce", "せ"),
+    ("so", "そ"),
+    ("sha", "しゃ"),
+    ("shu", "しゅ"),
+    ("she", "しぇ"),
+    ("sho", "しょ"),
+    ("sya", "しゃ"),
+    ("syi", "しぃ"),
+    ("syu", "しゅ"),
+    ("sye", "しぇ"),
+    ("syo", "しょ"),
+    ("za", "ざ"),
+    ("zi", "じ"),
+    ("zu", "ず"),
+    ("ze", "ぜ"),
+    ("zo", "ぞ"),
+    ("ja", "じゃ"),
+    ("ji", "じ"),
+    ("ju", "じゅ"),
+    ("je", "じぇ"),
+    ("jo", "じょ"),
+    ("zya", "じゃ"),
+    ("zyi", "じぃ"),
+    ("zyu", "じゅ"),
+    ("zye", "じぇ"),
+    ("zyo", "じょ"),
+    ("jya", "じゃ"),
+    ("jyi", "じぃ"),
+    ("jyu", "じゅ"),
+    ("jye", "じぇ"),
+    ("jyo", "じょ"),
+    ("ta", "た"),
+    ("ti", "ち"),
+    ("chi", "ち"),
+    ("tu", "つ"),
+    ("tsu", "つ"),
+    ("te", "て"),
+    ("to", "と"),
+    ("cha", "ちゃ"),
+    ("chu", "ちゅ"),
+    ("che", "ちぇ"),
+    ("cho", "ちょ"),
+    ("tya", "ちゃ"),
+    ("tyi", "ちぃ"),
+    ("tyu", "ちゅ"),
+    ("tye", "ちぇ"),
+    ("tyo", "ちょ"),
+    ("cya", "ちゃ"),
+    ("cyi", "ちぃ"),
+    ("cyu", "ちゅ"),
+    ("cye", "ちぇ"),
+    ("cyo", "ちょ"),
+    ("tsa", "つぁ"),
+    ("tsi", "つぃ"),
+    ("tse", "つぇ"),
+    ("tso", "つぉ"),
+    ("tha", "てゃ"),
+    ("thi", "てぃ"),
+    ("thu", "てゅ"),
+    ("the", "てぇ"),
+    ("tho", "てょ"),
+    ("twu", "とぅ"),
+    ("da", "だ"),
+    ("di", "ぢ"),
+    ("du", "づ"),
+    ("de", "で"),
+    ("do", "ど"),
+    ("dya", "ぢゃ"),
+    ("dyi", "ぢぃ"),
+    ("dyu", "ぢゅ"),
+    ("dye", "ぢぇ"),
+    ("dyo", "ぢょ"),
+    ("dha", "でゃ"),
+    ("dhi", "でぃ"),
+    ("dhu", "でゅ"),
+    ("dhe", "でぇ"),
+    ("dho", "でょ"),
+    ("dwu", "どぅ"),
+    ("na", "な"),
+    ("ni", "に"),
+    ("nu", "ぬ"),
+    ("ne", "ね"),
+    ("no", "の"),
+    ("nya", "にゃ"),
+    ("nyi", "にぃ"),
+    ("nyu", "にゅ"),
+    ("nye", "にぇ"),
+    ("nyo", "にょ"),
+    ("n", "ん"),
+    ("nn", "ん"),
+    ("n'", "ん"),
+    ("xn", "ん"),
+    ("ha", "は"),
+    ("hi", "ひ"),
+    ("hu", "ふ"),
+    ("fu", "ふ"),
+    ("he", "へ"),
+    ("ho", "ほ"),
+    ("hya", "ひゃ"),
+    ("hyi", "ひぃ"),
+    ("hyu", "ひゅ"),
+    ("hye", "ひぇ"),
+    ("hyo", "ひょ"),
+    ("fa", "ふぁ"),
+    ("fi", "ふぃ"),
+    ("fe", "ふぇ"),
+    ("fo", "ふぉ"),
+    ("fya", "ふゃ"),
+    ("fyu", "ふゅ"),
+    ("fyo", "ふょ"),
+    ("ba", "ば"),
+    ("bi", "び"),
+    ("bu", "ぶ"),
+    ("be", "べ"),
+    ("bo", "ぼ"),
+    ("bya", "びゃ"),
+    ("byi", "びぃ"),
+    ("byu", "びゅ"),
+    ("bye", "びぇ"),
+    ("byo", "びょ"),
+    ("pa", "ぱ"),
+    ("pi", "ぴ"),
+    ("pu", "ぷ"),
+    ("pe", "ぺ"),
+    ("po", "ぽ"),
+    ("pya", "ぴゃ"),
+    ("pyi", "ぴぃ"),
+    ("pyu", "ぴゅ"),
+    ("pye", "ぴぇ"),
+    ("pyo", "ぴょ"),
+    ("ma", "ま"),
+    ("mi", "み"),
+    ("mu", "む"),
+    ("me", "め"),
+    ("mo", "も"),
+    ("mya", "みゃ"),
+    ("myi", "みぃ"),
+    ("myu", "みゅ"),
+    ("mye", "みぇ"),
+    ("myo", "みょ"),
+    ("ya", "や"),
+    ("yu", "ゆ"),
+    ("ye", "いぇ"),
+    ("yo", "よ"),
+    ("ra", "ら"),
+    ("ri", "り"),
+    ("ru", "る"),
+    ("re", "れ"),
+    ("ro", "ろ"),
+    ("rya", "りゃ"),
+    ("ryi", "りぃ"),
+    ("ryu", "りゅ"),
+    ("rye", "りぇ"),
+    ("ryo", "りょ"),
+    ("wa", "わ"),
+    ("wi", "うぃ"),
+    ("wu", "う"),
+    ("we", "うぇ"),
+    ("wo", "を"),
+    ("wha", "うぁ"),
+    ("whi", "うぃ"),
+    ("whu", "う"),
+    ("whe", "うぇ"),
+    ("who", "うぉ"),
+    ("wyi", "ゐ"),
+    ("wye", "ゑ"),
+    ("va", "ゔぁ"),
+    ("vi", "ゔぃ"),
+    ("vu", "ゔ"),
+    ("ve", "ゔぇ"),
+    ("vo", "ゔぉ"),
+    ("vya", "ゔゃ"),
+    ("vyu", "ゔゅ"),
+    ("vyo", "ゔょ"),
+    ("xa", "ぁ"),
+    ("xi", "ぃ"),
+    ("xu", "ぅ"),
+    ("xe", "ぇ"),
+    ("xo", "ぉ"),
+    ("la", "ぁ"),
+    ("li", "ぃ"),
+    ("lu", "ぅ"),
+    ("le", "ぇ"),
+    ("lo", "ぉ"),
+    ("xya", "ゃ"),
+    ("xyu", "ゅ"),
+    ("xyo", "ょ"),
+    ("lya", "ゃ"),
+    ("lyu", "ゅ"),
+    ("lyo", "ょ"),
+    ("xtu", "っ"),
+    ("xtsu", "っ"),
+    ("ltu", "っ"),
+    ("ltsu", "っ"),
+    ("xwa", "ゎ"),
+    ("lwa", "ゎ"),
+    ("xka", "ゕ"),
+    ("xke", "ゖ"),
+    ("lka", "ゕ"),
+    ("lke", "ゖ"),
+    ("-", "ー"),
+    (",", "、"),
+    (".", "。"),
+    ("[", "「"),
+    ("]", "」"),
+    ("/", "・"),
+    ("~", "〜"),
+    ("z/", "・"),
+    ("z.", "…"),
+    ("z,", "‥"),
+    ("z-", "〜"),
+    ("z[", "『"),
+    ("z]", "』"),
+    ("zh", "←"),
+    ("zj", "↓"),
+    ("zk", "↑"),
+    ("zl", "→"),
+];
+
+// consonants which become "っ" when they are doubled
+const DOUBLE_CONSONANTS: &str = "bcdfghjklmpqrstvwxyz";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    output: String,
+    next: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RomajiTable {
+    rules: HashMap<String, Rule>,
+    // proper prefixes of the inputs, to know whether the next key should be waited for
+    prefixes: HashSet<String>,
+}
+
+impl Default for RomajiTable {
+    fn default() -> Self {
+        let mut table = RomajiTable::empty();
+
+        for (input, output) in DEFAULT_TABLE {
+            table.insert(input, output, "");
+        }
+        for c in DOUBLE_CONSONANTS.chars() {
+            table.insert(&format!("{}{}", c, c), "っ", &c.to_string());
+        }
+
+        table
+    }
+}
+
+impl RomajiTable {
+    pub fn empty() -> Self {
+        RomajiTable {
+            rules: HashMap::new(),
+            prefixes: HashSet::new(),
+        }
+    }
+
+    // the default table extended with the rules of the file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        let mut table = RomajiTable::default();
+        table.extend(&text);
+        Ok(table)
+    }
+
+    // each line is "input<TAB>output" or "input<TAB>output<TAB>next"
+    pub fn extend(&mut self, text: &str) {
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut columns = line.split('\t');
+            if let (Some(input), Some(output)) = (columns.next(), columns.next()) {
+                if !input.is_empty() {
+                    self.insert(input, output, columns.next().unwrap_or(""));
+                }
+            }
+        }
+    }
+
+    pub fn insert(&mut self, input: &str, output: &str, next: &str) {
+        for (index, _) in input.char_indices().skip(1) {
+            self.prefixes.insert(input[..index].to_string());
+        }
+
+        self.rules.insert(
+            input.to_string(),
+            Rule {
+                output: output.to_string(),
+                next: next.to_string(),
+            },
+        );
+    }
+
+    // converts the pending input followed by a character
+    // returns the converted text and the new pending input
+    pub fn feed(&self, pending: &str, c: char) -> (String, String) {
+        let input = format!("{}{}", pending, c);
+
+        // wait for the next key, e.g. "n" of "na"
+        if self.prefixes.contains(&input) {
+            return (String::new(), input);
+        }
+        if let Some(rule) = self.rules.get(&input) {
+            return (rule.output.clone(), rule.next.clone());
+        }
+
+        // the pending input doesn't continue, e.g. "n" of "nk"
+        let mut output = self.flush(pending);
+
+        let input = c.to_string();
+        if self.prefixes.contains(&input) {
+            return (output, input);
+        }
+        match self.rules.get(&input) {
+            Some(rule) => {
+                output.push_str(&rule.output);
+                (output, rule.next.clone())
+            }
+            None => {
+                output.push(c);
+                (output, String::new())
+            }
+        }
+    }
+
+    // converts the pending input without waiting for the next key
+    // it is left as it is if there is no rule for it
+    pub fn flush(&self, pending: &str) -> String {
+        match self.rules.get(pending) {
+            Some(rule) => format!("{}{}", rule.output, rule.next),
+            None => pending.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // feeds the keys one by one, and returns the converted text and the pending input
+    fn convert(table: &RomajiTable, keys: &str) -> (String, String) {
+        let mut output = String::new();
+        let mut pending = String::new();
+        for c in keys.chars() {
+            let (converted, next) = table.feed(&pending, c);
+            output.push_str(&converted);
+            pending = next;
+        }
+        (output, pending)
+    }
+
+    fn converted(output: &str, pending: &str) -> (String, String) {
+        (output.to_string(), pending.to_string())
+    }
+
+    #[test]
+    fn n_before_a_consonant() {
+        let table = RomajiTable::default();
+        assert_eq!(convert(&table, "kanji"), converted("かんじ", ""));
+        assert_eq!(convert(&table, "nk"), converted("ん", "k"));
+        // "n" waits for the next key, since it may be "na" or "nya"
+        assert_eq!(convert(&table, "hon"), converted("ほ", "n"));
+        assert_eq!(convert(&table, "ny"), converted("", "ny"));
+        assert_eq!(convert(&table, "nya"), converted("にゃ", ""));
+    }
+
+    #[test]
+    fn nn_and_n_apostrophe() {
+        let table = RomajiTable::default();
+        assert_eq!(convert(&table, "konnnichiha"), converted("こんにちは", ""));
+        assert_eq!(convert(&table, "nn"), converted("ん", ""));
+        assert_eq!(convert(&table, "kan'i"), converted("かんい", ""));
+    }
+
+    #[test]
+    fn doubled_consonants() {
+        let table = RomajiTable::default();
+        // "kk" gives "っ" and keeps "k" as the next pending input
+        assert_eq!(table.feed("k", 'k'), converted("っ", "k"));
+        assert_eq!(convert(&table, "kitte"), converted("きって", ""));
+        assert_eq!(convert(&table, "zasshi"), converted("ざっし", ""));
+        assert_eq!(convert(&table, "tt"), converted("っ", "t"));
+    }
+
+    #[test]
+    fn small_tsu() {
+        let table = RomajiTable::default();
+        assert_eq!(convert(&table, "xtu"), converted("っ", ""));
+        assert_eq!(convert(&table, "ltsu"), converted("っ", ""));
+        assert_eq!(convert(&table, "xtultsu"), converted("っっ", ""));
+    }
+
+    #[test]
+    fn flush_dangling_prefix() {
+        let table = RomajiTable::default();
+        assert_eq!(table.flush("n"), "ん");
+        assert_eq!(table.flush(""), "");
+        // the prefix without a rule is left as it is
+        assert_eq!(table.flush("ky"), "ky");
+        assert_eq!(table.flush("lts"), "lts");
+    }
+
+    #[test]
+    fn unknown_keys_pass_through() {
+        let table = RomajiTable::default();
+        assert_eq!(convert(&table, "q1"), converted("q1", ""));
+        assert_eq!(convert(&table, "kyo-"), converted("きょー", ""));
+    }
+
+    #[test]
+    fn user_rules() {
+        let mut table = RomajiTable::default();
+        table.extend("# comment\r\n;\tっ\nkz\tかん\nka\tカ\nwk\tわ\tk\n\nss\n");
+
+        // a new rule, and a new rule which shares its prefix with the default ones
+        assert_eq!(convert(&table, ";"), converted("っ", ""));
+        assert_eq!(convert(&table, "kz"), converted("かん", ""));
+        // the default rule is overridden
+        assert_eq!(convert(&table, "ka"), converted("カ", ""));
+        // the third column is kept as the next pending input
+        assert_eq!(convert(&table, "wka"), converted("わカ", ""));
+        // the line without an output is skipped
+        assert_eq!(convert(&table, "ss"), converted("っ", "s"));
+    }
+}
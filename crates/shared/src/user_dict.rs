@@ -0,0 +1,470 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::get_config_root;
+
+const USER_DICT_FILENAME: &str = "user_dict.json";
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PartOfSpeech {
+    #[default]
+    Noun,
+    ProperNoun,
+    PersonName,
+    Surname,
+    GivenName,
+    Organization,
+    PlaceName,
+    SuruNoun,
+    Adjective,
+    Adverb,
+    Symbol,
+    Emoticon,
+    Abbreviation,
+}
+
+impl PartOfSpeech {
+    pub const ALL: [PartOfSpeech; 13] = [
+        PartOfSpeech::Noun,
+        PartOfSpeech::ProperNoun,
+        PartOfSpeech::PersonName,
+        PartOfSpeech::Surname,
+        PartOfSpeech::GivenName,
+        PartOfSpeech::Organization,
+        PartOfSpeech::PlaceName,
+        PartOfSpeech::SuruNoun,
+        PartOfSpeech::Adjective,
+        PartOfSpeech::Adverb,
+        PartOfSpeech::Symbol,
+        PartOfSpeech::Emoticon,
+        PartOfSpeech::Abbreviation,
+    ];
+
+    // accepts the names used by google japanese input, mozc and ms-ime
+    // None for the unknown names, so that the import can skip the line
+    pub fn from_name(name: &str) -> Option<Self> {
+        let pos = match name.trim() {
+            "名詞" | "普通名詞" => PartOfSpeech::Noun,
+            "固有名詞" => PartOfSpeech::ProperNoun,
+            "人名" => PartOfSpeech::PersonName,
+            "姓" | "姓のみ" => PartOfSpeech::Surname,
+            "名" | "名のみ" => PartOfSpeech::GivenName,
+            "組織" | "組織名" => PartOfSpeech::Organization,
+            "地名" | "地名その他" => PartOfSpeech::PlaceName,
+            "名詞サ変" | "名詞ｻ変" | "さ変名詞" => PartOfSpeech::SuruNoun,
+            "形容詞" => PartOfSpeech::Adjective,
+            "副詞" => PartOfSpeech::Adverb,
+            "記号" => PartOfSpeech::Symbol,
+            "顔文字" => PartOfSpeech::Emoticon,
+            "短縮よみ" => PartOfSpeech::Abbreviation,
+            _ => return None,
+        };
+        Some(pos)
+    }
+
+    // name used by google japanese input and mozc
+    pub fn google_name(&self) -> &'static str {
+        match self {
+            PartOfSpeech::Noun => "名詞",
+            PartOfSpeech::ProperNoun => "固有名詞",
+            PartOfSpeech::PersonName => "人名",
+            PartOfSpeech::Surname => "姓",
+            PartOfSpeech::GivenName => "名",
+            PartOfSpeech::Organization => "組織",
+            PartOfSpeech::PlaceName => "地名",
+            PartOfSpeech::SuruNoun => "名詞サ変",
+            PartOfSpeech::Adjective => "形容詞",
+            PartOfSpeech::Adverb => "副詞",
+            PartOfSpeech::Symbol => "記号",
+            PartOfSpeech::Emoticon => "顔文字",
+            PartOfSpeech::Abbreviation => "短縮よみ",
+        }
+    }
+
+    // name used by the ms-ime dictionary tool
+    pub fn ms_ime_name(&self) -> &'static str {
+        match self {
+            PartOfSpeech::Noun => "名詞",
+            PartOfSpeech::ProperNoun => "固有名詞",
+            PartOfSpeech::PersonName => "人名",
+            PartOfSpeech::Surname => "姓",
+            PartOfSpeech::GivenName => "名",
+            PartOfSpeech::Organization => "固有名詞",
+            PartOfSpeech::PlaceName => "地名",
+            PartOfSpeech::SuruNoun => "さ変名詞",
+            PartOfSpeech::Adjective => "形容詞",
+            PartOfSpeech::Adverb => "副詞",
+            PartOfSpeech::Symbol => "記号",
+            PartOfSpeech::Emoticon => "顔文字",
+            PartOfSpeech::Abbreviation => "短縮よみ",
+        }
+    }
+
+    // identifier used in the config file and over grpc
+    pub fn id(&self) -> &'static str {
+        match self {
+            PartOfSpeech::Noun => "noun",
+            PartOfSpeech::ProperNoun => "proper_noun",
+            PartOfSpeech::PersonName => "person_name",
+            PartOfSpeech::Surname => "surname",
+            PartOfSpeech::GivenName => "given_name",
+            PartOfSpeech::Organization => "organization",
+            PartOfSpeech::PlaceName => "place_name",
+            PartOfSpeech::SuruNoun => "suru_noun",
+            PartOfSpeech::Adjective => "adjective",
+            PartOfSpeech::Adverb => "adverb",
+            PartOfSpeech::Symbol => "symbol",
+            PartOfSpeech::Emoticon => "emoticon",
+            PartOfSpeech::Abbreviation => "abbreviation",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Self {
+        PartOfSpeech::ALL
+            .into_iter()
+            .find(|pos| pos.id() == id)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct UserDictEntry {
+    pub reading: String,
+    pub surface: String,
+    #[serde(default)]
+    pub pos: PartOfSpeech,
+    #[serde(default)]
+    pub comment: String,
+}
+
+impl From<crate::proto::UserDictEntry> for UserDictEntry {
+    fn from(entry: crate::proto::UserDictEntry) -> Self {
+        UserDictEntry {
+            reading: entry.reading,
+            surface: entry.surface,
+            pos: PartOfSpeech::from_id(&entry.pos),
+            comment: entry.comment,
+        }
+    }
+}
+
+impl From<UserDictEntry> for crate::proto::UserDictEntry {
+    fn from(entry: UserDictEntry) -> Self {
+        crate::proto::UserDictEntry {
+            reading: entry.reading,
+            surface: entry.surface,
+            pos: entry.pos.id().to_string(),
+            comment: entry.comment,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DictFormat {
+    // google japanese input: utf-8, reading\tsurface\tpos\tcomment
+    Google,
+    // ms-ime dictionary tool: utf-16le with a bom and "!" header lines
+    MsIme,
+    // mozc: same layout as google japanese input
+    Mozc,
+}
+
+// guess the format from the content, ms-ime files always start with a "!Microsoft IME" header
+pub fn detect_format(bytes: &[u8]) -> io::Result<DictFormat> {
+    let text = decode(bytes)?;
+    if text
+        .lines()
+        .take(5)
+        .any(|line| line.starts_with("!Microsoft IME"))
+    {
+        Ok(DictFormat::MsIme)
+    } else {
+        Ok(DictFormat::Google)
+    }
+}
+
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> io::Result<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "odd length for a utf-16 file",
+        ));
+    }
+
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| {
+            if little_endian {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect::<Vec<_>>();
+
+    String::from_utf16(&units).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn decode(bytes: &[u8]) -> io::Result<String> {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => decode_utf16(rest, true),
+        [0xFE, 0xFF, rest @ ..] => decode_utf16(rest, false),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8(rest.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        // utf-16le without a bom, ascii characters leave a zero in every odd byte
+        [_, 0x00, ..] => decode_utf16(bytes, true),
+        _ => String::from_utf8(bytes.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ParsedDict {
+    pub entries: Vec<UserDictEntry>,
+    // 1-based line numbers of the entries with an unknown pos, e.g. verbs
+    pub skipped_lines: Vec<usize>,
+}
+
+// all three formats share the reading\tsurface\tpos\tcomment column layout
+// the entry without the pos is a noun, and the entry with an unknown pos is skipped
+pub fn parse(bytes: &[u8]) -> io::Result<ParsedDict> {
+    let text = decode(bytes)?;
+    let mut parsed = ParsedDict::default();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+
+        // skip comments and the ms-ime header
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+
+        let mut columns = line.split('\t');
+        let reading = columns.next().unwrap_or_default().trim();
+        let surface = columns.next().unwrap_or_default().trim();
+        if reading.is_empty() || surface.is_empty() {
+            continue;
+        }
+
+        let pos = match columns.next().unwrap_or_default() {
+            name if name.trim().is_empty() => PartOfSpeech::Noun,
+            name => match PartOfSpeech::from_name(name) {
+                Some(pos) => pos,
+                None => {
+                    parsed.skipped_lines.push(index + 1);
+                    continue;
+                }
+            },
+        };
+        let comment = columns.next().unwrap_or_default().trim().to_string();
+
+        parsed.entries.push(UserDictEntry {
+            reading: reading.to_string(),
+            surface: surface.to_string(),
+            pos,
+            comment,
+        });
+    }
+
+    Ok(parsed)
+}
+
+pub fn serialize(entries: &[UserDictEntry], format: DictFormat) -> Vec<u8> {
+    match format {
+        DictFormat::MsIme => {
+            let mut text =
+                String::from("!Microsoft IME Dictionary Tool\r\n!Format:WORDLIST\r\n\r\n");
+            for entry in entries {
+                text.push_str(&format!(
+                    "{}\t{}\t{}\t{}\r\n",
+                    entry.reading,
+                    entry.surface,
+                    entry.pos.ms_ime_name(),
+                    entry.comment
+                ));
+            }
+
+            let mut bytes = vec![0xFF, 0xFE];
+            for unit in text.encode_utf16() {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+            bytes
+        }
+        DictFormat::Google | DictFormat::Mozc => {
+            let mut text = String::new();
+            for entry in entries {
+                text.push_str(&format!(
+                    "{}\t{}\t{}\t{}\n",
+                    entry.reading,
+                    entry.surface,
+                    entry.pos.google_name(),
+                    entry.comment
+                ));
+            }
+            text.into_bytes()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct UserDictionary {
+    pub entries: Vec<UserDictEntry>,
+}
+
+impl UserDictionary {
+    pub fn read() -> io::Result<Self> {
+        let dict_path = get_config_root().join(USER_DICT_FILENAME);
+        if !dict_path.exists() {
+            return Ok(UserDictionary::default());
+        }
+        let dict_str = std::fs::read_to_string(dict_path)?;
+        serde_json::from_str(&dict_str).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // write to a temporary file and replace, like AppConfig::write
+    pub fn write(&self) -> io::Result<()> {
+        let config_root = get_config_root();
+        if !config_root.exists() {
+            std::fs::create_dir_all(&config_root)?;
+        }
+
+        let dict_path = config_root.join(USER_DICT_FILENAME);
+        let temp_path = dict_path.with_extension("json.tmp");
+        let dict_str = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        std::fs::write(&temp_path, dict_str)?;
+        std::fs::rename(&temp_path, &dict_path)
+    }
+
+    // entries are identified by reading and surface, adding an existing pair updates it
+    // returns true if a new entry was added
+    pub fn add(&mut self, entry: UserDictEntry) -> bool {
+        if entry.reading.is_empty() || entry.surface.is_empty() {
+            return false;
+        }
+
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.reading == entry.reading && e.surface == entry.surface)
+        {
+            Some(existing) => {
+                *existing = entry;
+                false
+            }
+            None => {
+                self.entries.push(entry);
+                true
+            }
+        }
+    }
+
+    // returns true if the entry was found
+    pub fn remove(&mut self, reading: &str, surface: &str) -> bool {
+        let len = self.entries.len();
+        self.entries
+            .retain(|e| !(e.reading == reading && e.surface == surface));
+        self.entries.len() != len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(reading: &str, surface: &str, pos: PartOfSpeech, comment: &str) -> UserDictEntry {
+        UserDictEntry {
+            reading: reading.to_string(),
+            surface: surface.to_string(),
+            pos,
+            comment: comment.to_string(),
+        }
+    }
+
+    // an entry of each pos, with and without the comment
+    fn entries() -> Vec<UserDictEntry> {
+        PartOfSpeech::ALL
+            .into_iter()
+            .enumerate()
+            .map(|(index, pos)| {
+                let comment = if index % 2 == 0 { "" } else { "メモ" };
+                entry(
+                    &format!("よみ{}", index),
+                    &format!("単語{}", index),
+                    pos,
+                    comment,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn google_and_mozc_round_trip() {
+        for format in [DictFormat::Google, DictFormat::Mozc] {
+            let bytes = serialize(&entries(), format);
+            assert_eq!(detect_format(&bytes).unwrap(), DictFormat::Google);
+            assert_eq!(parse(&bytes).unwrap().entries, entries());
+        }
+    }
+
+    #[test]
+    fn ms_ime_round_trip() {
+        let bytes = serialize(&entries(), DictFormat::MsIme);
+        assert_eq!(&bytes[..2], &[0xFF, 0xFE]);
+        assert_eq!(detect_format(&bytes).unwrap(), DictFormat::MsIme);
+
+        // ms-ime has no organization, it is exported as a proper noun
+        let expected: Vec<UserDictEntry> = entries()
+            .into_iter()
+            .map(|mut entry| {
+                if entry.pos == PartOfSpeech::Organization {
+                    entry.pos = PartOfSpeech::ProperNoun;
+                }
+                entry
+            })
+            .collect();
+        assert_eq!(parse(&bytes).unwrap().entries, expected);
+    }
+
+    #[test]
+    fn comments_and_incomplete_lines_are_skipped() {
+        let text = "# comment\n\nよみ\n\t単語\nかな\t仮名\r\nあい\t愛\t\t\n";
+        assert_eq!(
+            parse(text.as_bytes()).unwrap().entries,
+            vec![
+                entry("かな", "仮名", PartOfSpeech::Noun, ""),
+                entry("あい", "愛", PartOfSpeech::Noun, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_pos_is_skipped() {
+        let text = "かな\t仮名\t名詞\nうごく\t動く\t動詞\nはしる\t走る\t動詞\nにほん\t日本\t地名\n";
+        let parsed = parse(text.as_bytes()).unwrap();
+        assert_eq!(
+            parsed.entries,
+            vec![
+                entry("かな", "仮名", PartOfSpeech::Noun, ""),
+                entry("にほん", "日本", PartOfSpeech::PlaceName, ""),
+            ]
+        );
+        assert_eq!(parsed.skipped_lines, vec![2, 3]);
+    }
+
+    #[test]
+    fn utf16_without_a_bom() {
+        // the ascii characters leave a zero in every odd byte
+        let bytes: Vec<u8> = "a\tA\t名詞\t\r\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        assert_eq!(
+            parse(&bytes).unwrap().entries,
+            vec![entry("a", "A", PartOfSpeech::Noun, "")]
+        );
+    }
+}
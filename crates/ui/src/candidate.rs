@@ -42,9 +42,11 @@ pub fn create_candidate_window(event_loop: &EventLoop<UserEvent>) -> Result<Wind
     Ok(window)
 }
 
-pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
+// the page size is given before the page is loaded, since the window height depends on it
+pub fn create_candidate_webview<'a>(page_size: usize) -> Result<WebViewBuilder<'a>> {
     let webview_builder = WebViewBuilder::new()
     .with_transparent(true)
+    .with_initialization_script(&format!("window.pageSize = {};", page_size))
     .with_html(
         r##"
         <html>
@@ -74,7 +76,6 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                         padding: 0;
                         flex: 1;
                         overflow-y: auto;
-                        scroll-snap-type: y proximity;
                         list-style-position: inside;
                         list-style-type: none;
                         counter-reset: number 0;
@@ -95,7 +96,6 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                         font-size: 0.9rem;
                         display: flex;
                         align-items: center;
-                        scroll-snap-align: start;
 
                         &::before {
                             content: counter(number);
@@ -114,6 +114,41 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                             outline: 1px solid #2CB5FF;
                             outline-offset: -1px;
                         }
+
+                        & .surface {
+                            flex: 1;
+                        }
+
+                        & .annotation {
+                            margin-left: 0.75rem;
+                            color: #838384;
+                            font-size: 0.7rem;
+                            white-space: nowrap;
+                        }
+                    }
+                    #prediction-list {
+                        display: flex;
+                        flex-wrap: wrap;
+                        gap: 0.25rem 0.75rem;
+                        align-items: center;
+                        padding: 6px 10px;
+                        border-top: 1px solid #E4E4E4;
+                        font-size: 0.8rem;
+                        user-select: none;
+
+                        &:empty {
+                            display: none;
+                        }
+
+                        & span:first-child::after {
+                            content: "Tab";
+                            margin-left: 0.4rem;
+                            padding: 0 0.3rem;
+                            border: 1px solid #BCBCBC;
+                            border-radius: 3px;
+                            color: #636363;
+                            font-size: 0.65rem;
+                        }
                     }
                     footer {
                         display: flex;
@@ -124,6 +159,10 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                         font-size: 0.8rem;
                         user-select: none;
                     }
+                    #page-indicator {
+                        color: #838384;
+                        font-variant-numeric: tabular-nums;
+                    }
 
                     @media (prefers-color-scheme: dark) {
                         body {
@@ -143,67 +182,117 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                                 color: #BDBDBD;
                             }
 
+                            & .annotation {
+                                color: #9E9E9E;
+                            }
+
                             &[data-selected] {
                                 background-color: #3949AB;
                                 outline: 1px solid #5C6BC0;
                             }
                         }
                             
+                        #prediction-list {
+                            border-top: 1px solid #424242;
+
+                            & span:first-child::after {
+                                border-color: #757575;
+                                color: #BDBDBD;
+                            }
+                        }
                         footer {
                             border-top: 1px solid #424242;
                         }
                     }
                 </style>
                 <script>
-                    function updateCandidates(candidates) {
+                    // the source is shown instead when the candidate has no annotation
+                    const sourceLabels = {
+                        user: 'ユーザー辞書',
+                        learned: '学習',
+                        emoji: '絵文字',
+                        symbol: '記号',
+                    };
+
+                    function renderCandidate(li, candidate) {
+                        const surface = document.createElement('span');
+                        surface.className = 'surface';
+                        surface.textContent = candidate.surface;
+
+                        const annotation = document.createElement('span');
+                        annotation.className = 'annotation';
+                        annotation.textContent = candidate.annotation || sourceLabels[candidate.source] || '';
+
+                        li.title = candidate.reading;
+                        li.replaceChildren(surface, annotation);
+                    }
+
+                    // the candidates are shown by pages, and the number keys select from the shown page
+                    // the page size is given by the settings, see create_candidate_webview
+                    let pageSize = window.pageSize || 9;
+                    let candidates = [];
+                    let selectedIndex = 0;
+
+                    function renderPage() {
                         const candidateList = document.getElementById('candidate-list');
+                        const pageStart = Math.floor(selectedIndex / pageSize) * pageSize;
+                        const page = candidates.slice(pageStart, pageStart + pageSize);
 
                         const existingItems = Array.from(candidateList.children);
 
-                        candidates.forEach((candidate, index) => {
+                        page.forEach((candidate, index) => {
                             if (existingItems[index]) {
-                                existingItems[index].textContent = candidate;
+                                renderCandidate(existingItems[index], candidate);
                             } else {
                                 const li = document.createElement('li');
-                                li.textContent = candidate;
+                                renderCandidate(li, candidate);
                                 candidateList.appendChild(li);
                             }
                         });
 
-                        while (existingItems.length > candidates.length) {
+                        while (existingItems.length > page.length) {
                             candidateList.removeChild(existingItems.pop());
                         }
+
+                        Array.from(candidateList.children).forEach((li, index) => {
+                            if (pageStart + index === selectedIndex) {
+                                li.setAttribute('data-selected', '');
+                            } else {
+                                li.removeAttribute('data-selected');
+                            }
+                        });
+
+                        const indicator = document.getElementById('page-indicator');
+                        indicator.textContent = candidates.length > 0 ? `${selectedIndex + 1} / ${candidates.length}` : '';
+                    }
+
+                    function updateCandidates(newCandidates) {
+                        candidates = newCandidates;
+                        selectedIndex = 0;
+                        renderPage();
+                    }
+
+                    // the predictions are shown under the candidates, and the first one is accepted with Tab
+                    function updatePredictions(predictions) {
+                        const predictionList = document.getElementById('prediction-list');
+                        predictionList.replaceChildren(...predictions.map((prediction) => {
+                            const span = document.createElement('span');
+                            span.textContent = prediction;
+                            return span;
+                        }));
                     }
 
                     function updateSelection(index) {
-                        const candidateList = document.getElementById('candidate-list');
-                        const selected = candidateList.querySelector('[data-selected]');
-                        if (selected) {
-                            selected.removeAttribute('data-selected');
-                        }
-                        
-                        candidateList.children[index].setAttribute('data-selected', '');
-                        
-                        const itemHeight = candidateList.children[0].offsetHeight;
-                        const visibleItems = Math.floor(candidateList.clientHeight / itemHeight);
-                        
-                        const groupSize = 5;
-                        const groupIndex = Math.floor(index / groupSize);
-                        const scrollToIndex = groupIndex * groupSize;
-                        
-                        if (index === scrollToIndex || !isElementInView(candidateList.children[index], candidateList)) {
-                            candidateList.children[scrollToIndex].scrollIntoView({ behavior: "instant", block: "start", inline: "start" });
-                        }
+                        selectedIndex = Math.min(Math.max(index, 0), Math.max(candidates.length - 1, 0));
+                        renderPage();
                     }
-                    
-                    function isElementInView(element, container) {
-                        const containerRect = container.getBoundingClientRect();
-                        const elementRect = element.getBoundingClientRect();
-                        
-                        return (
-                            elementRect.top >= containerRect.top &&
-                            elementRect.bottom <= containerRect.bottom
-                        );
+
+                    function updateConfig(config) {
+                        const size = Math.min(Math.max(config.candidate_window.page_size, 1), 9);
+                        if (size !== pageSize) {
+                            pageSize = size;
+                            adjustWindowSize();
+                        }
                     }
 
                     function adjustWindowSize() {
@@ -212,8 +301,8 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                         // Clear any existing items
                         candidateList.innerHTML = '';
                         
-                        // Add 5 test items to measure
-                        for (let i = 0; i < 5; i++) {
+                        // Add test items of a page to measure
+                        for (let i = 0; i < pageSize; i++) {
                             const li = document.createElement('li');
                             li.textContent = `Item ${i+1}`;
                             candidateList.appendChild(li);
@@ -227,8 +316,8 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                         // Get the height of a single item
                         const itemHeight = candidateList.children[0].offsetHeight;
                         
-                        // Calculate the height needed for exactly 5 items
-                        const candidateListHeight = itemHeight * 5;
+                        // Calculate the height needed for exactly one page
+                        const candidateListHeight = itemHeight * pageSize;
                         const footerHeight = footer.offsetHeight;
                         const mainPadding = parseInt(window.getComputedStyle(main).paddingTop) + 
                                            parseInt(window.getComputedStyle(main).paddingBottom);
@@ -238,8 +327,9 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                         // Calculate total window height needed
                         const totalHeight = candidateListHeight + footerHeight + mainPadding + bodyPadding;
                         
-                        // Clear the test items
+                        // Clear the test items and show the current page again
                         candidateList.innerHTML = '';
+                        renderPage();
                         
                         window.ipc.postMessage(JSON.stringify({
                             type: 'resize',
@@ -247,8 +337,39 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                         }));
                     }
 
-                    
+                    // the mouse operations are reported to the client, which updates the composition
+                    // the index is counted from the first candidate, not from the shown page
+                    function candidateIndex(event) {
+                        const candidateList = document.getElementById('candidate-list');
+                        const li = event.target.closest('li');
+                        if (!li || li.parentElement !== candidateList) {
+                            return -1;
+                        }
+                        const pageStart = Math.floor(selectedIndex / pageSize) * pageSize;
+                        const index = pageStart + Array.from(candidateList.children).indexOf(li);
+                        return index < candidates.length ? index : -1;
+                    }
+
+                    function watchMouse() {
+                        const candidateList = document.getElementById('candidate-list');
+
+                        candidateList.addEventListener('mouseover', (event) => {
+                            const index = candidateIndex(event);
+                            if (index >= 0 && index !== selectedIndex) {
+                                window.ipc.postMessage(JSON.stringify({ type: 'select', index }));
+                            }
+                        });
+
+                        candidateList.addEventListener('click', (event) => {
+                            const index = candidateIndex(event);
+                            if (index >= 0) {
+                                window.ipc.postMessage(JSON.stringify({ type: 'commit', index }));
+                            }
+                        });
+                    }
+
                     window.addEventListener('DOMContentLoaded', () => {
+                        watchMouse();
                         setTimeout(adjustWindowSize, 50); // Small delay to ensure rendering is complete
                     });
                 </script>
@@ -257,10 +378,12 @@ pub fn create_candidate_webview<'a>() -> Result<WebViewBuilder<'a>> {
                 <main>
                     <ol id="candidate-list">
                     </ol>
+                    <section id="prediction-list"></section>
                     <footer>
                         <svg width="20" height="14" viewBox="0 0 22 16" fill="none" xmlns="http://www.w3.org/2000/svg">
                             <path d="M3.5 8C4.59202 9.04403 7.54398 10.3978 13.5068 9.93754M1.25349 5.39919C2.77722 0.413397 8.08911 0.79692 10.9673 1.24436C14.2687 1.71311 20.8969 3.82675 20.9985 8.53129C21.1255 14.412 13.1894 15.3069 10.0784 14.9233C6.96748 14.5398 -0.46071 13.0696 1.25349 5.39919Z" stroke="#838384" stroke-width="1.5" stroke-linecap="round"/>
                         </svg>
+                        <span id="page-indicator"></span>
                     </footer>
                 </main>
             </body>
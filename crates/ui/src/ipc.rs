@@ -1,21 +1,45 @@
+use std::pin::Pin;
+
+use async_stream::stream;
+use futures_core::Stream;
 use shared::proto::{
-    window_service_server::WindowService as WindowServiceProto, EmptyResponse, SetCandidateRequest,
-    SetInputModeRequest, SetPositionRequest, SetSelectionRequest,
+    window_event::Event, window_service_server::WindowService as WindowServiceProto, EmptyResponse,
+    SetCandidateRequest, SetInputModeRequest, SetPositionRequest, SetPredictionsRequest,
+    SetSelectionRequest, WindowEvent,
 };
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tonic::{Request, Response, Status};
 
 #[derive(Debug, Clone)]
 pub struct WindowController {
     sender: mpsc::Sender<WindowAction>,
+    // the operations on the candidate window are sent to every client watching the events
+    events: broadcast::Sender<WindowEvent>,
 }
 
 impl WindowController {
     pub fn new(sender: mpsc::Sender<WindowAction>) -> Self {
-        Self { sender }
+        let (events, _) = broadcast::channel(32);
+        Self { sender, events }
+    }
+
+    // called from the ipc handler of the candidate webview
+    pub fn send_event(&self, event: Event) {
+        // there may be no client watching the events
+        let _ = self.events.send(WindowEvent { event: Some(event) });
     }
 }
 
+// 候補ウィンドウに表示する変換候補
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Candidate {
+    pub surface: String,
+    pub reading: String,
+    pub source: String,
+    pub annotation: String,
+    pub score: f32,
+}
+
 // ウィンドウ操作コマンド
 #[derive(Debug, serde::Serialize)]
 pub enum WindowAction {
@@ -31,7 +55,10 @@ pub enum WindowAction {
         index: i32,
     },
     SetCandidate {
-        candidates: Vec<String>,
+        candidates: Vec<Candidate>,
+    },
+    SetPredictions {
+        predictions: Vec<String>,
     },
     SetInputMode(String),
 }
@@ -43,6 +70,8 @@ pub struct WindowService {
 
 #[tonic::async_trait]
 impl WindowServiceProto for WindowService {
+    type WatchEventsStream = Pin<Box<dyn Stream<Item = Result<WindowEvent, Status>> + Send>>;
+
     async fn show_window(
         &self,
         _request: Request<EmptyResponse>,
@@ -93,13 +122,37 @@ impl WindowServiceProto for WindowService {
         &self,
         request: Request<SetCandidateRequest>,
     ) -> Result<Response<EmptyResponse>, Status> {
-        let candidate = request.into_inner().candidates;
+        let candidates = request
+            .into_inner()
+            .candidates
+            .into_iter()
+            .map(|c| Candidate {
+                surface: c.surface,
+                reading: c.reading,
+                source: c.source,
+                annotation: c.annotation,
+                score: c.score,
+            })
+            .collect();
 
         self.controller
             .sender
-            .send(WindowAction::SetCandidate {
-                candidates: candidate,
-            })
+            .send(WindowAction::SetCandidate { candidates })
+            .await
+            .unwrap();
+
+        Ok(Response::new(EmptyResponse {}))
+    }
+
+    async fn set_predictions(
+        &self,
+        request: Request<SetPredictionsRequest>,
+    ) -> Result<Response<EmptyResponse>, Status> {
+        let predictions = request.into_inner().predictions;
+
+        self.controller
+            .sender
+            .send(WindowAction::SetPredictions { predictions })
             .await
             .unwrap();
 
@@ -133,4 +186,23 @@ impl WindowServiceProto for WindowService {
 
         Ok(Response::new(EmptyResponse {}))
     }
+
+    async fn watch_events(
+        &self,
+        _request: Request<EmptyResponse>,
+    ) -> Result<Response<Self::WatchEventsStream>, Status> {
+        let mut receiver = self.controller.events.subscribe();
+        let events = stream! {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => yield Ok(event),
+                    // the old events are no longer meaningful
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        };
+
+        Ok(Response::new(Box::pin(events)))
+    }
 }
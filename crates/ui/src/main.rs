@@ -4,7 +4,10 @@ use std::sync::Arc;
 use anyhow::Context as _;
 use azookey_server::TonicNamedPipeServer;
 use ipc::{WindowAction, WindowController, WindowService};
+use shared::config_watcher::{self, ConfigWatcher};
+use shared::proto::window_event::Event as CandidateEvent;
 use shared::proto::window_service_server::WindowServiceServer;
+use shared::AppConfig;
 use tao::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
 use tao::platform::windows::{EventLoopBuilderExtWindows, WindowExtWindows};
 use tao::{
@@ -34,8 +37,10 @@ pub mod utils;
 pub enum UserEvent {
     UpdateHeight(i32),
     UpdateCandidates(String),
+    UpdatePredictions(String),
     UpdateSelection(i32),
     UpdateInputMethod(String),
+    UpdateConfig(String),
     WindowAction(WindowAction),
 }
 
@@ -69,8 +74,11 @@ async fn main() -> anyhow::Result<()> {
     let task_guard: Arc<Mutex<Option<JoinHandle<()>>>> = Arc::new(Mutex::new(None));
 
     let proxy_clone = event_loop_proxy.clone();
+    let controller_clone = window_controller.clone();
     let candidate_window = candidate::create_candidate_window(&event_loop)?;
-    let candidate_webview_builder = candidate::create_candidate_webview()?;
+    let config = AppConfig::read().unwrap_or_default();
+    let candidate_webview_builder =
+        candidate::create_candidate_webview(config.candidate_window.page_size())?;
     let candidate_webview = candidate_webview_builder
         .with_devtools(true)
         .with_ipc_handler(move |message| {
@@ -84,6 +92,18 @@ async fn main() -> anyhow::Result<()> {
                                 .unwrap();
                         }
                     }
+
+                    // the mouse operations are passed to the client which owns the composition
+                    let index = message.get("index").and_then(|index| index.as_i64());
+                    if let Some(index) = index {
+                        if type_value == "select" {
+                            controller_clone
+                                .send_event(CandidateEvent::SelectCandidate(index as i32));
+                        } else if type_value == "commit" {
+                            controller_clone
+                                .send_event(CandidateEvent::CommitCandidate(index as i32));
+                        }
+                    }
                 }
             }
         })
@@ -129,6 +149,13 @@ async fn main() -> anyhow::Result<()> {
                         }))
                         .unwrap();
                 }
+                WindowAction::SetPredictions { predictions } => {
+                    proxy_clone
+                        .send_event(UserEvent::WindowAction(WindowAction::SetPredictions {
+                            predictions,
+                        }))
+                        .unwrap();
+                }
                 WindowAction::SetSelection { index } => {
                     proxy_clone
                         .send_event(UserEvent::WindowAction(WindowAction::SetSelection {
@@ -147,6 +174,18 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    // pass the changed settings to the webviews
+    let proxy_clone = event_loop_proxy.clone();
+    let mut config_watcher = Some(ConfigWatcher::spawn(
+        config_watcher::DEFAULT_INTERVAL,
+        move |config| {
+            if let Ok(config) = serde_json::to_string(&config) {
+                let _ = proxy_clone.send_event(UserEvent::UpdateConfig(config));
+            }
+        },
+    ));
+
+    // the watcher is moved into the event loop, and stopped when the loop ends
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
 
@@ -158,12 +197,20 @@ async fn main() -> anyhow::Result<()> {
                 event: WindowEvent::CloseRequested,
                 ..
             } => *control_flow = ControlFlow::Exit,
+            Event::LoopDestroyed => {
+                config_watcher.take();
+            }
             Event::UserEvent(script) => match script {
                 UserEvent::UpdateCandidates(candidates) => {
                     candidate_webview
                         .evaluate_script(&format!("updateCandidates({})", candidates))
                         .unwrap();
                 }
+                UserEvent::UpdatePredictions(predictions) => {
+                    candidate_webview
+                        .evaluate_script(&format!("updatePredictions({})", predictions))
+                        .unwrap();
+                }
                 UserEvent::UpdateSelection(index) => {
                     candidate_webview
                         .evaluate_script(&format!("updateSelection({})", index))
@@ -174,6 +221,12 @@ async fn main() -> anyhow::Result<()> {
                         .evaluate_script(&format!("updateInputMethod(\"{}\")", input_method))
                         .unwrap();
                 }
+                UserEvent::UpdateConfig(config) => {
+                    // the pages which don't depend on the settings don't define updateConfig
+                    let script = format!("if (window.updateConfig) {{ updateConfig({}) }}", config);
+                    candidate_webview.evaluate_script(&script).unwrap();
+                    indicator_webview.evaluate_script(&script).unwrap();
+                }
                 UserEvent::UpdateHeight(height) => {
                     let width = candidate_window.inner_size().width as i32;
                     candidate_window.set_inner_size(LogicalSize::new(width, height));
@@ -259,9 +312,10 @@ async fn main() -> anyhow::Result<()> {
                             ));
                         }
                         WindowAction::SetCandidate { candidates } => {
+                            // the annotation is shown in the column beside the surface
                             let max_len = candidates
                                 .iter()
-                                .map(|s| s.chars().count())
+                                .map(|c| c.surface.chars().count() + c.annotation.chars().count())
                                 .max()
                                 .unwrap_or(0) as u32;
 
@@ -279,6 +333,15 @@ async fn main() -> anyhow::Result<()> {
                                 .send_event(UserEvent::UpdateCandidates(candidates))
                                 .unwrap();
                         }
+                        WindowAction::SetPredictions { predictions } => {
+                            let predictions = serde_json::to_string(&predictions)
+                                .context("Failed to serialize predictions")
+                                .unwrap();
+
+                            event_loop_proxy
+                                .send_event(UserEvent::UpdatePredictions(predictions))
+                                .unwrap();
+                        }
                         WindowAction::SetSelection { index } => {
                             event_loop_proxy
                                 .send_event(UserEvent::UpdateSelection(index))
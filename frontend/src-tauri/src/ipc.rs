@@ -1,6 +1,7 @@
 use anyhow::Result;
 use hyper_util::rt::TokioIo;
 use shared::proto::azookey_service_client::AzookeyServiceClient;
+use shared::user_dict::UserDictEntry;
 use std::{sync::Arc, time::Duration};
 use tokio::{net::windows::named_pipe::ClientOptions, time};
 use tonic::transport::Endpoint;
@@ -56,4 +57,52 @@ impl IPCService {
 
         Ok(())
     }
+
+    pub fn clear_learning_history(&mut self) -> anyhow::Result<()> {
+        let request = tonic::Request::new(shared::proto::ClearLearningHistoryRequest {});
+        self.runtime
+            .clone()
+            .block_on(self.azookey_client.clear_learning_history(request))?;
+
+        Ok(())
+    }
+
+    pub fn list_user_dict_entries(&mut self) -> anyhow::Result<Vec<UserDictEntry>> {
+        let request = tonic::Request::new(shared::proto::ListUserDictEntriesRequest {});
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.list_user_dict_entries(request))?;
+
+        Ok(response
+            .into_inner()
+            .entries
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub fn add_user_dict_entries(&mut self, entries: Vec<UserDictEntry>) -> anyhow::Result<u32> {
+        let request = tonic::Request::new(shared::proto::AddUserDictEntriesRequest {
+            entries: entries.into_iter().map(Into::into).collect(),
+        });
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.add_user_dict_entries(request))?;
+
+        Ok(response.into_inner().added)
+    }
+
+    pub fn remove_user_dict_entries(&mut self, entries: Vec<UserDictEntry>) -> anyhow::Result<u32> {
+        let request = tonic::Request::new(shared::proto::RemoveUserDictEntriesRequest {
+            entries: entries.into_iter().map(Into::into).collect(),
+        });
+        let response = self
+            .runtime
+            .clone()
+            .block_on(self.azookey_client.remove_user_dict_entries(request))?;
+
+        Ok(response.into_inner().removed)
+    }
 }
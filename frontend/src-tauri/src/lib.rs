@@ -1,6 +1,7 @@
 mod ipc;
 
 use serde::{Deserialize, Serialize};
+use shared::user_dict::{self, DictFormat, UserDictEntry};
 use shared::AppConfig;
 use std::{path::PathBuf, sync::Mutex};
 
@@ -13,7 +14,7 @@ pub struct AppState {
 impl AppState {
     fn new() -> Self {
         AppState {
-            settings: Mutex::new(AppConfig::new()),
+            settings: Mutex::new(AppConfig::new().unwrap_or_default()),
             ipc: ipc::IPCService::new().unwrap(),
         }
     }
@@ -31,12 +32,97 @@ fn get_config(state: tauri::State<AppState>) -> AppConfig {
 }
 
 #[tauri::command]
-fn update_config(state: tauri::State<AppState>, new_config: AppConfig) {
+fn update_config(state: tauri::State<AppState>, new_config: AppConfig) -> Result<(), String> {
     let mut config = state.settings.lock().unwrap();
+    new_config.write().map_err(|e| e.to_string())?;
     *config = new_config;
-    config.write();
 
-    state.ipc.clone().update_config().unwrap();
+    state.ipc.clone().update_config().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn clear_learning_history(state: tauri::State<AppState>) -> Result<(), String> {
+    state
+        .ipc
+        .clone()
+        .clear_learning_history()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_user_dict(state: tauri::State<AppState>) -> Result<Vec<UserDictEntry>, String> {
+    state
+        .ipc
+        .clone()
+        .list_user_dict_entries()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn add_user_dict_entry(state: tauri::State<AppState>, entry: UserDictEntry) -> Result<(), String> {
+    state
+        .ipc
+        .clone()
+        .add_user_dict_entries(vec![entry])
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn remove_user_dict_entry(
+    state: tauri::State<AppState>,
+    entry: UserDictEntry,
+) -> Result<(), String> {
+    state
+        .ipc
+        .clone()
+        .remove_user_dict_entries(vec![entry])
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct ImportResult {
+    added: u32,
+    skipped_lines: Vec<usize>,
+}
+
+// the format is detected from the content, so the file from any of the supported imes can be imported
+// returns the count of newly added entries and the lines which were skipped for an unknown part of speech
+#[tauri::command]
+fn import_user_dict(state: tauri::State<AppState>, data: Vec<u8>) -> Result<ImportResult, String> {
+    let parsed = user_dict::parse(&data).map_err(|e| e.to_string())?;
+
+    let added = state
+        .ipc
+        .clone()
+        .add_user_dict_entries(parsed.entries)
+        .map_err(|e| e.to_string())?;
+
+    Ok(ImportResult {
+        added,
+        skipped_lines: parsed.skipped_lines,
+    })
+}
+
+#[tauri::command]
+fn export_user_dict(state: tauri::State<AppState>, format: DictFormat) -> Result<Vec<u8>, String> {
+    let entries = state
+        .ipc
+        .clone()
+        .list_user_dict_entries()
+        .map_err(|e| e.to_string())?;
+
+    Ok(user_dict::serialize(&entries, format))
+}
+
+// the page to open at startup, e.g. "--page dictionary" from the language bar menu
+#[tauri::command]
+fn get_initial_page() -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--page");
+    args.nth(1)
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -98,7 +184,14 @@ pub fn run() {
             greet,
             get_config,
             update_config,
-            check_capability
+            check_capability,
+            clear_learning_history,
+            list_user_dict,
+            add_user_dict_entry,
+            remove_user_dict_entry,
+            import_user_dict,
+            export_user_dict,
+            get_initial_page
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,4 +1,4 @@
-import { Bot, Settings, Megaphone } from "lucide-react"
+import { Bot, Settings, Megaphone, BookOpen } from "lucide-react"
 
 import {
     Sidebar,
@@ -29,6 +29,11 @@ const contents = [
         url: "/zenzai",
         icon: Bot,
     },
+    {
+        title: "辞書",
+        url: "/dictionary",
+        icon: BookOpen,
+    },
 ]
 
 // Footer items.
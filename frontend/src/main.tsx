@@ -1,21 +1,39 @@
-import React from "react";
+import React, { useEffect } from "react";
 import ReactDOM from "react-dom/client";
 import "./index.css";
 import { SidebarProvider } from "@/components/ui/sidebar"
 import { ThemeProvider } from "@/components/theme-provider"
-import { BrowserRouter, Routes, Route } from "react-router";
+import { BrowserRouter, Routes, Route, useNavigate } from "react-router";
+import { invoke } from "@tauri-apps/api/core";
 import { AppSidebar } from "@/components/app-sidebar"
 
 import { General } from "@/pages/general"
 import { Appearance } from "@/pages/appearance"
 import { Zenzai } from "@/pages/zenzai"
+import { Dictionary } from "@/pages/dictionary"
 import { About } from "@/pages/about"
 import { Toaster } from "@/components/ui/sonner"
 
+// open the page passed by the language bar menu, e.g. the dictionary for "add word"
+function InitialPage() {
+  const navigate = useNavigate();
+
+  useEffect(() => {
+    invoke<string | null>("get_initial_page").then((page) => {
+      if (page) {
+        navigate(`/${page}`);
+      }
+    });
+  }, []);
+
+  return null;
+}
+
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <SidebarProvider>
       <BrowserRouter>
+        <InitialPage />
         <AppSidebar />
         <main className="w-full p-6">
           <ThemeProvider defaultTheme="system" storageKey="vite-ui-theme">
@@ -23,6 +41,7 @@ ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
               <Route path="/" element={<General />} />
               <Route path="/appearance" element={<Appearance />} />
               <Route path="/zenzai" element={<Zenzai />} />
+              <Route path="/dictionary" element={<Dictionary />} />
               <Route path="/about" element={<About />} />
             </Routes>
             <Toaster />
@@ -0,0 +1,227 @@
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { BookOpen, Download, Trash2, Upload } from "lucide-react";
+import {
+    Select,
+    SelectContent,
+    SelectItem,
+    SelectTrigger,
+    SelectValue,
+} from "@/components/ui/select"
+import { useEffect, useRef, useState } from "react";
+import { toast } from "sonner"
+import { invoke } from '@tauri-apps/api/core';
+
+type UserDictEntry = {
+    reading: string;
+    surface: string;
+    pos: string;
+    comment: string;
+};
+
+type ImportResult = {
+    added: number;
+    skipped_lines: number[];
+};
+
+const partsOfSpeech = [
+    { value: "noun", name: "名詞" },
+    { value: "proper_noun", name: "固有名詞" },
+    { value: "person_name", name: "人名" },
+    { value: "surname", name: "姓" },
+    { value: "given_name", name: "名" },
+    { value: "organization", name: "組織" },
+    { value: "place_name", name: "地名" },
+    { value: "suru_noun", name: "名詞サ変" },
+    { value: "adjective", name: "形容詞" },
+    { value: "adverb", name: "副詞" },
+    { value: "symbol", name: "記号" },
+    { value: "emoticon", name: "顔文字" },
+    { value: "abbreviation", name: "短縮よみ" },
+]
+
+const formats = [
+    { value: "google", name: "Google日本語入力", filename: "azookey_dictionary.txt" },
+    { value: "ms_ime", name: "Microsoft IME", filename: "azookey_dictionary_msime.txt" },
+    { value: "mozc", name: "Mozc", filename: "azookey_dictionary_mozc.txt" },
+]
+
+export const Dictionary = () => {
+    const [entries, setEntries] = useState<UserDictEntry[]>([]);
+    const [newEntry, setNewEntry] = useState<UserDictEntry>({
+        reading: "",
+        surface: "",
+        pos: "noun",
+        comment: "",
+    });
+    const [format, setFormat] = useState("google");
+    const fileInput = useRef<HTMLInputElement>(null);
+
+    const loadEntries = () => {
+        invoke<UserDictEntry[]>("list_user_dict")
+            .then(setEntries)
+            .catch(() => {
+                toast("辞書の読み込みに失敗しました");
+            });
+    };
+
+    // Load entries on component mount
+    useEffect(loadEntries, []);
+
+    const handleAdd = async () => {
+        if (!newEntry.reading || !newEntry.surface) {
+            toast("読みと単語を入力してください");
+            return;
+        }
+
+        try {
+            await invoke("add_user_dict_entry", { entry: newEntry });
+            setNewEntry((prev) => ({ ...prev, reading: "", surface: "", comment: "" }));
+            loadEntries();
+        } catch (error) {
+            toast("単語の登録に失敗しました");
+        }
+    };
+
+    const handleRemove = async (entry: UserDictEntry) => {
+        try {
+            await invoke("remove_user_dict_entry", { entry });
+            loadEntries();
+        } catch (error) {
+            toast("単語の削除に失敗しました");
+        }
+    };
+
+    const handleImport = async (event: React.ChangeEvent<HTMLInputElement>) => {
+        const file = event.target.files?.[0];
+        event.target.value = "";
+        if (!file) {
+            return;
+        }
+
+        try {
+            const data = Array.from(new Uint8Array(await file.arrayBuffer()));
+            const { added, skipped_lines } = await invoke<ImportResult>("import_user_dict", { data });
+            if (skipped_lines.length > 0) {
+                toast(`${added}件の単語をインポートしました`, {
+                    description: `未対応の品詞の行をスキップしました: ${skipped_lines.join(", ")}行目`,
+                });
+            } else {
+                toast(`${added}件の単語をインポートしました`);
+            }
+            loadEntries();
+        } catch (error) {
+            toast("辞書のインポートに失敗しました", {
+                description: String(error),
+            });
+        }
+    };
+
+    const handleExport = async () => {
+        try {
+            const data = await invoke<number[]>("export_user_dict", { format });
+            const blob = new Blob([new Uint8Array(data)], { type: "text/plain" });
+            const link = document.createElement("a");
+            link.href = URL.createObjectURL(blob);
+            link.download = formats.find((f) => f.value === format)?.filename ?? "azookey_dictionary.txt";
+            link.click();
+            URL.revokeObjectURL(link.href);
+        } catch (error) {
+            toast("辞書のエクスポートに失敗しました");
+        }
+    };
+
+    return (
+        <div className="space-y-8">
+            <section className="space-y-2">
+                <h1 className="text-sm font-bold text-foreground">単語の登録</h1>
+                <div className="space-y-4 rounded-md border p-4">
+                    <div className="flex items-center space-x-4">
+                        <BookOpen />
+                        <div className="flex-1 space-y-1">
+                            <p className="text-sm font-medium leading-none">
+                                ユーザー辞書
+                            </p>
+                            <p className="text-xs text-muted-foreground">
+                                登録した単語は変換候補に優先して表示されます
+                            </p>
+                        </div>
+                    </div>
+                    <div className="flex items-center gap-x-2">
+                        <Input placeholder="読み (ひらがな)" value={newEntry.reading} onChange={(e) => setNewEntry((prev) => ({ ...prev, reading: e.target.value }))} />
+                        <Input placeholder="単語" value={newEntry.surface} onChange={(e) => setNewEntry((prev) => ({ ...prev, surface: e.target.value }))} />
+                        <Select value={newEntry.pos} onValueChange={(pos) => setNewEntry((prev) => ({ ...prev, pos }))}>
+                            <SelectTrigger className="w-36">
+                                <SelectValue placeholder="品詞" />
+                            </SelectTrigger>
+                            <SelectContent>
+                                {partsOfSpeech.map((pos) => (
+                                    <SelectItem key={pos.value} value={pos.value}>{pos.name}</SelectItem>
+                                ))}
+                            </SelectContent>
+                        </Select>
+                        <Button onClick={handleAdd}>登録</Button>
+                    </div>
+                    <div className="max-h-72 overflow-y-auto rounded-md border">
+                        {entries.length === 0 ? (
+                            <p className="p-4 text-xs text-muted-foreground">登録された単語はありません</p>
+                        ) : entries.map((entry) => (
+                            <div key={`${entry.reading}\t${entry.surface}`} className="flex items-center space-x-4 border-b px-4 py-2 last:border-b-0">
+                                <p className="w-1/3 text-sm">{entry.reading}</p>
+                                <p className="flex-1 text-sm font-medium">{entry.surface}</p>
+                                <p className="w-20 text-xs text-muted-foreground">
+                                    {partsOfSpeech.find((pos) => pos.value === entry.pos)?.name}
+                                </p>
+                                <Button variant="ghost" size="icon" onClick={() => handleRemove(entry)}>
+                                    <Trash2 />
+                                </Button>
+                            </div>
+                        ))}
+                    </div>
+                </div>
+            </section>
+            <section className="space-y-2">
+                <h1 className="text-sm font-bold text-foreground">インポートとエクスポート</h1>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Upload />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            辞書をインポート
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            Google日本語入力、Microsoft IME、Mozcの辞書ファイルを読み込みます
+                        </p>
+                    </div>
+                    <input ref={fileInput} type="file" accept=".txt,.tsv" className="hidden" onChange={handleImport} />
+                    <Button variant="secondary" onClick={() => fileInput.current?.click()}>
+                        ファイルを選択
+                    </Button>
+                </div>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Download />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            辞書をエクスポート
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            登録した単語を他のIMEで読み込める形式で保存します
+                        </p>
+                    </div>
+                    <Select value={format} onValueChange={setFormat}>
+                        <SelectTrigger className="w-48">
+                            <SelectValue placeholder="形式を選択" />
+                        </SelectTrigger>
+                        <SelectContent>
+                            {formats.map((f) => (
+                                <SelectItem key={f.value} value={f.value}>{f.name}</SelectItem>
+                            ))}
+                        </SelectContent>
+                    </Select>
+                    <Button variant="secondary" onClick={handleExport}>
+                        エクスポート
+                    </Button>
+                </div>
+            </section>
+        </div>
+    )
+}
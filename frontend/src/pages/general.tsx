@@ -1,7 +1,174 @@
 import { Button } from "@/components/ui/button";
-import { RefreshCcw, ExternalLink } from "lucide-react";
+import { Switch } from "@/components/ui/switch";
+import { RefreshCcw, ExternalLink, Brain, Lock, Trash2, Keyboard, Languages, AppWindow, Zap, Lightbulb, ListOrdered } from "lucide-react";
+import {
+    Select,
+    SelectContent,
+    SelectItem,
+    SelectTrigger,
+    SelectValue,
+} from "@/components/ui/select"
+import {
+    AlertDialog,
+    AlertDialogAction,
+    AlertDialogCancel,
+    AlertDialogContent,
+    AlertDialogDescription,
+    AlertDialogFooter,
+    AlertDialogHeader,
+    AlertDialogTitle,
+    AlertDialogTrigger,
+} from "@/components/ui/alert-dialog"
+import { useEffect, useState } from "react";
+import { toast } from "sonner"
+import { invoke } from '@tauri-apps/api/core';
+
+const keymapPresets = [
+    { value: "ms_ime", name: "Microsoft IME" },
+    { value: "atok", name: "ATOK" },
+    { value: "kotoeri", name: "ことえり" },
+]
+
+const pageSizes = [5, 6, 7, 8, 9];
+
+const inputStyles = [
+    { value: "romaji", name: "ローマ字入力" },
+    { value: "kana", name: "かな入力" },
+]
 
 export const General = () => {
+    const [learning, setLearning] = useState({
+        enable: true,
+        read_only: false,
+    });
+    const [keymapPreset, setKeymapPreset] = useState("ms_ime");
+    const [inputStyle, setInputStyle] = useState("romaji");
+    const [rememberMode, setRememberMode] = useState(true);
+    const [liveConversion, setLiveConversion] = useState(true);
+    const [prediction, setPrediction] = useState(true);
+    const [pageSize, setPageSize] = useState(9);
+
+    // Load config on component mount
+    useEffect(() => {
+        invoke<any>("get_config")
+            .then((data) => {
+                setLearning({
+                    enable: data.learning.enable,
+                    read_only: data.learning.read_only,
+                });
+                setKeymapPreset(data.keymap.preset);
+                setInputStyle(data.input.style);
+                setRememberMode(data.input.remember_mode);
+                setLiveConversion(data.input.live_conversion);
+                setPrediction(data.prediction.enable);
+                setPageSize(data.candidate_window.page_size);
+            })
+            .catch(() => {
+                // Keep default values if config fetch fails
+            });
+    }, []);
+
+    const updateConfig = async (updater: (config: any) => void) => {
+        try {
+            const data = await invoke<any>("get_config");
+            updater(data);
+            await invoke("update_config", { newConfig: data });
+            return data;
+        } catch (error) {
+            toast("設定の更新に失敗しました");
+            return null;
+        }
+    };
+
+    const handleLearningChange = async () => {
+        const data = await updateConfig((data) => {
+            data.learning.enable = !learning.enable;
+        });
+
+        if (data) {
+            setLearning((prev) => ({ ...prev, enable: data.learning.enable }));
+        }
+    };
+
+    const handleReadOnlyChange = async () => {
+        const data = await updateConfig((data) => {
+            data.learning.read_only = !learning.read_only;
+        });
+
+        if (data) {
+            setLearning((prev) => ({ ...prev, read_only: data.learning.read_only }));
+        }
+    };
+
+    const handleKeymapPresetChange = async (preset: string) => {
+        const data = await updateConfig((data) => {
+            data.keymap.preset = preset;
+        });
+
+        if (data) {
+            setKeymapPreset(data.keymap.preset);
+        }
+    };
+
+    const handleInputStyleChange = async (style: string) => {
+        const data = await updateConfig((data) => {
+            data.input.style = style;
+        });
+
+        if (data) {
+            setInputStyle(data.input.style);
+        }
+    };
+
+    const handleRememberModeChange = async () => {
+        const data = await updateConfig((data) => {
+            data.input.remember_mode = !rememberMode;
+        });
+
+        if (data) {
+            setRememberMode(data.input.remember_mode);
+        }
+    };
+
+    const handleLiveConversionChange = async () => {
+        const data = await updateConfig((data) => {
+            data.input.live_conversion = !liveConversion;
+        });
+
+        if (data) {
+            setLiveConversion(data.input.live_conversion);
+        }
+    };
+
+    const handlePredictionChange = async () => {
+        const data = await updateConfig((data) => {
+            data.prediction.enable = !prediction;
+        });
+
+        if (data) {
+            setPrediction(data.prediction.enable);
+        }
+    };
+
+    const handlePageSizeChange = async (size: string) => {
+        const data = await updateConfig((data) => {
+            data.candidate_window.page_size = Number(size);
+        });
+
+        if (data) {
+            setPageSize(data.candidate_window.page_size);
+        }
+    };
+
+    const handleClearHistory = async () => {
+        try {
+            await invoke("clear_learning_history");
+            toast("学習履歴を消去しました");
+        } catch (error) {
+            toast("学習履歴の消去に失敗しました");
+        }
+    };
+
     return (
         <div className="space-y-8">
             <section className="space-y-2">
@@ -21,6 +188,163 @@ export const General = () => {
                     </Button>
                 </div>
             </section>
+            <section className="space-y-2">
+                <h1 className="text-sm font-bold text-foreground">学習</h1>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Brain />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            学習を有効化
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            確定した変換候補を覚えて、次回から優先して表示します
+                        </p>
+                    </div>
+                    <Switch checked={learning.enable} onCheckedChange={handleLearningChange} />
+                </div>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Lock />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            学習内容を固定
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            これまでの学習内容は使用しますが、新しく学習しません
+                        </p>
+                    </div>
+                    <Switch checked={learning.read_only} disabled={!learning.enable} onCheckedChange={handleReadOnlyChange} />
+                </div>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Trash2 />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            学習履歴を消去
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            これまでに学習した変換候補をすべて消去します
+                        </p>
+                    </div>
+                    <AlertDialog>
+                        <AlertDialogTrigger asChild>
+                            <Button variant="secondary">消去する</Button>
+                        </AlertDialogTrigger>
+                        <AlertDialogContent>
+                            <AlertDialogHeader>
+                                <AlertDialogTitle>学習履歴を消去しますか？</AlertDialogTitle>
+                                <AlertDialogDescription>
+                                    この操作は取り消せません。
+                                </AlertDialogDescription>
+                            </AlertDialogHeader>
+                            <AlertDialogFooter>
+                                <AlertDialogCancel>キャンセル</AlertDialogCancel>
+                                <AlertDialogAction onClick={handleClearHistory}>消去する</AlertDialogAction>
+                            </AlertDialogFooter>
+                        </AlertDialogContent>
+                    </AlertDialog>
+                </div>
+            </section>
+            <section className="space-y-2">
+                <h1 className="text-sm font-bold text-foreground">キー設定</h1>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Languages />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            入力方式
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            かな入力ではJISキーボードのかな配列で入力します (カタカナ/ひらがなキーで切り替え)
+                        </p>
+                    </div>
+                    <Select value={inputStyle} onValueChange={handleInputStyleChange}>
+                        <SelectTrigger className="w-48">
+                            <SelectValue placeholder="入力方式を選択" />
+                        </SelectTrigger>
+                        <SelectContent>
+                            {inputStyles.map((style) => (
+                                <SelectItem key={style.value} value={style.value}>{style.name}</SelectItem>
+                            ))}
+                        </SelectContent>
+                    </Select>
+                </div>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Keyboard />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            キー配列
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            変換中のキー操作を、使い慣れたIMEに合わせます
+                        </p>
+                    </div>
+                    <Select value={keymapPreset} onValueChange={handleKeymapPresetChange}>
+                        <SelectTrigger className="w-48">
+                            <SelectValue placeholder="キー配列を選択" />
+                        </SelectTrigger>
+                        <SelectContent>
+                            {keymapPresets.map((preset) => (
+                                <SelectItem key={preset.value} value={preset.value}>{preset.name}</SelectItem>
+                            ))}
+                        </SelectContent>
+                    </Select>
+                </div>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <AppWindow />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            アプリごとに入力モードを記憶
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            アプリを切り替えたときに、そのアプリで最後に使った入力モードに戻します
+                        </p>
+                    </div>
+                    <Switch checked={rememberMode} onCheckedChange={handleRememberModeChange} />
+                </div>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Zap />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            ライブ変換
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            入力中に変換結果を表示します。オフにすると、スペースキーを押すまでひらがなで表示します
+                        </p>
+                    </div>
+                    <Switch checked={liveConversion} onCheckedChange={handleLiveConversionChange} />
+                </div>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <Lightbulb />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            予測変換
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            入力中の読みに続く候補を変換候補の下に表示します。Tabキーで確定します
+                        </p>
+                    </div>
+                    <Switch checked={prediction} onCheckedChange={handlePredictionChange} />
+                </div>
+                <div className="flex items-center space-x-4 rounded-md border p-4">
+                    <ListOrdered />
+                    <div className="flex-1 space-y-1">
+                        <p className="text-sm font-medium leading-none">
+                            1ページの候補数
+                        </p>
+                        <p className="text-xs text-muted-foreground">
+                            変換中は数字キーでページ内の候補を選び、PageUp/PageDownでページを切り替えます
+                        </p>
+                    </div>
+                    <Select value={String(pageSize)} onValueChange={handlePageSizeChange}>
+                        <SelectTrigger className="w-48">
+                            <SelectValue placeholder="候補数を選択" />
+                        </SelectTrigger>
+                        <SelectContent>
+                            {pageSizes.map((size) => (
+                                <SelectItem key={size} value={String(size)}>{size}件</SelectItem>
+                            ))}
+                        </SelectContent>
+                    </Select>
+                </div>
+            </section>
             {/* <section className="space-y-2">
                 <h1 className="text-sm font-bold text-foreground">診断とフィードバック</h1>
                 <div className="flex items-center space-x-4 rounded-md border p-4">
@@ -38,4 +362,4 @@ export const General = () => {
             </section> */}
         </div>
     )
-}
\ No newline at end of file
+}
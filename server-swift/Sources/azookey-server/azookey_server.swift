@@ -3,23 +3,46 @@ import Foundation
 import ffi
 
 @MainActor let converter = KanaKanjiConverter()
-@MainActor var composingText = ComposingText()
+// composing text and left side context of each session
+@MainActor var composingTexts: [Int32: ComposingText] = [:]
+@MainActor var contexts: [Int32: String] = [:]
+// reading lengths of the clauses resized by the user, they are reset when the composing text is changed
+@MainActor var clauseLengths: [Int32: [Int]] = [:]
+// clauses of the composing text, they are segmented again only when the text or the lengths are changed
+@MainActor var segmentedClauses: [Int32: [ClauseResult]] = [:]
+// the converter caches the previous result, so it should be reset when the session is switched
+@MainActor var activeSession: Int32? = nil
+// candidates shown to the session, the committed one is looked up from them to be learned
+@MainActor var lastCandidates: [Int32: [Candidate]] = [:]
+// candidates whose reading is longer than the input, they are returned by GetPredictions
+@MainActor var lastPredictions: [Int32: [Candidate]] = [:]
 
 @MainActor var execURL = URL(filePath: "")
+// learning data is persisted here, %APPDATA%/Azookey/memory
+@MainActor var memoryURL = URL(filePath: "./memory")
 @MainActor var config: [String : Any] = [
     "enable": false,
     "profile": "",
+    "learning": true,
+    "learningReadOnly": false,
 ]
 
+@MainActor func getLearningType() -> LearningType {
+    if !(config["learning"] as! Bool) {
+        return .nothing
+    }
+    return config["learningReadOnly"] as! Bool ? .onlyOutput : .inputAndOutput
+}
+
 @MainActor func getOptions(context: String = "") -> ConvertRequestOptions {
     return ConvertRequestOptions(
         requireJapanesePrediction: true,
         requireEnglishPrediction: false,
         keyboardLanguage: .ja_JP,
-        learningType: .nothing,
+        learningType: getLearningType(),
         dictionaryResourceURL: execURL.appendingPathComponent("Dictionary"),
-        memoryDirectoryURL: URL(filePath: "./test"),
-        sharedContainerURL: URL(filePath: "./test"),
+        memoryDirectoryURL: memoryURL,
+        sharedContainerURL: memoryURL,
         textReplacer: .init {
             return execURL.appendingPathComponent("EmojiDictionary").appendingPathComponent("emoji_all_E15.1.txt")
         },
@@ -72,27 +95,210 @@ func constructCandidateString(candidate: Candidate, hiragana: String) -> String
     return result
 }
 
+// the values are the same as CandidateSource in service.proto
+enum CandidateSource: Int32 {
+    case dictionary = 0
+    case user = 1
+    case learned = 2
+    case zenzai = 3
+    case emoji = 4
+}
+
+func isEmoji(_ text: String) -> Bool {
+    return !text.isEmpty && text.unicodeScalars.allSatisfy {
+        $0.properties.isEmojiPresentation || $0.value == 0xFE0F || $0.value == 0x200D
+    }
+}
+
+// zenzai reranks the candidates, so the best one is marked as converted by it
+@MainActor func candidateSource(_ candidate: Candidate, rank: Int) -> CandidateSource {
+    if isEmoji(candidate.text) {
+        return .emoji
+    }
+    if candidate.data.contains(where: { $0.metadata.contains(.isFromUserDictionary) }) {
+        return .user
+    }
+    if candidate.data.contains(where: { $0.metadata.contains(.isLearned) }) {
+        return .learned
+    }
+    if rank == 0 && config["enable"] as! Bool {
+        return .zenzai
+    }
+    return .dictionary
+}
+
+// the emoji may not be shown by the font of the application
+func candidateAnnotation(_ candidate: Candidate) -> String {
+    return isEmoji(candidate.text) ? "[環境依存]" : ""
+}
+
+@MainActor func toFFICandidate(_ candidate: Candidate, rank: Int, text: String, subtext: String, hiragana: String, correspondingCount: Int) -> FFICandidate {
+    return FFICandidate(
+        text: strdup(text),
+        subtext: strdup(subtext),
+        hiragana: strdup(hiragana),
+        correspondingCount: Int32(correspondingCount),
+        source: candidateSource(candidate, rank: rank).rawValue,
+        annotation: strdup(candidateAnnotation(candidate)),
+        score: candidate.value
+    )
+}
+
+@MainActor func activate(session: Int32) {
+    if activeSession != session {
+        converter.stopComposition()
+        activeSession = session
+    }
+}
+
+@MainActor func getComposingText(session: Int32) -> ComposingText {
+    return composingTexts[session] ?? ComposingText()
+}
+
+@MainActor func setComposingText(session: Int32, _ composingText: ComposingText) {
+    composingTexts[session] = composingText
+    clauseLengths[session] = nil
+    segmentedClauses[session] = nil
+    lastCandidates[session] = nil
+    lastPredictions[session] = nil
+}
+
+struct ClauseResult {
+    var reading: String
+    var surface: String
+    var correspondingCount: Int
+    // composing text which contains only this clause
+    var composingText: ComposingText
+}
+
+// composing text of the first `length` characters
+func clausePrefix(_ composingText: ComposingText, length: Int) -> ComposingText {
+    var prefix = composingText
+    _ = prefix.moveCursorFromCursorPosition(count: length - prefix.convertTargetCursorPosition)
+    return prefix.prefixToCursorPosition()
+}
+
+// split the best path into clauses
+// the resized clauses keep their lengths, and the rest is split by the first clause results of the converter
+@MainActor func segmentClauses(session: Int32) -> [ClauseResult] {
+    if let clauses = segmentedClauses[session] {
+        return clauses
+    }
+
+    var rest = getComposingText(session: session)
+    _ = rest.moveCursorFromCursorPosition(count: rest.convertTarget.count - rest.convertTargetCursorPosition)
+
+    let lengths = clauseLengths[session] ?? []
+    let options = getOptions(context: contexts[session] ?? "")
+    var clauses: [ClauseResult] = []
+
+    activate(session: session)
+
+    while !rest.convertTarget.isEmpty {
+        let length: Int
+        if clauses.count < lengths.count {
+            length = min(max(lengths[clauses.count], 1), rest.convertTarget.count)
+        } else if let candidate = converter.requestCandidates(rest, options: options).firstClauseResults.first {
+            var after = rest
+            after.prefixComplete(correspondingCount: candidate.correspondingCount)
+            length = max(rest.convertTarget.count - after.convertTarget.count, 1)
+        } else {
+            length = rest.convertTarget.count
+        }
+
+        let prefix = clausePrefix(rest, length: length)
+        let converted = converter.requestCandidates(prefix, options: options)
+        let candidate = converted.mainResults.first { $0.correspondingCount == prefix.input.count }
+
+        clauses.append(ClauseResult(
+            reading: prefix.convertTarget,
+            surface: candidate?.text ?? prefix.convertTarget,
+            correspondingCount: prefix.input.count,
+            composingText: prefix
+        ))
+
+        rest.prefixComplete(correspondingCount: prefix.input.count)
+    }
+
+    // the converter caches the last input, so reset it not to mix with the next input
+    converter.stopComposition()
+
+    segmentedClauses[session] = clauses
+    return clauses
+}
+
+func toClauseListPointer(_ clauses: [ClauseResult]) -> UnsafeMutablePointer<UnsafeMutablePointer<FFIClause>?> {
+    let pointer = UnsafeMutablePointer<UnsafeMutablePointer<FFIClause>?>.allocate(capacity: clauses.count)
+    for (i, clause) in clauses.enumerated() {
+        pointer[i] = UnsafeMutablePointer<FFIClause>.allocate(capacity: 1)
+        pointer[i]?.pointee = FFIClause(
+            reading: strdup(clause.reading),
+            surface: strdup(clause.surface),
+            correspondingCount: Int32(clause.correspondingCount)
+        )
+    }
+    return pointer
+}
+
+// the layout of shared::AppConfig, only the fields used by the converter are decoded
+struct AppConfig: Decodable {
+    struct Zenzai: Decodable {
+        var enable: Bool
+        var profile: String
+    }
+
+    struct Learning: Decodable {
+        var enable: Bool
+        var read_only: Bool
+    }
+
+    var zenzai: Zenzai
+    var learning: Learning
+}
+
+// the config is read and migrated by rust, and passed as json
 @_silgen_name("LoadConfig")
-@MainActor public func load_config() {
-    if let appDataPath = ProcessInfo.processInfo.environment["APPDATA"] {
-        let settingsPath = URL(filePath: appDataPath).appendingPathComponent("Azookey/settings.json")
-        
-        do {
-            let data = try Data(contentsOf: settingsPath)
-            if let json = try JSONSerialization.jsonObject(with: data) as? [String: Any],
-               let zenzaiDict = json["zenzai"] as? [String: Any] {
-                
-                if let enableValue = zenzaiDict["enable"] as? Bool {
-                    config["enable"] = enableValue
-                }
-                
-                if let profileValue = zenzaiDict["profile"] as? String {
-                    config["profile"] = profileValue
-                }
-            }
-        } catch {
-            print("Failed to read settings: \(error)")
+@MainActor public func load_config(json: UnsafePointer<CChar>) {
+    let data = Data(String(cString: json).utf8)
+
+    do {
+        let appConfig = try JSONDecoder().decode(AppConfig.self, from: data)
+        config["enable"] = appConfig.zenzai.enable
+        config["profile"] = appConfig.zenzai.profile
+        config["learning"] = appConfig.learning.enable
+        config["learningReadOnly"] = appConfig.learning.read_only
+    } catch {
+        print("Failed to read settings: \(error)")
+    }
+}
+
+struct UserDictEntry: Decodable {
+    var reading: String
+    var surface: String
+}
+
+// the entries are passed as json, the same layout as user_dict.json
+@_silgen_name("SetUserDictionary")
+@MainActor public func set_user_dictionary(json: UnsafePointer<CChar>) {
+    let data = Data(String(cString: json).utf8)
+
+    do {
+        let entries = try JSONDecoder().decode([UserDictEntry].self, from: data)
+        // the part of speech is not used by the converter yet, every word is registered as a proper noun
+        let dicdata = entries.map {
+            DicdataElement(
+                word: $0.surface,
+                ruby: $0.reading.toKatakana(),
+                cid: CIDData.固有名詞.cid,
+                mid: MIDData.一般.mid,
+                value: -5
+            )
         }
+        converter.sendToDicdataStore(.importDynamicUserDict(dicdata))
+        // the new words can change the clauses
+        segmentedClauses.removeAll()
+    } catch {
+        print("Failed to read user dictionary: \(error)")
     }
 }
 
@@ -104,51 +310,82 @@ func constructCandidateString(candidate: Candidate, hiragana: String) -> String
     let path = String(cString: path)
     execURL = URL(filePath: path)
 
-    load_config()
+    if let appDataPath = ProcessInfo.processInfo.environment["APPDATA"] {
+        memoryURL = URL(filePath: appDataPath).appendingPathComponent("Azookey/memory")
+        try? FileManager.default.createDirectory(at: memoryURL, withIntermediateDirectories: true)
+    }
 
+    var composingText = ComposingText()
     composingText.insertAtCursorPosition("a", inputStyle: .roman2kana)
     converter.requestCandidates(composingText, options: getOptions())
-    composingText = ComposingText()
+    converter.stopComposition()
+}
+
+@_silgen_name("CreateSession")
+@MainActor public func create_session(session: Int32) {
+    composingTexts[session] = ComposingText()
+    contexts[session] = ""
+}
+
+@_silgen_name("CloseSession")
+@MainActor public func close_session(session: Int32) {
+    composingTexts.removeValue(forKey: session)
+    contexts.removeValue(forKey: session)
+    clauseLengths.removeValue(forKey: session)
+    segmentedClauses.removeValue(forKey: session)
+    lastCandidates.removeValue(forKey: session)
+    lastPredictions.removeValue(forKey: session)
+    if activeSession == session {
+        converter.stopComposition()
+        activeSession = nil
+    }
 }
 
 @_silgen_name("AppendText")
 @MainActor public func append_text(
+    session: Int32,
     input: UnsafePointer<CChar>,
-    cursorPtr: UnsafeMutablePointer<Int>
+    cursorPtr: UnsafeMutablePointer<Int32>
 ) -> UnsafeMutablePointer<CChar> {
     let inputString = String(cString: input)
+    var composingText = getComposingText(session: session)
     composingText.insertAtCursorPosition(inputString, inputStyle: .roman2kana)
+    setComposingText(session: session, composingText)
 
-    cursorPtr.pointee = composingText.convertTargetCursorPosition    
+    cursorPtr.pointee = Int32(composingText.convertTargetCursorPosition)
     return _strdup(composingText.convertTarget)!
 }
 
 @_silgen_name("RemoveText")
 @MainActor public func remove_text(
-    cursorPtr: UnsafeMutablePointer<Int>
+    session: Int32,
+    cursorPtr: UnsafeMutablePointer<Int32>
 ) -> UnsafeMutablePointer<CChar> {
+    var composingText = getComposingText(session: session)
     composingText.deleteBackwardFromCursorPosition(count: 1)
+    setComposingText(session: session, composingText)
 
-    cursorPtr.pointee = composingText.convertTargetCursorPosition
+    cursorPtr.pointee = Int32(composingText.convertTargetCursorPosition)
     return _strdup(composingText.convertTarget)!
 }
 
 @_silgen_name("MoveCursor")
 @MainActor public func move_cursor(
+    session: Int32,
     offset: Int32,
-    cursorPtr: UnsafeMutablePointer<Int>
+    cursorPtr: UnsafeMutablePointer<Int32>
 ) -> UnsafeMutablePointer<CChar> {
-    let previousCursor = composingText.convertTargetCursorPosition
+    var composingText = getComposingText(session: session)
     let cursor = composingText.moveCursorFromCursorPosition(count: Int(offset))
-    print("offset: \(offset), cursor: \(cursor)")
+    setComposingText(session: session, composingText)
 
-    cursorPtr.pointee = cursor
+    cursorPtr.pointee = Int32(cursor)
     return _strdup(composingText.convertTarget)!
 }
 
 @_silgen_name("ClearText")
-@MainActor public func clear_text() {
-    composingText = ComposingText()
+@MainActor public func clear_text(session: Int32) {
+    setComposingText(session: session, ComposingText())
 }
 
 func to_list_pointer(_ list: [FFICandidate]) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
@@ -161,47 +398,197 @@ func to_list_pointer(_ list: [FFICandidate]) -> UnsafeMutablePointer<UnsafeMutab
 }
 
 @_silgen_name("GetComposedText")
-@MainActor public func get_composed_text(lengthPtr: UnsafeMutablePointer<Int>) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
-    let hiragana = composingText.convertTarget
-    let contextString = (config["context"] as? String) ?? ""
+@MainActor public func get_composed_text(session: Int32, lengthPtr: UnsafeMutablePointer<Int32>) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
+    activate(session: session)
+
+    let composingText = getComposingText(session: session)
+    // only the text before the cursor is converted, and the rest is returned as the subtext
+    let prefixComposingText = composingText.prefixToCursorPosition()
+    let hiragana = prefixComposingText.convertTarget
+
+    if hiragana.isEmpty {
+        lastPredictions[session] = nil
+        lengthPtr.pointee = 1
+        return to_list_pointer([
+            FFICandidate(text: strdup(""), subtext: strdup(composingText.convertTarget), hiragana: strdup(""), correspondingCount: 0, source: CandidateSource.dictionary.rawValue, annotation: strdup(""), score: 0)
+        ])
+    }
+
+    let contextString = contexts[session] ?? ""
     let options = getOptions(context: contextString)
-    let converted = converter.requestCandidates(composingText, options: options)
+    let converted = converter.requestCandidates(prefixComposingText, options: options)
+    lastCandidates[session] = converted.mainResults
+    // the predictions are mixed in the main results, so they are split by the length of the reading
+    let isPrediction = { (candidate: Candidate) in
+        candidate.data.map { $0.ruby }.joined().count > hiragana.count
+    }
+    lastPredictions[session] = converted.mainResults.filter(isPrediction)
+    var conversions = converted.mainResults.filter { !isPrediction($0) }
+    if conversions.isEmpty {
+        conversions = converted.mainResults
+    }
     var result: [FFICandidate] = []
 
-    for i in 0..<converted.mainResults.count {
-        let candidate = converted.mainResults[i]
+    for i in 0..<conversions.count {
+        let candidate = conversions[i]
 
-        let text = strdup(constructCandidateString(candidate: candidate, hiragana: hiragana))
-        let hiragana = strdup(hiragana)
+        let text = constructCandidateString(candidate: candidate, hiragana: hiragana)
         let correspondingCount = candidate.correspondingCount
 
         var afterComposingText = composingText
         afterComposingText.prefixComplete(correspondingCount: correspondingCount)
-        let subtext = strdup(afterComposingText.convertTarget)
+        let subtext = afterComposingText.convertTarget
 
-        result.append(FFICandidate(text: text, subtext: subtext, hiragana: hiragana, correspondingCount: Int32(correspondingCount)))        
+        result.append(toFFICandidate(candidate, rank: i, text: text, subtext: subtext, hiragana: hiragana, correspondingCount: correspondingCount))
     }
 
-    lengthPtr.pointee = result.count
+    lengthPtr.pointee = Int32(result.count)
 
     return to_list_pointer(result)
 }
 
+@_silgen_name("GetPredictions")
+@MainActor public func get_predictions(session: Int32, lengthPtr: UnsafeMutablePointer<Int32>) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
+    let composingText = getComposingText(session: session)
+    let hiragana = composingText.prefixToCursorPosition().convertTarget
+    let predictions = lastPredictions[session] ?? []
+    var result: [FFICandidate] = []
+
+    for (rank, candidate) in predictions.enumerated() {
+        var afterComposingText = composingText
+        afterComposingText.prefixComplete(correspondingCount: candidate.correspondingCount)
+
+        // the reading of the prediction is longer than the typed hiragana
+        let reading = candidate.data.map { $0.ruby }.joined().toHiragana()
+        result.append(toFFICandidate(candidate, rank: rank, text: candidate.text, subtext: afterComposingText.convertTarget, hiragana: reading.isEmpty ? hiragana : reading, correspondingCount: candidate.correspondingCount))
+    }
+
+    lengthPtr.pointee = Int32(result.count)
+    return to_list_pointer(result)
+}
+
 @_silgen_name("ShrinkText")
 @MainActor public func shrink_text(
-    offset: Int32
+    session: Int32,
+    offset: Int32,
+    cursorPtr: UnsafeMutablePointer<Int32>
 ) -> UnsafeMutablePointer<CChar>  {
-    var afterComposingText = composingText
+    var afterComposingText = getComposingText(session: session)
     afterComposingText.prefixComplete(correspondingCount: Int(offset))
-    composingText = afterComposingText
+    setComposingText(session: session, afterComposingText)
 
-    return _strdup(composingText.convertTarget)!
+    cursorPtr.pointee = Int32(afterComposingText.convertTargetCursorPosition)
+
+    return _strdup(afterComposingText.convertTarget)!
 }
 
 @_silgen_name("SetContext")
 @MainActor public func set_context(
+    session: Int32,
     context: UnsafePointer<CChar>
 ) {
     let contextString = String(cString: context)
-    config["context"] = contextString
-}
\ No newline at end of file
+    // the context is sent on every key, and only a different one changes the conversion of the clauses
+    if contexts[session] != contextString {
+        segmentedClauses[session] = nil
+    }
+    contexts[session] = contextString
+}
+
+@_silgen_name("GetClauses")
+@MainActor public func get_clauses(
+    session: Int32,
+    lengthPtr: UnsafeMutablePointer<Int32>
+) -> UnsafeMutablePointer<UnsafeMutablePointer<FFIClause>?> {
+    let clauses = segmentClauses(session: session)
+
+    lengthPtr.pointee = Int32(clauses.count)
+    return toClauseListPointer(clauses)
+}
+
+@_silgen_name("ResizeClause")
+@MainActor public func resize_clause(
+    session: Int32,
+    index: Int32,
+    offset: Int32,
+    lengthPtr: UnsafeMutablePointer<Int32>
+) -> UnsafeMutablePointer<UnsafeMutablePointer<FFIClause>?> {
+    let clauses = segmentClauses(session: session)
+    let index = Int(index)
+
+    if index < clauses.count {
+        // the clause can take the characters up to the end of the text
+        let available = clauses[index...].reduce(0) { $0 + $1.reading.count }
+        let length = min(max(clauses[index].reading.count + Int(offset), 1), available)
+        clauseLengths[session] = clauses[..<index].map { $0.reading.count } + [length]
+        segmentedClauses[session] = nil
+    }
+
+    let resized = segmentClauses(session: session)
+
+    lengthPtr.pointee = Int32(resized.count)
+    return toClauseListPointer(resized)
+}
+
+@_silgen_name("GetClauseCandidates")
+@MainActor public func get_clause_candidates(
+    session: Int32,
+    index: Int32,
+    lengthPtr: UnsafeMutablePointer<Int32>
+) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
+    let clauses = segmentClauses(session: session)
+    let index = Int(index)
+
+    guard index < clauses.count else {
+        lengthPtr.pointee = 0
+        return to_list_pointer([])
+    }
+
+    let clause = clauses[index]
+    let options = getOptions(context: contexts[session] ?? "")
+    let converted = converter.requestCandidates(clause.composingText, options: options)
+    converter.stopComposition()
+    // keep the candidates of the other clauses, since all the clauses are committed at once
+    lastCandidates[session, default: []].append(contentsOf: converted.mainResults)
+
+    // only the candidates which cover the whole clause
+    var result: [FFICandidate] = []
+    for (rank, candidate) in converted.mainResults.enumerated() where candidate.correspondingCount == clause.correspondingCount {
+        result.append(toFFICandidate(candidate, rank: rank, text: candidate.text, subtext: "", hiragana: clause.reading, correspondingCount: clause.correspondingCount))
+    }
+
+    lengthPtr.pointee = Int32(result.count)
+    return to_list_pointer(result)
+}
+
+@_silgen_name("CommitCandidate")
+@MainActor public func commit_candidate(
+    session: Int32,
+    reading: UnsafePointer<CChar>,
+    surface: UnsafePointer<CChar>,
+    correspondingCount: Int32
+) {
+    guard getLearningType() == .inputAndOutput else {
+        return
+    }
+
+    let reading = String(cString: reading)
+    let surface = String(cString: surface)
+    let candidates = lastCandidates[session] ?? []
+
+    // only the candidates made by the converter are learned, the edited text is not
+    guard let candidate = candidates.first(where: {
+        $0.correspondingCount == Int(correspondingCount)
+            && ($0.text == surface || constructCandidateString(candidate: $0, hiragana: reading) == surface)
+    }) else {
+        return
+    }
+
+    converter.updateLearningData(candidate)
+    converter.commitUpdateLearningData()
+}
+
+@_silgen_name("ClearLearningHistory")
+@MainActor public func clear_learning_history() {
+    converter.sendToDicdataStore(.resetMemory)
+}
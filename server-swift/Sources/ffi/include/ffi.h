@@ -10,4 +10,13 @@ struct FFICandidate {
     char *subtext;
     char *hiragana;
     int correspondingCount;
-};
\ No newline at end of file
+    int source; // CandidateSource in service.proto
+    char *annotation;
+    float score;
+};
+
+struct FFIClause {
+    char *reading;
+    char *surface;
+    int correspondingCount;
+};